@@ -1,3 +1,5 @@
+#![allow(clippy::duplicate_mod)]
+
 use criterion::criterion_main;
 
 mod bench_bq;
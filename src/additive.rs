@@ -0,0 +1,248 @@
+//! # Shared Machinery for Additive Quantizers
+//!
+//! This module contains helpers shared by quantizers that approximate a vector as the sum of
+//! one codeword from each of several full-dimensional codebooks (additive, residual, and local
+//! search quantization). It provides reconstruction from codes, beam-search encoding, and a
+//! joint least-squares update of all codebooks for fixed codes.
+
+use crate::distances::Distance;
+use crate::exceptions::VqError;
+use crate::packing::{bits_for, PackedCodes};
+use crate::utils::lbg_quantize;
+use crate::vector::Vector;
+use nalgebra::DMatrix;
+use rayon::prelude::*;
+
+/// Regularization weight that pulls each codeword towards its previous value in the
+/// least-squares update. It keeps unused codewords unchanged and the system well conditioned.
+const RIDGE: f64 = 1e-3;
+
+/// Sums the selected codeword of each codebook.
+///
+/// `codes` may be shorter than `codebooks`, in which case only the first `codes.len()`
+/// codebooks contribute.
+pub(crate) fn reconstruct(codebooks: &[Vec<Vector<f32>>], codes: &[usize], dim: usize) -> Vec<f32> {
+    let mut sum = vec![0.0f32; dim];
+    for (codebook, &code) in codebooks.iter().zip(codes.iter()) {
+        for (s, &c) in sum.iter_mut().zip(codebook[code].data.iter()) {
+            *s += c;
+        }
+    }
+    sum
+}
+
+/// Packs one codeword index per codebook with the width needed for the largest codebook.
+pub(crate) fn pack_codes(codebooks: &[Vec<Vector<f32>>], codes: &[usize]) -> PackedCodes {
+    let k = codebooks.iter().map(|c| c.len()).max().unwrap_or(1);
+    PackedCodes::from_codes(bits_for(k), codes.iter().map(|&c| c as u16))
+}
+
+/// Unpacks codes and checks that they hold one in-range index per codebook.
+///
+/// # Panics
+/// Panics with a custom error if the number of codes does not equal the number of codebooks or
+/// a code is out of range for its codebook.
+pub(crate) fn unpack_codes(codebooks: &[Vec<Vector<f32>>], codes: &PackedCodes) -> Vec<usize> {
+    if codes.len() != codebooks.len() {
+        panic!(
+            "{}",
+            VqError::DimensionMismatch {
+                expected: codebooks.len(),
+                found: codes.len()
+            }
+        );
+    }
+    let codes: Vec<usize> = codes.iter().map(|c| c as usize).collect();
+    if codes
+        .iter()
+        .zip(codebooks.iter())
+        .any(|(&c, codebook)| c >= codebook.len())
+    {
+        panic!(
+            "{}",
+            VqError::InvalidParameter("Code is out of range for its codebook".to_string())
+        );
+    }
+    codes
+}
+
+/// Learns `m` codebooks greedily on residuals, as a residual quantizer would.
+///
+/// Each codebook is trained with the LBG algorithm (squared Euclidean distance) on the residuals
+/// left by the previous codebooks. This is the usual starting point for jointly trained
+/// additive codebooks.
+///
+/// # Panics
+/// Same conditions as `lbg_quantize`.
+pub(crate) fn residual_codebooks(
+    data: &[Vector<f32>],
+    m: usize,
+    k: usize,
+    max_iters: usize,
+    seed: u64,
+) -> Vec<Vec<Vector<f32>>> {
+    let distance = Distance::SquaredEuclidean;
+    let mut codebooks: Vec<Vec<Vector<f32>>> = Vec::with_capacity(m);
+    let mut residuals = data.to_vec();
+    for i in 0..m {
+        let (codebook, _) = lbg_quantize(
+            &residuals,
+            k,
+            max_iters,
+            &distance,
+            seed + i as u64,
+            None,
+            &|_, _| true,
+        );
+        residuals.par_iter_mut().for_each(|r| {
+            let (code, _) = beam_search(std::slice::from_ref(&codebook), &r.data, 1, &distance);
+            *r = &*r - &codebook[code[0]];
+        });
+        codebooks.push(codebook);
+    }
+    codebooks
+}
+
+/// Encodes a vector with beam search over the codebooks, taken in order.
+///
+/// The search keeps the `beam_width` best partial reconstructions after each codebook. Every
+/// partial reconstruction is extended with every codeword of the next codebook, and the
+/// candidates are ranked by the distance between the vector and the extended reconstruction.
+/// A beam width of 1 is the greedy stage-by-stage encoder used by residual quantization.
+///
+/// # Returns
+/// The selected codeword index for each codebook and the distance of the final reconstruction.
+pub(crate) fn beam_search(
+    codebooks: &[Vec<Vector<f32>>],
+    vector: &[f32],
+    beam_width: usize,
+    distance: &Distance,
+) -> (Vec<usize>, f32) {
+    let dim = vector.len();
+    // Each beam entry holds the codes so far, the partial reconstruction, and its distance.
+    let mut beam: Vec<(Vec<usize>, Vec<f32>, f32)> = vec![(
+        Vec::with_capacity(codebooks.len()),
+        vec![0.0; dim],
+        distance.compute(vector, &vec![0.0; dim]),
+    )];
+
+    for codebook in codebooks {
+        let mut candidates: Vec<(usize, usize, f32)> =
+            Vec::with_capacity(beam.len() * codebook.len());
+        for (b, (_, partial, _)) in beam.iter().enumerate() {
+            let mut extended = vec![0.0f32; dim];
+            for (j, codeword) in codebook.iter().enumerate() {
+                for ((e, &p), &c) in extended
+                    .iter_mut()
+                    .zip(partial.iter())
+                    .zip(codeword.data.iter())
+                {
+                    *e = p + c;
+                }
+                candidates.push((b, j, distance.compute(vector, &extended)));
+            }
+        }
+        let keep = beam_width.max(1).min(candidates.len());
+        candidates.select_nth_unstable_by(keep - 1, |a, b| a.2.partial_cmp(&b.2).unwrap());
+        candidates.truncate(keep);
+        candidates.sort_by(|a, b| a.2.partial_cmp(&b.2).unwrap());
+
+        beam = candidates
+            .into_iter()
+            .map(|(b, j, dist)| {
+                let (codes, partial, _) = &beam[b];
+                let mut codes = codes.clone();
+                codes.push(j);
+                let partial = partial
+                    .iter()
+                    .zip(codebook[j].data.iter())
+                    .map(|(&p, &c)| p + c)
+                    .collect();
+                (codes, partial, dist)
+            })
+            .collect();
+    }
+
+    let (codes, _, dist) = beam.swap_remove(0);
+    (codes, dist)
+}
+
+/// Updates all codebooks jointly by least squares for fixed codes.
+///
+/// With `B` the binary matrix that selects one codeword per codebook for every training vector
+/// and `X` the training data, the codewords `C` minimize `||X - B C||²`. The normal equations
+/// `(BᵀB + λI) C = BᵀX + λ C_prev` include a small ridge term towards the previous codewords,
+/// so codewords that no vector uses keep their value.
+///
+/// # Parameters
+/// - `data`: The training vectors.
+/// - `codes`: The codes of each training vector (one index per codebook).
+/// - `previous`: The current codebooks, which set the codebook sizes and the ridge target.
+///
+/// # Returns
+/// The updated codebooks.
+pub(crate) fn least_squares_codebooks(
+    data: &[Vector<f32>],
+    codes: &[Vec<usize>],
+    previous: &[Vec<Vector<f32>>],
+) -> Vec<Vec<Vector<f32>>> {
+    let dim = data[0].len();
+    let offsets: Vec<usize> = previous
+        .iter()
+        .scan(0, |acc, c| {
+            let offset = *acc;
+            *acc += c.len();
+            Some(offset)
+        })
+        .collect();
+    let total: usize = previous.iter().map(|c| c.len()).sum();
+
+    let mut gram = DMatrix::<f64>::zeros(total, total);
+    let mut rhs = DMatrix::<f64>::zeros(total, dim);
+    for (x, code) in data.iter().zip(codes.iter()) {
+        let rows: Vec<usize> = code
+            .iter()
+            .zip(offsets.iter())
+            .map(|(&c, &o)| o + c)
+            .collect();
+        for &r in &rows {
+            for &s in &rows {
+                gram[(r, s)] += 1.0;
+            }
+            for (d, &value) in x.data.iter().enumerate() {
+                rhs[(r, d)] += value as f64;
+            }
+        }
+    }
+    for (i, codebook) in previous.iter().enumerate() {
+        for (j, codeword) in codebook.iter().enumerate() {
+            let r = offsets[i] + j;
+            gram[(r, r)] += RIDGE;
+            for (d, &value) in codeword.data.iter().enumerate() {
+                rhs[(r, d)] += RIDGE * value as f64;
+            }
+        }
+    }
+
+    // The Gram matrix plus the ridge term is symmetric positive definite.
+    let solution = match gram.clone().cholesky() {
+        Some(cholesky) => cholesky.solve(&rhs),
+        None => gram
+            .lu()
+            .solve(&rhs)
+            .expect("Least-squares codebook update failed"),
+    };
+
+    previous
+        .iter()
+        .enumerate()
+        .map(|(i, codebook)| {
+            (0..codebook.len())
+                .map(|j| {
+                    let r = offsets[i] + j;
+                    Vector::new((0..dim).map(|d| solution[(r, d)] as f32).collect())
+                })
+                .collect()
+        })
+        .collect()
+}
@@ -0,0 +1,210 @@
+//! # Additive Quantizer Implementation
+//!
+//! This module implements Additive Quantization (AQ). Like a residual quantizer, AQ approximates
+//! an input vector as the sum of one codeword from each of `m` full-dimensional codebooks.
+//! Unlike a residual quantizer, the codebooks are trained jointly, and encoding uses beam
+//! search over codeword combinations instead of greedy stage-by-stage selection.
+//!
+//! Training starts from residual codebooks learned with the Linde-Buzo-Gray (LBG) algorithm and
+//! then alternates between two steps:
+//! - Encoding every training vector with beam search (keeping its previous codes if they are
+//!   better), and
+//! - Updating all codebooks jointly by least squares for the fixed codes.
+//!
+//! Both steps minimize the squared Euclidean reconstruction error, which is the metric AQ is
+//! defined for. At the same code size (`m` codes of `k` entries), AQ reaches a lower error than
+//! product and residual quantization at the cost of slower encoding.
+//!
+//! # Errors
+//! The `fit` and `quantize` methods panic with custom errors from the exceptions module when:
+//! - The training data is empty.
+//! - `m`, `k`, or `beam_width` is zero, or there are fewer training vectors than `k`.
+//! - The input vector's dimension does not match the training data.
+//!
+//! # Example
+//! ```
+//! use vq::vector::Vector;
+//! use vq::aq::AdditiveQuantizer;
+//!
+//! let training_data: Vec<Vector<f32>> = (0..50)
+//!     .map(|i| Vector::new(vec![i as f32, (i % 5) as f32, (i % 3) as f32]))
+//!     .collect();
+//!
+//! // Two codebooks of 4 codewords, 10 LBG iterations for initialization,
+//! // 3 AQ iterations, and a beam width of 4.
+//! let aq = AdditiveQuantizer::fit(&training_data, 2, 4, 10, 3, 4, 42);
+//!
+//! let input = Vector::new(vec![7.0, 2.0, 1.0]);
+//! let codes = aq.encode(&input);
+//! let reconstruction = aq.decode(&codes);
+//! let quantized = aq.quantize(&input);
+//! println!("Codes: {:?}, reconstruction: {}, quantized: {}", codes, reconstruction, quantized);
+//! ```
+
+use crate::additive::{
+    beam_search, least_squares_codebooks, pack_codes, reconstruct, residual_codebooks, unpack_codes,
+};
+use crate::distances::Distance;
+use crate::exceptions::VqError;
+use crate::packing::PackedCodes;
+use crate::vector::Vector;
+use half::f16;
+use rayon::prelude::*;
+
+/// An additive quantizer with jointly trained codebooks and beam-search encoding.
+pub struct AdditiveQuantizer {
+    /// A vector of `m` full-dimensional codebooks. Each codebook is a vector of centroids.
+    codebooks: Vec<Vec<Vector<f32>>>,
+    /// Dimensionality of the input vectors.
+    dim: usize,
+    /// The number of partial reconstructions kept after each codebook during encoding.
+    beam_width: usize,
+}
+
+impl AdditiveQuantizer {
+    /// Constructs a new `AdditiveQuantizer` from training data.
+    ///
+    /// # Parameters
+    /// - `training_data`: A slice of training vectors (`Vector<f32>`) used to learn the codebooks.
+    /// - `m`: The number of codebooks (codes per vector).
+    /// - `k`: The number of codewords per codebook.
+    /// - `max_iters`: The maximum number of LBG iterations used to initialize each codebook.
+    /// - `aq_iters`: The number of alternations between beam-search encoding and the joint
+    ///   codebook update.
+    /// - `beam_width`: The number of partial reconstructions kept during beam search.
+    /// - `seed`: A random seed for the LBG initialization (codebook `i` uses `seed + i`).
+    ///
+    /// # Panics
+    /// Panics with a custom error if:
+    /// - `training_data` is empty.
+    /// - `m` or `beam_width` is zero.
+    /// - `k` is zero or larger than the number of training vectors.
+    pub fn fit(
+        training_data: &[Vector<f32>],
+        m: usize,
+        k: usize,
+        max_iters: usize,
+        aq_iters: usize,
+        beam_width: usize,
+        seed: u64,
+    ) -> Self {
+        if training_data.is_empty() {
+            panic!("{}", VqError::EmptyInput);
+        }
+        if m == 0 {
+            panic!(
+                "{}",
+                VqError::InvalidParameter("m must be greater than 0".to_string())
+            );
+        }
+        if beam_width == 0 {
+            panic!(
+                "{}",
+                VqError::InvalidParameter("beam_width must be greater than 0".to_string())
+            );
+        }
+        let dim = training_data[0].len();
+        let distance = Distance::SquaredEuclidean;
+
+        // --- Initialization ---
+        // Learn residual codebooks greedily, as a residual quantizer would.
+        let mut codebooks = residual_codebooks(training_data, m, k, max_iters, seed);
+        let mut codes: Vec<Vec<usize>> = training_data
+            .par_iter()
+            .map(|v| beam_search(&codebooks, &v.data, 1, &distance).0)
+            .collect();
+
+        // --- Joint Refinement ---
+        for _ in 0..aq_iters {
+            codebooks = least_squares_codebooks(training_data, &codes, &codebooks);
+            codes = training_data
+                .par_iter()
+                .zip(codes.par_iter())
+                .map(|(v, previous)| {
+                    let (candidate, candidate_dist) =
+                        beam_search(&codebooks, &v.data, beam_width, &distance);
+                    let previous_dist =
+                        distance.compute(&v.data, &reconstruct(&codebooks, previous, dim));
+                    if candidate_dist <= previous_dist {
+                        candidate
+                    } else {
+                        previous.clone()
+                    }
+                })
+                .collect();
+        }
+
+        Self {
+            codebooks,
+            dim,
+            beam_width,
+        }
+    }
+
+    /// Encodes an input vector as one codeword index per codebook using beam search.
+    ///
+    /// # Parameters
+    /// - `vector`: The input vector (`Vector<f32>`) to encode.
+    ///
+    /// # Returns
+    /// The `m` codeword indices, packed with `ceil(log2(k))` bits each.
+    ///
+    /// # Panics
+    /// Panics with a custom error if the input vector's dimension does not match the training data.
+    pub fn encode(&self, vector: &Vector<f32>) -> PackedCodes {
+        pack_codes(&self.codebooks, &self.encode_indices(vector))
+    }
+
+    /// Selects one codeword index per codebook for `vector`.
+    fn encode_indices(&self, vector: &Vector<f32>) -> Vec<usize> {
+        if vector.len() != self.dim {
+            panic!(
+                "{}",
+                VqError::DimensionMismatch {
+                    expected: self.dim,
+                    found: vector.len()
+                }
+            );
+        }
+        beam_search(
+            &self.codebooks,
+            &vector.data,
+            self.beam_width,
+            &Distance::SquaredEuclidean,
+        )
+        .0
+    }
+
+    /// Reconstructs a vector from its codes by summing the selected codewords.
+    ///
+    /// # Panics
+    /// Panics with a custom error if the number of codes does not equal `m` or a code is out of range.
+    pub fn decode(&self, codes: &PackedCodes) -> Vector<f32> {
+        let codes = unpack_codes(&self.codebooks, codes);
+        Vector::new(reconstruct(&self.codebooks, &codes, self.dim))
+    }
+
+    /// Quantizes an input vector by encoding it with beam search and summing the selected codewords.
+    ///
+    /// # Parameters
+    /// - `vector`: The input vector (`Vector<f32>`) to quantize.
+    ///
+    /// # Returns
+    /// The reconstruction as a half-precision vector (`Vector<f16>`).
+    ///
+    /// # Panics
+    /// Panics with a custom error if the input vector's dimension does not match the training data.
+    pub fn quantize(&self, vector: &Vector<f32>) -> Vector<f16> {
+        let codes = self.encode_indices(vector);
+        let quantized: Vec<f16> = reconstruct(&self.codebooks, &codes, self.dim)
+            .into_iter()
+            .map(f16::from_f32)
+            .collect();
+        Vector::new(quantized)
+    }
+
+    /// Returns the learned codebooks.
+    pub fn codebooks(&self) -> &[Vec<Vector<f32>>] {
+        &self.codebooks
+    }
+}
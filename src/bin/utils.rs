@@ -86,11 +86,7 @@ pub fn calculate_recall(original: &[Vector<f32>], approx: &[Vector<f32>], k: usi
         let query = &original[i];
         let search_window = if n_samples > 10_000 { 5000 } else { n_samples };
 
-        let start_idx = if i > search_window / 2 {
-            i - search_window / 2
-        } else {
-            0
-        };
+        let start_idx = i.saturating_sub(search_window / 2);
         let end_idx = (i + search_window / 2).min(n_samples);
 
         // True neighbors using original data.
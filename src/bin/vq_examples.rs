@@ -46,6 +46,14 @@ fn example_quantizers(training_data: &[Vector<f32>], test_vector: &Vector<f32>)
     example_opq(training_data, test_vector);
     example_tsvq(training_data, test_vector);
     example_rvq(training_data, test_vector);
+    example_aq(training_data, test_vector);
+    example_lsq(training_data, test_vector);
+    example_rabitq(training_data, test_vector);
+    example_lattice(test_vector);
+    example_lvq(training_data, test_vector);
+    example_ternary(training_data, test_vector);
+    example_lloyd_max(training_data, test_vector);
+    example_fp8(training_data, test_vector);
 }
 
 /// Example: Binary Quantizer (BQ).
@@ -85,7 +93,7 @@ fn example_pq(training_data: &[Vector<f32>], test_vector: &Vector<f32>) {
         2,                   // Number of subquantizers.
         2,                   // Number of centroids per subquantizer.
         20,                  // Maximum iterations.
-        Distance::Euclidean, // Distance metric to use for quantization.
+        Distance::Euclidean, // Distance metric for node distortions.
         33,                  // Seed for random number generation.
     );
     let quantized = pq.quantize(test_vector);
@@ -102,7 +110,7 @@ fn example_opq(training_data: &[Vector<f32>], test_vector: &Vector<f32>) {
         2,                   // Number of centroids per subquantizer.
         20,                  // Maximum iterations for PQ.
         5,                   // Maximum iterations for OPQ-specific optimization.
-        Distance::Euclidean, // Distance metric to use for quantization.
+        Distance::Euclidean, // Distance metric for node distortions.
         43,                  // Seed for random number generation.
     );
     let quantized = opq.quantize(test_vector);
@@ -116,7 +124,7 @@ fn example_tsvq(training_data: &[Vector<f32>], test_vector: &Vector<f32>) {
     let tsvq = TSVQ::new(
         training_data,       // Training data.
         3,                   // Maximum tree depth.
-        Distance::Euclidean, // Distance metric to use for quantization.
+        Distance::Euclidean, // Distance metric for node distortions.
     );
     let quantized = tsvq.quantize(test_vector);
     println!("Tree-Structured Quantizer output: {}", quantized);
@@ -132,9 +140,130 @@ fn example_rvq(training_data: &[Vector<f32>], test_vector: &Vector<f32>) {
         2,                   // Number of centroids per stage.
         20,                  // Maximum iterations.
         10e-6,               // Error threshold.
-        Distance::Euclidean, // Distance metric to use for quantization.
+        Distance::Euclidean, // Distance metric for node distortions.
         53,                  // Seed for random number generation.
     );
     let quantized = rvq.quantize(test_vector);
     println!("Residual Quantizer output: {}", quantized);
 }
+
+/// Example: Additive Quantizer (AQ).
+/// Approximates the vector as a sum of jointly trained codewords found with beam search.
+fn example_aq(training_data: &[Vector<f32>], test_vector: &Vector<f32>) {
+    use vq::aq::AdditiveQuantizer;
+    // Fit an AQ with 2 codebooks, refined jointly for 3 iterations.
+    let aq = AdditiveQuantizer::fit(
+        training_data, // Training data.
+        2,             // Number of codebooks.
+        2,             // Number of centroids per codebook.
+        20,            // Maximum iterations for the initial codebooks.
+        3,             // Number of joint refinement iterations.
+        4,             // Beam width used for encoding.
+        63,            // Seed for random number generation.
+    );
+    let quantized = aq.quantize(test_vector);
+    println!("Additive Quantizer output: {}", quantized);
+}
+
+/// Example: Local Search Quantizer (LSQ++).
+/// Approximates the vector as a sum of codewords found with iterated local search.
+fn example_lsq(training_data: &[Vector<f32>], test_vector: &Vector<f32>) {
+    use vq::lsq::LocalSearchQuantizer;
+    // Fit an LSQ with 2 codebooks, refined for 3 iterations.
+    let lsq = LocalSearchQuantizer::fit(
+        training_data, // Training data.
+        2,             // Number of codebooks.
+        2,             // Number of centroids per codebook.
+        20,            // Maximum iterations for the initial codebooks.
+        3,             // Number of LSQ iterations.
+        4,             // Number of local search rounds used for encoding.
+        73,            // Seed for random number generation.
+    );
+    let quantized = lsq.quantize(test_vector);
+    println!("Local Search Quantizer output: {}", quantized);
+}
+
+/// Example: RaBitQ Quantizer.
+/// Stores one bit per dimension of a randomly rotated vector and estimates distances with bounds.
+fn example_rabitq(training_data: &[Vector<f32>], test_vector: &Vector<f32>) {
+    use vq::rabitq::RaBitQuantizer;
+    let quantizer = RaBitQuantizer::fit(
+        training_data, // Training data (used for the centroid).
+        79,            // Seed for the random rotation.
+    );
+    let code = quantizer.encode(&training_data[0]);
+    let query = quantizer.prepare_query(test_vector);
+    let estimate = quantizer.estimate_distance(&code, &query);
+    println!(
+        "RaBitQ estimated squared distance: {} (between {} and {})",
+        estimate.distance, estimate.lower, estimate.upper
+    );
+}
+
+/// Example: Lattice Quantizer.
+/// Rounds blocks of the vector to the nearest point of a scaled lattice, without training.
+fn example_lattice(v: &Vector<f32>) {
+    use vq::lattice::{Lattice, LatticeQuantizer};
+    let quantizer = LatticeQuantizer::new(
+        Lattice::D(2), // Checkerboard lattice on blocks of 2 dimensions.
+        v.len(),       // Vector dimension.
+        1.0,           // Lattice spacing.
+        128,           // Largest squared norm of the enumerated points.
+    );
+    let quantized = quantizer.quantize(v);
+    println!("Lattice Quantizer output: {}", quantized);
+}
+
+/// Example: Locally-adaptive Vector Quantizer (LVQ).
+/// Scalar-quantizes each centred vector with its own scale and bias.
+fn example_lvq(training_data: &[Vector<f32>], test_vector: &Vector<f32>) {
+    use vq::lvq::LocallyAdaptiveQuantizer;
+    let lvq = LocallyAdaptiveQuantizer::fit(
+        training_data, // Training data (used for the mean).
+        4,             // Bits per component of the primary codes.
+        Some(4),       // Bits per component of the residual codes.
+    );
+    let quantized = lvq.quantize(test_vector);
+    println!("Locally-adaptive Vector Quantizer output: {}", quantized);
+}
+
+/// Example: Ternary Quantizer.
+/// Maps values to -1, 0, or +1 using learned dead-zone thresholds.
+fn example_ternary(training_data: &[Vector<f32>], test_vector: &Vector<f32>) {
+    use vq::ternary::{TernaryPacking, TernaryQuantizer, ThresholdMode};
+    let quantizer = TernaryQuantizer::fit(
+        training_data,               // Training data.
+        ThresholdMode::PerDimension, // Learn one threshold per dimension.
+        TernaryPacking::Base3,       // Pack five values per byte.
+    );
+    let quantized = quantizer.quantize(test_vector);
+    println!("Ternary Quantizer output: {}", quantized);
+}
+
+/// Example: Lloyd-Max Scalar Quantizer.
+/// Learns non-uniform levels for each dimension from the training data.
+fn example_lloyd_max(training_data: &[Vector<f32>], test_vector: &Vector<f32>) {
+    use vq::lloyd_max::LloydMaxQuantizer;
+    let quantizer = LloydMaxQuantizer::fit(
+        training_data, // Training data.
+        4,             // Number of quantization levels.
+        20,            // Maximum Lloyd-Max iterations.
+        true,          // Learn separate levels for each dimension.
+    );
+    let quantized = quantizer.quantize(test_vector);
+    println!("Lloyd-Max Quantizer output: {}", quantized);
+}
+
+/// Example: FP8 Quantizer.
+/// Stores each value as a scaled 8-bit float (E4M3).
+fn example_fp8(training_data: &[Vector<f32>], test_vector: &Vector<f32>) {
+    use vq::fp8::{Fp8Format, Fp8Quantizer, Fp8Scaling};
+    let quantizer = Fp8Quantizer::fit(
+        training_data,         // Training data (used for the scale).
+        Fp8Format::E4M3,       // 4 exponent bits and 3 mantissa bits.
+        Fp8Scaling::PerTensor, // One scale for all vectors.
+    );
+    let code = quantizer.encode(test_vector);
+    let dequantized = quantizer.decode(&code);
+    println!("FP8 Quantizer output: {}", dequantized);
+}
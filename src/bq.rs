@@ -17,9 +17,14 @@
 //! let input = Vector::new(vec![0.3, 0.5, 0.8]);
 //! let quantized = quantizer.quantize(&input);
 //! // quantized now contains [0, 1, 1]
+//!
+//! // The same decisions packed with one bit per element.
+//! let codes = quantizer.encode(&input);
+//! assert_eq!(codes.to_vec(), vec![0, 1, 1]);
 //! ```
 
 use crate::exceptions::VqError;
+use crate::packing::PackedCodes;
 use crate::vector::{Vector, PARALLEL_THRESHOLD};
 use rayon::prelude::*;
 
@@ -102,4 +107,22 @@ impl BinaryQuantizer {
         };
         Vector::new(quantized_vector)
     }
+
+    /// Encodes an input vector as one bit per element: 1 if the element is at or above the
+    /// threshold and 0 otherwise.
+    ///
+    /// Unlike `quantize`, which stores one `u8` level per element, the bits are packed
+    /// (64 elements per word).
+    ///
+    /// # Parameters
+    /// - `vector`: A reference to the input vector (`Vector<f32>`) to encode.
+    ///
+    /// # Returns
+    /// The packed 1-bit codes (`PackedCodes`).
+    pub fn encode(&self, vector: &Vector<f32>) -> PackedCodes {
+        PackedCodes::from_codes(
+            1,
+            vector.data.iter().map(|&x| u16::from(x >= self.threshold)),
+        )
+    }
 }
@@ -70,6 +70,7 @@ where
 }
 
 /// Enum listing the available distance metrics.
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Distance {
     /// Squared Euclidean distance (sum of squared differences).
     SquaredEuclidean,
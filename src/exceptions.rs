@@ -22,6 +22,14 @@ pub enum VqError {
     /// Indicates that a metric-specific parameter is invalid.
     #[error("Invalid metric parameter for {metric}: {details}")]
     InvalidMetricParameter { metric: String, details: String },
+
+    /// Indicates that a distance metric cannot be used for the requested operation.
+    #[error("Unsupported distance metric {metric}: {details}")]
+    UnsupportedDistance { metric: String, details: String },
+
+    /// Indicates that training was stopped by a `TrainingObserver`.
+    #[error("Training was cancelled by the observer.")]
+    Cancelled,
 }
 
 /// A convenience result type for operations in the `Vq` library.
@@ -0,0 +1,296 @@
+//! # FP8 Quantizer Implementation
+//!
+//! This module provides conversion between `f32` values and 8-bit floating-point (FP8) values in
+//! the two OCP formats:
+//! - `E4M3`: 1 sign bit, 4 exponent bits (bias 7), and 3 mantissa bits. It has no infinities,
+//!   `S.1111.111` is NaN, and the largest finite value is 448.
+//! - `E5M2`: 1 sign bit, 5 exponent bits (bias 15), and 2 mantissa bits. It follows the IEEE 754
+//!   conventions for infinities and NaN, and the largest finite value is 57344.
+//!
+//! Conversions round to the nearest representable value (ties to even) and saturate: values
+//! beyond the largest finite value, including infinities, become the largest finite value of the
+//! same sign. NaN is encoded as `0x7F` (or `0xFF` for a negative NaN) and decodes back to NaN.
+//!
+//! Because FP8 has a narrow range, values are divided by a scale before conversion so that the
+//! largest magnitude maps to the largest finite FP8 value. The scale is either learned once from
+//! the training data (per tensor) or computed for each encoded vector (per vector). Non-finite
+//! values are ignored when computing scales.
+//!
+//! # Errors
+//! The methods panic with custom errors from the exceptions module when:
+//! - The training data is empty.
+//! - An input vector's dimension or a code does not match the quantizer.
+//!
+//! # Example
+//! ```
+//! use vq::vector::Vector;
+//! use vq::fp8::{Fp8Format, Fp8Quantizer, Fp8Scaling};
+//!
+//! assert_eq!(Fp8Format::E4M3.encode_value(1.0), 0x38);
+//! assert_eq!(Fp8Format::E5M2.decode_value(0x3C), 1.0);
+//! // Saturating conversion.
+//! assert_eq!(Fp8Format::E4M3.encode_value(1000.0), 0x7E);
+//!
+//! let training_data: Vec<Vector<f32>> = (0..50)
+//!     .map(|i| Vector::new(vec![i as f32 * 0.1, -(i as f32) * 0.02, 3.0]))
+//!     .collect();
+//! let quantizer = Fp8Quantizer::fit(&training_data, Fp8Format::E4M3, Fp8Scaling::PerTensor);
+//!
+//! let input = Vector::new(vec![1.5, -0.4, 2.9]);
+//! let code = quantizer.encode(&input);
+//! let reconstruction = quantizer.decode(&code);
+//! println!("FP8 bytes: {:?}, reconstruction: {}", code.codes, reconstruction);
+//! ```
+
+use crate::exceptions::VqError;
+use crate::vector::Vector;
+
+/// An 8-bit floating-point format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fp8Format {
+    /// 4 exponent bits and 3 mantissa bits, without infinities (largest value 448).
+    E4M3,
+    /// 5 exponent bits and 2 mantissa bits, with infinities (largest value 57344).
+    E5M2,
+}
+
+impl Fp8Format {
+    /// Returns the number of mantissa bits.
+    pub fn mantissa_bits(&self) -> u32 {
+        match self {
+            Fp8Format::E4M3 => 3,
+            Fp8Format::E5M2 => 2,
+        }
+    }
+
+    /// Returns the exponent bias.
+    pub fn bias(&self) -> i32 {
+        match self {
+            Fp8Format::E4M3 => 7,
+            Fp8Format::E5M2 => 15,
+        }
+    }
+
+    /// Returns the largest finite value.
+    pub fn max_value(&self) -> f32 {
+        self.decode_value(self.max_code())
+    }
+
+    /// Converts an `f32` value to FP8 with rounding to nearest (ties to even) and saturation.
+    ///
+    /// # Returns
+    /// The FP8 bit pattern. NaN becomes `0x7F` with the sign of the input.
+    pub fn encode_value(&self, x: f32) -> u8 {
+        let sign = if x.is_sign_negative() { 0x80 } else { 0 };
+        if x.is_nan() {
+            return sign | 0x7F;
+        }
+        let a = x.abs();
+        if a == 0.0 {
+            return sign;
+        }
+        if a.is_infinite() {
+            return sign | self.max_code();
+        }
+        let mbits = self.mantissa_bits();
+        let min_exp = 1 - self.bias();
+        // Values below the smallest normal number share its exponent and become subnormal.
+        let mut exp = (a.log2().floor() as i32).max(min_exp);
+        let step = (2.0f32).powi(exp - mbits as i32);
+        let mut q = (a / step).round_ties_even() as u32;
+        if q == 1 << (mbits + 1) {
+            // Rounding carried into the next binade.
+            exp += 1;
+            q = 1 << mbits;
+        }
+        let magnitude = if q < 1 << mbits {
+            q
+        } else {
+            (((exp + self.bias()) as u32) << mbits) | (q - (1 << mbits))
+        };
+        // Codes are ordered by magnitude, so anything above the largest finite code saturates.
+        sign | magnitude.min(self.max_code() as u32) as u8
+    }
+
+    /// Converts an FP8 bit pattern to `f32`.
+    pub fn decode_value(&self, code: u8) -> f32 {
+        let mbits = self.mantissa_bits();
+        let sign = if code & 0x80 != 0 { -1.0 } else { 1.0 };
+        let exp_field = ((code & 0x7F) >> mbits) as i32;
+        let mantissa = (code & ((1 << mbits) - 1)) as f32;
+        let max_exp_field = (1 << (7 - mbits)) - 1;
+        match self {
+            Fp8Format::E4M3 if code & 0x7F == 0x7F => return f32::NAN,
+            Fp8Format::E5M2 if exp_field == max_exp_field => {
+                return if mantissa == 0.0 {
+                    sign * f32::INFINITY
+                } else {
+                    f32::NAN
+                };
+            }
+            _ => {}
+        }
+        let scale = (1 << mbits) as f32;
+        if exp_field == 0 {
+            sign * mantissa / scale * (2.0f32).powi(1 - self.bias())
+        } else {
+            sign * (1.0 + mantissa / scale) * (2.0f32).powi(exp_field - self.bias())
+        }
+    }
+
+    /// Returns the bit pattern of the largest finite value.
+    fn max_code(&self) -> u8 {
+        match self {
+            Fp8Format::E4M3 => 0x7E,
+            Fp8Format::E5M2 => 0x7B,
+        }
+    }
+}
+
+/// How FP8 scaling factors are chosen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fp8Scaling {
+    /// One scale learned from the largest magnitude in the training data.
+    PerTensor,
+    /// A scale computed from the largest magnitude of each encoded vector.
+    PerVector,
+}
+
+/// A vector encoded by `Fp8Quantizer::encode`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fp8Code {
+    /// The FP8 bit pattern of each scaled component.
+    pub codes: Vec<u8>,
+    /// The scale that the components were divided by before conversion.
+    pub scale: f32,
+}
+
+/// A quantizer that stores each component as a scaled FP8 value.
+pub struct Fp8Quantizer {
+    /// The FP8 format of the codes.
+    format: Fp8Format,
+    /// How scales are chosen.
+    scaling: Fp8Scaling,
+    /// The scale learned from the training data (used in per-tensor mode).
+    scale: f32,
+    /// Dimensionality of the input vectors.
+    dim: usize,
+    /// The `f32` value of every FP8 bit pattern.
+    table: Vec<f32>,
+}
+
+impl Fp8Quantizer {
+    /// Creates a new `Fp8Quantizer` from training data.
+    ///
+    /// # Parameters
+    /// - `training_data`: A slice of training vectors (`Vector<f32>`).
+    /// - `format`: The FP8 format of the codes.
+    /// - `scaling`: Whether to use one scale learned from the training data or one scale per vector.
+    ///
+    /// # Panics
+    /// Panics with a custom error if `training_data` is empty or the vectors have different dimensions.
+    pub fn fit(training_data: &[Vector<f32>], format: Fp8Format, scaling: Fp8Scaling) -> Self {
+        if training_data.is_empty() {
+            panic!("{}", VqError::EmptyInput);
+        }
+        let dim = training_data[0].len();
+        if let Some(v) = training_data.iter().find(|v| v.len() != dim) {
+            panic!(
+                "{}",
+                VqError::DimensionMismatch {
+                    expected: dim,
+                    found: v.len()
+                }
+            );
+        }
+        let max_abs = training_data
+            .iter()
+            .map(|v| max_abs(&v.data))
+            .fold(0.0, f32::max);
+
+        Self {
+            format,
+            scaling,
+            scale: scale_for(max_abs, format),
+            dim,
+            table: (0..=255u8).map(|c| format.decode_value(c)).collect(),
+        }
+    }
+
+    /// Scales a vector and converts each component to FP8.
+    ///
+    /// # Parameters
+    /// - `vector`: The input vector (`Vector<f32>`) to encode.
+    ///
+    /// # Returns
+    /// An `Fp8Code` holding one FP8 byte per component and the scale that was used.
+    ///
+    /// # Panics
+    /// Panics with a custom error if the input vector's dimension does not match the training data.
+    pub fn encode(&self, vector: &Vector<f32>) -> Fp8Code {
+        self.check_dim(vector.len());
+        let scale = match self.scaling {
+            Fp8Scaling::PerTensor => self.scale,
+            Fp8Scaling::PerVector => scale_for(max_abs(&vector.data), self.format),
+        };
+        let codes = vector
+            .data
+            .iter()
+            .map(|&x| self.format.encode_value(x / scale))
+            .collect();
+        Fp8Code { codes, scale }
+    }
+
+    /// Converts FP8 codes back to `f32` and multiplies them by their scale.
+    ///
+    /// # Panics
+    /// Panics with a custom error if the number of codes does not match the quantizer's dimension.
+    pub fn decode(&self, code: &Fp8Code) -> Vector<f32> {
+        self.check_dim(code.codes.len());
+        let data = code
+            .codes
+            .iter()
+            .map(|&c| self.table[c as usize] * code.scale)
+            .collect();
+        Vector::new(data)
+    }
+
+    /// Returns the FP8 format of the codes.
+    pub fn format(&self) -> Fp8Format {
+        self.format
+    }
+
+    /// Returns the scale learned from the training data.
+    pub fn scale(&self) -> f32 {
+        self.scale
+    }
+
+    fn check_dim(&self, len: usize) {
+        if len != self.dim {
+            panic!(
+                "{}",
+                VqError::DimensionMismatch {
+                    expected: self.dim,
+                    found: len
+                }
+            );
+        }
+    }
+}
+
+/// Returns the largest finite magnitude of `values`, or zero if there is none.
+fn max_abs(values: &[f32]) -> f32 {
+    values
+        .iter()
+        .filter(|x| x.is_finite())
+        .fold(0.0, |m, &x| m.max(x.abs()))
+}
+
+/// Returns the scale that maps `max_abs` to the largest finite value of `format`.
+fn scale_for(max_abs: f32, format: Fp8Format) -> f32 {
+    if max_abs > 0.0 {
+        max_abs / format.max_value()
+    } else {
+        1.0
+    }
+}
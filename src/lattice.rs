@@ -0,0 +1,378 @@
+//! # Lattice Vector Quantizer Implementation
+//!
+//! This module implements a training-free lattice quantizer. The input vector is split into
+//! blocks, and each block is rounded to the nearest point of a scaled lattice using the
+//! closed-form algorithms of Conway and Sloane. Supported lattices are:
+//! - `Lattice::Z(n)`: the integer lattice `Zⁿ`, where each coordinate is rounded independently.
+//! - `Lattice::D(n)`: the checkerboard lattice `Dⁿ` of integer points with an even coordinate sum.
+//! - `Lattice::E8`: the Gosset lattice `E₈ = D₈ ∪ (D₈ + ½)`, applied to blocks of 8 dimensions.
+//!
+//! For fixed-rate codes, the lattice is truncated to the shells with squared norm at most
+//! `max_norm` (in lattice units, before scaling). These points are enumerated once, sorted by
+//! norm, and indexed, so each block is encoded as a single index. Blocks whose nearest lattice
+//! point lies outside the truncation are encoded as the nearest enumerated point instead.
+//!
+//! Because no training is involved, the quantizer suits streaming data whose distribution is
+//! not known in advance, and it serves as a baseline for the trained quantizers.
+//!
+//! # Errors
+//! The methods panic with custom errors from the exceptions module when:
+//! - The block dimension is zero or does not divide the vector dimension.
+//! - The scale is not positive and finite.
+//! - The truncated lattice has more than 65536 points, which do not fit in 16-bit codes.
+//! - An input vector's dimension or a code does not match the quantizer.
+//!
+//! # Example
+//! ```
+//! use vq::vector::Vector;
+//! use vq::lattice::{Lattice, LatticeQuantizer};
+//!
+//! // Quantize 16-dimensional vectors as two E8 blocks, keeping the shells up to squared norm 4.
+//! let quantizer = LatticeQuantizer::new(Lattice::E8, 16, 0.5, 4);
+//! assert_eq!(quantizer.codebook_size(), 2401);
+//!
+//! let input = Vector::new((0..16).map(|i| (i as f32 * 0.37).sin()).collect());
+//! let codes = quantizer.encode(&input);
+//! let reconstruction = quantizer.decode(&codes);
+//! println!("Codes: {:?}, reconstruction: {}", codes, reconstruction);
+//! ```
+
+use crate::exceptions::VqError;
+use crate::packing::{bits_for, PackedCodes};
+use crate::vector::Vector;
+use std::collections::HashMap;
+
+/// A lattice used by `LatticeQuantizer`, with the block dimension it applies to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Lattice {
+    /// The integer lattice `Zⁿ` on blocks of `n` dimensions.
+    Z(usize),
+    /// The checkerboard lattice `Dⁿ` (integer points with an even coordinate sum) on blocks of
+    /// `n` dimensions.
+    D(usize),
+    /// The Gosset lattice `E₈` on blocks of 8 dimensions.
+    E8,
+}
+
+impl Lattice {
+    /// Returns the number of dimensions of one lattice block.
+    pub fn block_dim(&self) -> usize {
+        match self {
+            Lattice::Z(n) | Lattice::D(n) => *n,
+            Lattice::E8 => 8,
+        }
+    }
+
+    /// Returns the lattice point closest to `x`, in doubled coordinates.
+    ///
+    /// Doubled coordinates (`2p`) keep the half-integer points of `E₈` exact as integers.
+    pub fn nearest_point(&self, x: &[f32]) -> Vec<i32> {
+        match self {
+            Lattice::Z(_) => x.iter().map(|&v| 2 * v.round() as i32).collect(),
+            Lattice::D(_) => nearest_d(x).into_iter().map(|v| 2 * v).collect(),
+            Lattice::E8 => {
+                // Try both cosets of D₈ and keep the closer point.
+                let even: Vec<i32> = nearest_d(x).into_iter().map(|v| 2 * v).collect();
+                let shifted: Vec<f32> = x.iter().map(|&v| v - 0.5).collect();
+                let odd: Vec<i32> = nearest_d(&shifted).into_iter().map(|v| 2 * v + 1).collect();
+                if doubled_distance(x, &even) <= doubled_distance(x, &odd) {
+                    even
+                } else {
+                    odd
+                }
+            }
+        }
+    }
+
+    /// Returns true if the doubled coordinates `y` describe a point of the lattice.
+    fn contains(&self, y: &[i32]) -> bool {
+        let all_even = y.iter().all(|v| v % 2 == 0);
+        let sum: i32 = y.iter().sum();
+        match self {
+            Lattice::Z(_) => all_even,
+            Lattice::D(_) => all_even && sum % 4 == 0,
+            Lattice::E8 => (all_even || y.iter().all(|v| v % 2 != 0)) && sum % 4 == 0,
+        }
+    }
+
+    /// Enumerates the lattice points with squared norm at most `max_norm`, in doubled
+    /// coordinates, sorted by norm and then lexicographically.
+    ///
+    /// # Panics
+    /// Panics with a custom error as soon as more than 65536 points are found.
+    fn enumerate(&self, max_norm: u32) -> Vec<Vec<i32>> {
+        let n = self.block_dim();
+        // In doubled coordinates the squared norm is four times larger.
+        let limit = 4 * max_norm as i64;
+        let bound = (limit as f64).sqrt().floor() as i32;
+        // Integer points have even doubled coordinates; the half-integer coset of E₈ has odd ones.
+        let parities: &[i32] = match self {
+            Lattice::Z(_) | Lattice::D(_) => &[0],
+            Lattice::E8 => &[0, 1],
+        };
+        let mut points = Vec::new();
+        for &parity in parities {
+            let values: Vec<i32> = (-bound..=bound)
+                .filter(|v| v.rem_euclid(2) == parity)
+                .collect();
+            let mut current = Vec::with_capacity(n);
+            enumerate_box(n, &values, limit, &mut current, 0, &mut |y| {
+                if self.contains(y) {
+                    if points.len() == 1 << 16 {
+                        panic!(
+                            "{}",
+                            VqError::InvalidParameter(
+                                "The truncated lattice has more than 65536 points, but codes are limited to 16 bits"
+                                    .to_string()
+                            )
+                        );
+                    }
+                    points.push(y.to_vec());
+                }
+            });
+        }
+        points.sort_by_key(|y| (squared_norm(y), y.clone()));
+        points
+    }
+}
+
+/// A fixed-rate lattice quantizer with shell truncation.
+pub struct LatticeQuantizer {
+    /// The lattice applied to each block.
+    lattice: Lattice,
+    /// The spacing of the lattice: a lattice point `p` represents the vector `scale · p`.
+    scale: f32,
+    /// Dimensionality of the input vectors.
+    dim: usize,
+    /// The enumerated lattice points in doubled coordinates, indexed by code.
+    codebook: Vec<Vec<i32>>,
+    /// Maps each enumerated lattice point (doubled coordinates) to its code.
+    index: HashMap<Vec<i32>, usize>,
+}
+
+impl LatticeQuantizer {
+    /// Creates a new `LatticeQuantizer`.
+    ///
+    /// # Parameters
+    /// - `lattice`: The lattice and block dimension. `Lattice::E8` uses blocks of 8 dimensions.
+    /// - `dim`: The dimensionality of the input vectors. It must be a multiple of the block dimension.
+    /// - `scale`: The lattice spacing. Smaller values give finer quantization but need more shells.
+    /// - `max_norm`: The largest squared norm (in lattice units) of the enumerated lattice points.
+    ///
+    /// # Panics
+    /// Panics with a custom error if:
+    /// - The block dimension is zero or does not divide `dim`, or `dim` is zero.
+    /// - `scale` is not positive and finite.
+    /// - The truncated lattice has more than 65536 points.
+    pub fn new(lattice: Lattice, dim: usize, scale: f32, max_norm: u32) -> Self {
+        let block_dim = lattice.block_dim();
+        if block_dim == 0 || dim == 0 || dim % block_dim != 0 {
+            panic!(
+                "{}",
+                VqError::InvalidParameter(format!(
+                    "Vector dimension {} must be a positive multiple of the block dimension {}",
+                    dim, block_dim
+                ))
+            );
+        }
+        if !(scale.is_finite() && scale > 0.0) {
+            panic!(
+                "{}",
+                VqError::InvalidParameter("Scale must be positive and finite".to_string())
+            );
+        }
+        let codebook = lattice.enumerate(max_norm);
+        let index = codebook
+            .iter()
+            .enumerate()
+            .map(|(i, y)| (y.clone(), i))
+            .collect();
+        Self {
+            lattice,
+            scale,
+            dim,
+            codebook,
+            index,
+        }
+    }
+
+    /// Returns the number of codes per block, i.e. the number of enumerated lattice points.
+    pub fn codebook_size(&self) -> usize {
+        self.codebook.len()
+    }
+
+    /// Returns the number of bits needed to store one block code.
+    pub fn bits_per_block(&self) -> u8 {
+        bits_for(self.codebook.len())
+    }
+
+    /// Rounds a vector to the nearest point of the scaled lattice, without shell truncation.
+    ///
+    /// # Panics
+    /// Panics with a custom error if the input vector's dimension does not match the quantizer.
+    pub fn nearest_point(&self, vector: &Vector<f32>) -> Vector<f32> {
+        self.check_dim(vector);
+        let data = vector
+            .data
+            .chunks(self.lattice.block_dim())
+            .flat_map(|block| self.to_vector(&self.lattice.nearest_point(&self.unscale(block))))
+            .collect();
+        Vector::new(data)
+    }
+
+    /// Encodes a vector as one index per block into the truncated lattice.
+    ///
+    /// # Parameters
+    /// - `vector`: The input vector (`Vector<f32>`) to encode.
+    ///
+    /// # Returns
+    /// The `dim / block_dim` block codes, packed with `bits_per_block()` bits each.
+    ///
+    /// # Panics
+    /// Panics with a custom error if the input vector's dimension does not match the quantizer.
+    pub fn encode(&self, vector: &Vector<f32>) -> PackedCodes {
+        self.check_dim(vector);
+        let codes = vector
+            .data
+            .chunks(self.lattice.block_dim())
+            .map(|block| {
+                let x = self.unscale(block);
+                let nearest = self.lattice.nearest_point(&x);
+                match self.index.get(&nearest) {
+                    Some(&code) => code,
+                    // The nearest point lies outside the truncation: search the enumerated points.
+                    None => self
+                        .codebook
+                        .iter()
+                        .enumerate()
+                        .map(|(i, y)| (i, doubled_distance(&x, y)))
+                        .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+                        .map(|(i, _)| i)
+                        .unwrap_or(0),
+                }
+            })
+            .map(|code| code as u16);
+        PackedCodes::from_codes(self.bits_per_block(), codes)
+    }
+
+    /// Reconstructs a vector from its block codes.
+    ///
+    /// # Panics
+    /// Panics with a custom error if the number of codes does not match the number of blocks or
+    /// a code is out of range.
+    pub fn decode(&self, codes: &PackedCodes) -> Vector<f32> {
+        let blocks = self.dim / self.lattice.block_dim();
+        if codes.len() != blocks {
+            panic!(
+                "{}",
+                VqError::DimensionMismatch {
+                    expected: blocks,
+                    found: codes.len()
+                }
+            );
+        }
+        if codes.iter().any(|c| c as usize >= self.codebook.len()) {
+            panic!(
+                "{}",
+                VqError::InvalidParameter("Code is out of range for the lattice".to_string())
+            );
+        }
+        Vector::new(
+            codes
+                .iter()
+                .flat_map(|c| self.to_vector(&self.codebook[c as usize]))
+                .collect(),
+        )
+    }
+
+    /// Quantizes a vector to the nearest point of the truncated, scaled lattice.
+    ///
+    /// # Panics
+    /// Panics with a custom error if the input vector's dimension does not match the quantizer.
+    pub fn quantize(&self, vector: &Vector<f32>) -> Vector<f32> {
+        self.decode(&self.encode(vector))
+    }
+
+    fn check_dim(&self, vector: &Vector<f32>) {
+        if vector.len() != self.dim {
+            panic!(
+                "{}",
+                VqError::DimensionMismatch {
+                    expected: self.dim,
+                    found: vector.len()
+                }
+            );
+        }
+    }
+
+    fn unscale(&self, block: &[f32]) -> Vec<f32> {
+        block.iter().map(|&v| v / self.scale).collect()
+    }
+
+    fn to_vector(&self, doubled: &[i32]) -> Vec<f32> {
+        doubled
+            .iter()
+            .map(|&v| v as f32 * 0.5 * self.scale)
+            .collect()
+    }
+}
+
+/// Returns the point of `Dⁿ` closest to `x` (Conway and Sloane, Algorithm 2).
+///
+/// Every coordinate is rounded to the nearest integer. If the coordinate sum is odd, the
+/// coordinate with the largest rounding error is rounded the other way instead.
+fn nearest_d(x: &[f32]) -> Vec<i32> {
+    let mut rounded: Vec<i32> = x.iter().map(|&v| v.round() as i32).collect();
+    if rounded.iter().sum::<i32>() % 2 != 0 {
+        let (worst, _) = x
+            .iter()
+            .zip(rounded.iter())
+            .map(|(&v, &r)| (v - r as f32).abs())
+            .enumerate()
+            .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+            .unwrap();
+        if x[worst] >= rounded[worst] as f32 {
+            rounded[worst] += 1;
+        } else {
+            rounded[worst] -= 1;
+        }
+    }
+    rounded
+}
+
+/// Returns the squared distance between `x` and the point with doubled coordinates `y`.
+fn doubled_distance(x: &[f32], y: &[i32]) -> f32 {
+    x.iter()
+        .zip(y.iter())
+        .map(|(&a, &b)| (a - b as f32 * 0.5).powi(2))
+        .sum()
+}
+
+fn squared_norm(y: &[i32]) -> i64 {
+    y.iter().map(|&v| (v as i64) * (v as i64)).sum()
+}
+
+/// Visits every integer vector of length `n` with coordinates taken from `values` and squared
+/// norm at most `limit`, pruning partial vectors that already exceed the limit.
+fn enumerate_box(
+    n: usize,
+    values: &[i32],
+    limit: i64,
+    current: &mut Vec<i32>,
+    norm: i64,
+    visit: &mut dyn FnMut(&[i32]),
+) {
+    if current.len() == n {
+        visit(current);
+        return;
+    }
+    for &v in values {
+        let next = norm + (v as i64) * (v as i64);
+        if next > limit {
+            continue;
+        }
+        current.push(v);
+        enumerate_box(n, values, limit, current, next, visit);
+        current.pop();
+    }
+}
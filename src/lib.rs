@@ -1,11 +1,23 @@
+mod additive;
+pub mod aq;
 pub mod bq;
 pub mod distances;
 pub mod exceptions;
+pub mod fp8;
+pub mod lattice;
+pub mod lloyd_max;
+pub mod lsq;
+pub mod lvq;
 pub mod opq;
+pub mod packing;
 pub mod pq;
+pub mod progress;
+pub mod rabitq;
+pub mod report;
 pub mod rvq;
 mod settings;
 pub mod sq;
+pub mod ternary;
 pub mod tsvq;
 mod utils;
 pub mod vector;
@@ -0,0 +1,278 @@
+//! # Lloyd-Max Scalar Quantizer Implementation
+//!
+//! This module provides a non-uniform scalar quantizer. Like `ScalarQuantizer`, it maps each
+//! value to one of at most 256 levels and returns the level indices as `u8` codes. Unlike
+//! `ScalarQuantizer`, the levels are not evenly spaced: the decision boundaries and reconstruction
+//! points are learned from training data with the one-dimensional Lloyd-Max algorithm. For
+//! Gaussian-like coordinates this places more levels near the centre of the distribution, where
+//! most values lie, and fewer in the tails.
+//!
+//! Starting from reconstruction points at the quantiles of the data, the algorithm alternates
+//! between two steps until the boundaries stop changing:
+//! - Each decision boundary is set to the midpoint of its two neighbouring reconstruction points.
+//! - Each reconstruction point is set to the mean of the training values between its boundaries.
+//!
+//! Boundaries and reconstruction points are learned either once for all dimensions or separately
+//! for each dimension. Encoding a value is a binary search over the sorted boundaries.
+//!
+//! # Errors
+//! The methods panic with custom errors from the exceptions module when:
+//! - The training data is empty.
+//! - The number of levels is not between 2 and 256.
+//! - An input vector's dimension does not match the training data.
+//!
+//! # Example
+//! ```
+//! use vq::vector::Vector;
+//! use vq::lloyd_max::LloydMaxQuantizer;
+//!
+//! let training_data: Vec<Vector<f32>> = (0..100)
+//!     .map(|i| Vector::new(vec![(i as f32 * 0.1).sin(), (i as f32 * 0.3).cos()]))
+//!     .collect();
+//!
+//! // 16 levels per dimension, at most 50 Lloyd-Max iterations, learned per dimension.
+//! let quantizer = LloydMaxQuantizer::fit(&training_data, 16, 50, true);
+//!
+//! let input = Vector::new(vec![0.25, -0.8]);
+//! let levels = quantizer.quantize(&input);
+//! let codes = quantizer.encode(&input);
+//! assert_eq!(codes.bits(), 4);
+//! let reconstruction = quantizer.decode(&codes);
+//! println!("Levels: {}, reconstruction: {}", levels, reconstruction);
+//! ```
+
+use crate::exceptions::VqError;
+use crate::packing::{bits_for, PackedCodes};
+use crate::vector::{Vector, PARALLEL_THRESHOLD};
+use rayon::prelude::*;
+
+/// A non-uniform scalar quantizer with boundaries learned by the Lloyd-Max algorithm.
+pub struct LloydMaxQuantizer {
+    /// The sorted decision boundaries (`levels - 1` values) of each dimension, or a single shared set.
+    boundaries: Vec<Vec<f32>>,
+    /// The reconstruction points (`levels` values) of each dimension, or a single shared set.
+    points: Vec<Vec<f32>>,
+    /// Dimensionality of the input vectors.
+    dim: usize,
+}
+
+impl LloydMaxQuantizer {
+    /// Creates a new `LloydMaxQuantizer` by learning boundaries and reconstruction points from training data.
+    ///
+    /// # Parameters
+    /// - `training_data`: A slice of training vectors (`Vector<f32>`).
+    /// - `levels`: The number of quantization levels. Must be between 2 and 256.
+    /// - `max_iters`: The maximum number of Lloyd-Max iterations.
+    /// - `per_dimension`: If true, each dimension gets its own boundaries; otherwise all dimensions
+    ///   share boundaries learned from all values.
+    ///
+    /// # Panics
+    /// Panics with a custom error if `training_data` is empty, the vectors have different dimensions,
+    /// or `levels` is not within the valid range.
+    pub fn fit(
+        training_data: &[Vector<f32>],
+        levels: usize,
+        max_iters: usize,
+        per_dimension: bool,
+    ) -> Self {
+        if training_data.is_empty() {
+            panic!("{}", VqError::EmptyInput);
+        }
+        if levels < 2 {
+            panic!(
+                "{}",
+                VqError::InvalidParameter("levels must be at least 2".to_string())
+            );
+        }
+        if levels > 256 {
+            panic!(
+                "{}",
+                VqError::InvalidParameter("levels must be no more than 256".to_string())
+            );
+        }
+        let dim = training_data[0].len();
+        if let Some(v) = training_data.iter().find(|v| v.len() != dim) {
+            panic!(
+                "{}",
+                VqError::DimensionMismatch {
+                    expected: dim,
+                    found: v.len()
+                }
+            );
+        }
+
+        let samples: Vec<Vec<f32>> = if per_dimension {
+            (0..dim)
+                .map(|d| training_data.iter().map(|v| v.data[d]).collect())
+                .collect()
+        } else {
+            vec![training_data
+                .iter()
+                .flat_map(|v| v.data.iter().copied())
+                .collect()]
+        };
+        let (boundaries, points) = samples
+            .into_par_iter()
+            .map(|values| lloyd_max(values, levels, max_iters))
+            .unzip();
+
+        Self {
+            boundaries,
+            points,
+            dim,
+        }
+    }
+
+    /// Quantizes an input vector by mapping each element to the level whose cell contains it.
+    ///
+    /// If the input vector's length exceeds `PARALLEL_THRESHOLD`, parallel iteration is used.
+    ///
+    /// # Parameters
+    /// - `vector`: A reference to the input vector (`Vector<f32>`) to quantize.
+    ///
+    /// # Returns
+    /// A new vector (`Vector<u8>`) containing the level index of each element.
+    ///
+    /// # Panics
+    /// Panics with a custom error if the input vector's dimension does not match the training data.
+    pub fn quantize(&self, vector: &Vector<f32>) -> Vector<u8> {
+        self.check_dim(vector.len());
+        let quantized_vector: Vec<u8> = if vector.data.len() > PARALLEL_THRESHOLD {
+            vector
+                .data
+                .par_iter()
+                .enumerate()
+                .map(|(d, &x)| self.quantize_scalar(d, x))
+                .collect()
+        } else {
+            vector
+                .data
+                .iter()
+                .enumerate()
+                .map(|(d, &x)| self.quantize_scalar(d, x))
+                .collect()
+        };
+        Vector::new(quantized_vector)
+    }
+
+    /// Quantizes an input vector and packs the level indices with `ceil(log2(levels))` bits each.
+    ///
+    /// # Panics
+    /// Panics with a custom error if the input vector's dimension does not match the training data.
+    pub fn encode(&self, vector: &Vector<f32>) -> PackedCodes {
+        let levels = self.points[0].len();
+        PackedCodes::from_codes(
+            bits_for(levels),
+            self.quantize(vector).data.into_iter().map(u16::from),
+        )
+    }
+
+    /// Maps packed level indices back to their reconstruction points.
+    ///
+    /// # Panics
+    /// Panics with a custom error if the number of codes does not match the training data's
+    /// dimension or a code is out of range.
+    pub fn decode(&self, codes: &PackedCodes) -> Vector<f32> {
+        self.check_dim(codes.len());
+        let data = codes
+            .iter()
+            .enumerate()
+            .map(|(d, c)| {
+                let points = &self.points[self.table(d)];
+                match points.get(c as usize) {
+                    Some(&p) => p,
+                    None => panic!(
+                        "{}",
+                        VqError::InvalidParameter(format!(
+                            "Code {} is out of range for {} levels",
+                            c,
+                            points.len()
+                        ))
+                    ),
+                }
+            })
+            .collect();
+        Vector::new(data)
+    }
+
+    /// Returns the sorted decision boundaries used for dimension `d`.
+    pub fn boundaries(&self, d: usize) -> &[f32] {
+        &self.boundaries[self.table(d)]
+    }
+
+    /// Returns the reconstruction points used for dimension `d`.
+    pub fn points(&self, d: usize) -> &[f32] {
+        &self.points[self.table(d)]
+    }
+
+    /// Returns the index of the boundaries and points used for dimension `d`.
+    fn table(&self, d: usize) -> usize {
+        if self.boundaries.len() == 1 {
+            0
+        } else {
+            d
+        }
+    }
+
+    /// Quantizes a single value of dimension `d` by binary search over the boundaries.
+    fn quantize_scalar(&self, d: usize, x: f32) -> u8 {
+        self.boundaries[self.table(d)].partition_point(|&b| b < x) as u8
+    }
+
+    fn check_dim(&self, len: usize) {
+        if len != self.dim {
+            panic!(
+                "{}",
+                VqError::DimensionMismatch {
+                    expected: self.dim,
+                    found: len
+                }
+            );
+        }
+    }
+}
+
+/// Runs the one-dimensional Lloyd-Max algorithm on `values`.
+///
+/// # Returns
+/// The `levels - 1` sorted decision boundaries and the `levels` reconstruction points.
+fn lloyd_max(mut values: Vec<f32>, levels: usize, max_iters: usize) -> (Vec<f32>, Vec<f32>) {
+    values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let n = values.len();
+    // Prefix sums give the mean of any range of sorted values in constant time.
+    let mut prefix = vec![0.0f64; n + 1];
+    for (i, &v) in values.iter().enumerate() {
+        prefix[i + 1] = prefix[i] + v as f64;
+    }
+
+    // Start from the midpoints of equal-mass cells.
+    let mut points: Vec<f32> = (0..levels)
+        .map(|j| values[((2 * j + 1) * n / (2 * levels)).min(n - 1)])
+        .collect();
+    let mut boundaries = midpoints(&points);
+
+    for _ in 0..max_iters {
+        let mut start = 0;
+        for (j, point) in points.iter_mut().enumerate() {
+            let end = match boundaries.get(j) {
+                Some(&b) => values.partition_point(|&v| v <= b),
+                None => n,
+            };
+            // Empty cells keep their reconstruction point.
+            if end > start {
+                *point = ((prefix[end] - prefix[start]) / (end - start) as f64) as f32;
+            }
+            start = end;
+        }
+        let updated = midpoints(&points);
+        if updated == boundaries {
+            break;
+        }
+        boundaries = updated;
+    }
+    (boundaries, points)
+}
+
+fn midpoints(points: &[f32]) -> Vec<f32> {
+    points.windows(2).map(|w| (w[0] + w[1]) / 2.0).collect()
+}
@@ -0,0 +1,347 @@
+//! # Local Search Quantizer Implementation
+//!
+//! This module implements Local Search Quantization (LSQ++), a multi-codebook quantizer that
+//! approximates an input vector as the sum of one codeword from each of `m` full-dimensional
+//! codebooks. It trades encoding speed for accuracy and typically reaches a lower reconstruction
+//! error than additive quantization with beam search, which makes it suited for offline
+//! compression.
+//!
+//! Encoding uses iterated local search: codes are improved with iterated conditional modes (ICM),
+//! which repeatedly re-selects the best codeword of one codebook while the others are fixed.
+//! To escape local minima, a few randomly chosen codes are then perturbed and ICM is run again;
+//! the perturbed solution is kept only if it lowers the error.
+//!
+//! Training starts from residual codebooks learned with the Linde-Buzo-Gray (LBG) algorithm and
+//! then alternates between:
+//! - Updating all codebooks jointly by least squares for the fixed codes. Following LSQ++, the
+//!   training data is perturbed with Gaussian noise whose scale shrinks over the iterations
+//!   (stochastic relaxation), which helps the codebooks escape poor local minima.
+//! - Re-encoding every training vector with iterated local search, starting from its current codes.
+//!
+//! All steps minimize the squared Euclidean reconstruction error.
+//!
+//! # Errors
+//! The `fit` and `quantize` methods panic with custom errors from the exceptions module when:
+//! - The training data is empty.
+//! - `m` or `k` is zero, or there are fewer training vectors than `k`.
+//! - The input vector's dimension does not match the training data.
+//!
+//! # Example
+//! ```
+//! use vq::vector::Vector;
+//! use vq::lsq::LocalSearchQuantizer;
+//!
+//! let training_data: Vec<Vector<f32>> = (0..50)
+//!     .map(|i| Vector::new(vec![i as f32, (i % 5) as f32, (i % 3) as f32]))
+//!     .collect();
+//!
+//! // Two codebooks of 4 codewords, 10 LBG iterations for initialization,
+//! // 3 LSQ iterations, and 4 local search rounds per encoding.
+//! let lsq = LocalSearchQuantizer::fit(&training_data, 2, 4, 10, 3, 4, 42);
+//!
+//! let input = Vector::new(vec![7.0, 2.0, 1.0]);
+//! let codes = lsq.encode(&input);
+//! let quantized = lsq.quantize(&input);
+//! println!("Codes: {:?}, quantized: {}", codes, quantized);
+//! ```
+
+use crate::additive::{
+    beam_search, least_squares_codebooks, pack_codes, reconstruct, residual_codebooks, unpack_codes,
+};
+use crate::distances::Distance;
+use crate::exceptions::VqError;
+use crate::packing::PackedCodes;
+use crate::vector::Vector;
+use half::f16;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rand_distr::{Distribution, Normal};
+use rayon::prelude::*;
+
+/// The number of ICM sweeps over all codebooks per local search round.
+const ICM_SWEEPS: usize = 4;
+
+/// The number of codes replaced by random codewords before each local search round.
+const PERTURBED_CODES: usize = 4;
+
+/// A local search quantizer (LSQ++) with jointly trained additive codebooks.
+pub struct LocalSearchQuantizer {
+    /// A vector of `m` full-dimensional codebooks. Each codebook is a vector of centroids.
+    codebooks: Vec<Vec<Vector<f32>>>,
+    /// Dimensionality of the input vectors.
+    dim: usize,
+    /// The number of perturbation and ICM rounds used to encode a vector.
+    ils_iters: usize,
+    /// The seed for the random perturbations used when encoding.
+    seed: u64,
+}
+
+impl LocalSearchQuantizer {
+    /// Constructs a new `LocalSearchQuantizer` from training data.
+    ///
+    /// # Parameters
+    /// - `training_data`: A slice of training vectors (`Vector<f32>`) used to learn the codebooks.
+    /// - `m`: The number of codebooks (codes per vector).
+    /// - `k`: The number of codewords per codebook.
+    /// - `max_iters`: The maximum number of LBG iterations used to initialize each codebook.
+    /// - `lsq_iters`: The number of alternations between the codebook update and re-encoding.
+    /// - `ils_iters`: The number of perturbation and ICM rounds used to encode each vector.
+    /// - `seed`: A random seed for initialization, stochastic relaxation, and perturbations.
+    ///
+    /// # Panics
+    /// Panics with a custom error if:
+    /// - `training_data` is empty.
+    /// - `m` is zero.
+    /// - `k` is zero or larger than the number of training vectors.
+    pub fn fit(
+        training_data: &[Vector<f32>],
+        m: usize,
+        k: usize,
+        max_iters: usize,
+        lsq_iters: usize,
+        ils_iters: usize,
+        seed: u64,
+    ) -> Self {
+        if training_data.is_empty() {
+            panic!("{}", VqError::EmptyInput);
+        }
+        if m == 0 {
+            panic!(
+                "{}",
+                VqError::InvalidParameter("m must be greater than 0".to_string())
+            );
+        }
+        let dim = training_data[0].len();
+        let n = training_data.len();
+
+        // --- Initialization ---
+        let mut codebooks = residual_codebooks(training_data, m, k, max_iters, seed);
+        let mut codes: Vec<Vec<usize>> = training_data
+            .par_iter()
+            .map(|v| beam_search(&codebooks, &v.data, 1, &Distance::SquaredEuclidean).0)
+            .collect();
+
+        // Per-dimension standard deviation of the data, used to scale the relaxation noise.
+        let mean: Vec<f32> = (0..dim)
+            .map(|d| training_data.iter().map(|v| v.data[d]).sum::<f32>() / n as f32)
+            .collect();
+        let std_dev: Vec<f32> = (0..dim)
+            .map(|d| {
+                let var = training_data
+                    .iter()
+                    .map(|v| (v.data[d] - mean[d]).powi(2))
+                    .sum::<f32>()
+                    / n as f32;
+                var.sqrt()
+            })
+            .collect();
+
+        let mut rng = StdRng::seed_from_u64(seed);
+        for t in 0..lsq_iters {
+            // --- Codebook Update with Stochastic Relaxation ---
+            // The noise variance is diag(Σ) * τ / (m * k) with temperature τ = (1 - t / T)^0.5.
+            let temperature = (1.0 - t as f32 / lsq_iters as f32).sqrt();
+            let scale = (temperature / (m * k) as f32).sqrt();
+            let noise = Normal::new(0.0f32, 1.0).unwrap();
+            let perturbed: Vec<Vector<f32>> = training_data
+                .iter()
+                .map(|v| {
+                    let data = v
+                        .data
+                        .iter()
+                        .zip(std_dev.iter())
+                        .map(|(&x, &s)| x + noise.sample(&mut rng) * s * scale)
+                        .collect();
+                    Vector::new(data)
+                })
+                .collect();
+            codebooks = least_squares_codebooks(&perturbed, &codes, &codebooks);
+
+            // --- Encoding ---
+            let round_seed = rng.random::<u64>();
+            codes = training_data
+                .par_iter()
+                .zip(codes.par_iter())
+                .enumerate()
+                .map(|(i, (v, previous))| {
+                    let mut rng = StdRng::seed_from_u64(round_seed.wrapping_add(i as u64));
+                    local_search(&codebooks, &v.data, previous.clone(), ils_iters, &mut rng).0
+                })
+                .collect();
+        }
+
+        Self {
+            codebooks,
+            dim,
+            ils_iters,
+            seed,
+        }
+    }
+
+    /// Encodes an input vector as one codeword index per codebook using iterated local search.
+    ///
+    /// The search starts from the greedy residual encoding. The random perturbations are seeded
+    /// with the quantizer's seed, so encoding the same vector always gives the same codes.
+    ///
+    /// # Parameters
+    /// - `vector`: The input vector (`Vector<f32>`) to encode.
+    ///
+    /// # Returns
+    /// The `m` codeword indices, packed with `ceil(log2(k))` bits each.
+    ///
+    /// # Panics
+    /// Panics with a custom error if the input vector's dimension does not match the training data.
+    pub fn encode(&self, vector: &Vector<f32>) -> PackedCodes {
+        pack_codes(&self.codebooks, &self.encode_indices(vector))
+    }
+
+    /// Selects one codeword index per codebook for `vector`.
+    fn encode_indices(&self, vector: &Vector<f32>) -> Vec<usize> {
+        if vector.len() != self.dim {
+            panic!(
+                "{}",
+                VqError::DimensionMismatch {
+                    expected: self.dim,
+                    found: vector.len()
+                }
+            );
+        }
+        let initial = beam_search(
+            &self.codebooks,
+            &vector.data,
+            1,
+            &Distance::SquaredEuclidean,
+        )
+        .0;
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        local_search(
+            &self.codebooks,
+            &vector.data,
+            initial,
+            self.ils_iters,
+            &mut rng,
+        )
+        .0
+    }
+
+    /// Reconstructs a vector from its codes by summing the selected codewords.
+    ///
+    /// # Panics
+    /// Panics with a custom error if the number of codes does not equal `m` or a code is out of range.
+    pub fn decode(&self, codes: &PackedCodes) -> Vector<f32> {
+        let codes = unpack_codes(&self.codebooks, codes);
+        Vector::new(reconstruct(&self.codebooks, &codes, self.dim))
+    }
+
+    /// Quantizes an input vector by encoding it with local search and summing the selected codewords.
+    ///
+    /// # Parameters
+    /// - `vector`: The input vector (`Vector<f32>`) to quantize.
+    ///
+    /// # Returns
+    /// The reconstruction as a half-precision vector (`Vector<f16>`).
+    ///
+    /// # Panics
+    /// Panics with a custom error if the input vector's dimension does not match the training data.
+    pub fn quantize(&self, vector: &Vector<f32>) -> Vector<f16> {
+        let codes = self.encode_indices(vector);
+        let quantized: Vec<f16> = reconstruct(&self.codebooks, &codes, self.dim)
+            .into_iter()
+            .map(f16::from_f32)
+            .collect();
+        Vector::new(quantized)
+    }
+
+    /// Returns the learned codebooks.
+    pub fn codebooks(&self) -> &[Vec<Vector<f32>>] {
+        &self.codebooks
+    }
+}
+
+/// Returns the squared Euclidean distance between two slices.
+fn squared_error(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b.iter()).map(|(x, y)| (x - y) * (x - y)).sum()
+}
+
+/// Improves `codes` with iterated conditional modes.
+///
+/// Each step replaces the code of one codebook by the codeword that best approximates the
+/// vector minus the other codebooks' contributions. Sweeps stop early when no code changes.
+///
+/// # Returns
+/// The squared reconstruction error of the final codes.
+fn icm(codebooks: &[Vec<Vector<f32>>], vector: &[f32], codes: &mut [usize]) -> f32 {
+    let dim = vector.len();
+    let mut sum = reconstruct(codebooks, codes, dim);
+    let mut target = vec![0.0f32; dim];
+    for _ in 0..ICM_SWEEPS {
+        let mut changed = false;
+        for (i, codebook) in codebooks.iter().enumerate() {
+            // The part of the vector left for codebook `i` to explain.
+            for ((t, &x), (&s, &c)) in target
+                .iter_mut()
+                .zip(vector.iter())
+                .zip(sum.iter().zip(codebook[codes[i]].data.iter()))
+            {
+                *t = x - (s - c);
+            }
+            let mut best = codes[i];
+            let mut best_dist = squared_error(&target, &codebook[best].data);
+            for (j, codeword) in codebook.iter().enumerate() {
+                let dist = squared_error(&target, &codeword.data);
+                if dist < best_dist {
+                    best = j;
+                    best_dist = dist;
+                }
+            }
+            if best != codes[i] {
+                for ((s, &old), &new) in sum
+                    .iter_mut()
+                    .zip(codebook[codes[i]].data.iter())
+                    .zip(codebook[best].data.iter())
+                {
+                    *s += new - old;
+                }
+                codes[i] = best;
+                changed = true;
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+    squared_error(vector, &sum)
+}
+
+/// Encodes a vector with iterated local search starting from `initial` codes.
+///
+/// After an initial ICM pass, each round perturbs up to `PERTURBED_CODES` random codes, runs
+/// ICM again, and keeps the result if it lowers the error. The error never exceeds that of the
+/// initial codes.
+///
+/// # Returns
+/// The best codes found and their squared reconstruction error.
+fn local_search(
+    codebooks: &[Vec<Vector<f32>>],
+    vector: &[f32],
+    initial: Vec<usize>,
+    ils_iters: usize,
+    rng: &mut StdRng,
+) -> (Vec<usize>, f32) {
+    let mut best = initial;
+    let mut best_error = icm(codebooks, vector, &mut best);
+    let m = codebooks.len();
+    for _ in 0..ils_iters {
+        let mut candidate = best.clone();
+        for _ in 0..PERTURBED_CODES.min(m) {
+            let i = rng.random_range(0..m);
+            candidate[i] = rng.random_range(0..codebooks[i].len());
+        }
+        let error = icm(codebooks, vector, &mut candidate);
+        if error < best_error {
+            best = candidate;
+            best_error = error;
+        }
+    }
+    (best, best_error)
+}
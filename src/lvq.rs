@@ -0,0 +1,280 @@
+//! # Locally-Adaptive Vector Quantizer Implementation
+//!
+//! This module implements Locally-adaptive Vector Quantization (LVQ). Vectors are first centred
+//! on the mean of the training data. Each centred vector is then scalar-quantized with its own
+//! range: the smallest component is stored as the per-vector `bias`, and the step between levels
+//! as the per-vector `scale`. Unlike `ScalarQuantizer`, whose single `[min, max]` range must
+//! cover every vector, LVQ adapts to vectors with very different norms.
+//!
+//! Each component is stored with 4 or 8 bits in `PackedCodes`. An optional
+//! second level quantizes the remaining error of each component, which lies within half a step,
+//! with another 4 or 8 bits. The first level alone is compact enough for graph traversal, and
+//! the second level gives more accurate distances for reranking.
+//!
+//! # Errors
+//! The methods panic with custom errors from the exceptions module when:
+//! - The training data is empty.
+//! - A bit width other than 4 or 8 is requested.
+//! - An input vector's dimension or a code does not match the quantizer.
+//!
+//! # Example
+//! ```
+//! use vq::vector::Vector;
+//! use vq::lvq::LocallyAdaptiveQuantizer;
+//!
+//! let training_data: Vec<Vector<f32>> = (0..50)
+//!     .map(|i| Vector::new(vec![i as f32, (i % 5) as f32, (i % 3) as f32]))
+//!     .collect();
+//!
+//! // 4-bit primary codes with an 8-bit residual level.
+//! let lvq = LocallyAdaptiveQuantizer::fit(&training_data, 4, Some(8));
+//!
+//! let input = Vector::new(vec![7.0, 2.0, 1.0]);
+//! let code = lvq.encode(&input);
+//! let coarse = lvq.decode_primary(&code);
+//! let fine = lvq.decode(&code);
+//! println!("Coarse: {}, fine: {}", coarse, fine);
+//! ```
+
+use crate::exceptions::VqError;
+use crate::packing::PackedCodes;
+use crate::vector::Vector;
+use half::f16;
+
+/// A vector encoded by `LocallyAdaptiveQuantizer::encode`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LvqCode {
+    /// The primary level of each component.
+    pub codes: PackedCodes,
+    /// The per-vector step between primary levels.
+    pub scale: f32,
+    /// The per-vector offset of the lowest primary level (the smallest centred component).
+    pub bias: f32,
+    /// The residual level of each component, if the quantizer has a second level.
+    pub residual: Option<PackedCodes>,
+}
+
+/// A locally-adaptive scalar quantizer with per-vector scale and bias.
+pub struct LocallyAdaptiveQuantizer {
+    /// The mean of the training data, which all vectors are centred on.
+    mean: Vec<f32>,
+    /// Dimensionality of the input vectors.
+    dim: usize,
+    /// The number of bits of the primary codes (4 or 8).
+    bits: u8,
+    /// The number of bits of the residual codes (4 or 8), if any.
+    residual_bits: Option<u8>,
+}
+
+impl LocallyAdaptiveQuantizer {
+    /// Creates a new `LocallyAdaptiveQuantizer` from training data.
+    ///
+    /// The training data is only used to compute the mean that vectors are centred on.
+    ///
+    /// # Parameters
+    /// - `training_data`: A slice of training vectors (`Vector<f32>`).
+    /// - `bits`: The number of bits per component of the primary codes (4 or 8).
+    /// - `residual_bits`: The number of bits per component of the residual codes (4 or 8), or
+    ///   `None` for a single level.
+    ///
+    /// # Panics
+    /// Panics with a custom error if `training_data` is empty, the vectors have different
+    /// dimensions, or a bit width is not 4 or 8.
+    pub fn fit(training_data: &[Vector<f32>], bits: u8, residual_bits: Option<u8>) -> Self {
+        if training_data.is_empty() {
+            panic!("{}", VqError::EmptyInput);
+        }
+        for b in std::iter::once(bits).chain(residual_bits) {
+            if b != 4 && b != 8 {
+                panic!(
+                    "{}",
+                    VqError::InvalidParameter(format!("bits must be 4 or 8, got {}", b))
+                );
+            }
+        }
+        let dim = training_data[0].len();
+        let mut mean = vec![0.0f32; dim];
+        for v in training_data {
+            if v.len() != dim {
+                panic!(
+                    "{}",
+                    VqError::DimensionMismatch {
+                        expected: dim,
+                        found: v.len()
+                    }
+                );
+            }
+            for (m, &x) in mean.iter_mut().zip(v.data.iter()) {
+                *m += x;
+            }
+        }
+        let n = training_data.len() as f32;
+        mean.iter_mut().for_each(|m| *m /= n);
+
+        Self {
+            mean,
+            dim,
+            bits,
+            residual_bits,
+        }
+    }
+
+    /// Encodes a vector with its own scale and bias.
+    ///
+    /// # Parameters
+    /// - `vector`: The input vector (`Vector<f32>`) to encode.
+    ///
+    /// # Returns
+    /// An `LvqCode` holding the packed primary codes, the per-vector scale and bias, and the
+    /// packed residual codes if the quantizer has a second level.
+    ///
+    /// # Panics
+    /// Panics with a custom error if the input vector's dimension does not match the training data.
+    pub fn encode(&self, vector: &Vector<f32>) -> LvqCode {
+        if vector.len() != self.dim {
+            panic!(
+                "{}",
+                VqError::DimensionMismatch {
+                    expected: self.dim,
+                    found: vector.len()
+                }
+            );
+        }
+        let centred: Vec<f32> = vector
+            .data
+            .iter()
+            .zip(self.mean.iter())
+            .map(|(&x, &m)| x - m)
+            .collect();
+        let bias = centred.iter().copied().fold(f32::INFINITY, f32::min);
+        let upper = centred.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+        let top = max_level(self.bits);
+        let scale = (upper - bias) / top as f32;
+
+        let levels: Vec<u8> = centred
+            .iter()
+            .map(|&x| quantize_level(x - bias, scale, top))
+            .collect();
+
+        let residual = self.residual_bits.map(|residual_bits| {
+            // The primary error lies in [-scale / 2, scale / 2]; split that range uniformly.
+            let residual_max = max_level(residual_bits);
+            let residual_scale = scale / residual_max as f32;
+            let residual_levels: Vec<u8> = centred
+                .iter()
+                .zip(levels.iter())
+                .map(|(&x, &l)| {
+                    let error = x - (bias + l as f32 * scale);
+                    quantize_level(error + scale / 2.0, residual_scale, residual_max)
+                })
+                .collect();
+            PackedCodes::from_codes(residual_bits, residual_levels.into_iter().map(u16::from))
+        });
+
+        LvqCode {
+            codes: PackedCodes::from_codes(self.bits, levels.into_iter().map(u16::from)),
+            scale,
+            bias,
+            residual,
+        }
+    }
+
+    /// Reconstructs a vector from its primary codes only.
+    ///
+    /// # Panics
+    /// Panics with a custom error if the code does not match the quantizer's dimension.
+    pub fn decode_primary(&self, code: &LvqCode) -> Vector<f32> {
+        Vector::new(self.primary(code))
+    }
+
+    /// Reconstructs a vector from its primary and, if present, residual codes.
+    ///
+    /// # Panics
+    /// Panics with a custom error if the code does not match the quantizer's dimension or
+    /// residual configuration.
+    pub fn decode(&self, code: &LvqCode) -> Vector<f32> {
+        let mut data = self.primary(code);
+        match (self.residual_bits, &code.residual) {
+            (Some(residual_bits), Some(residual)) => {
+                let residual_max = max_level(residual_bits);
+                let residual_scale = code.scale / residual_max as f32;
+                self.check_codes(residual, residual_bits);
+                for (x, l) in data.iter_mut().zip(residual.iter()) {
+                    *x += l as f32 * residual_scale - code.scale / 2.0;
+                }
+            }
+            (None, None) => {}
+            _ => panic!(
+                "{}",
+                VqError::InvalidParameter(
+                    "Code residual does not match the quantizer configuration".to_string()
+                )
+            ),
+        }
+        Vector::new(data)
+    }
+
+    /// Quantizes an input vector and returns its reconstruction from all levels.
+    ///
+    /// # Parameters
+    /// - `vector`: The input vector (`Vector<f32>`) to quantize.
+    ///
+    /// # Returns
+    /// The reconstruction as a half-precision vector (`Vector<f16>`).
+    ///
+    /// # Panics
+    /// Panics with a custom error if the input vector's dimension does not match the training data.
+    pub fn quantize(&self, vector: &Vector<f32>) -> Vector<f16> {
+        let decoded = self.decode(&self.encode(vector));
+        Vector::new(decoded.data.into_iter().map(f16::from_f32).collect())
+    }
+
+    /// Returns the mean that vectors are centred on.
+    pub fn mean(&self) -> &[f32] {
+        &self.mean
+    }
+
+    fn primary(&self, code: &LvqCode) -> Vec<f32> {
+        self.check_codes(&code.codes, self.bits);
+        code.codes
+            .iter()
+            .zip(self.mean.iter())
+            .map(|(l, &m)| m + code.bias + l as f32 * code.scale)
+            .collect()
+    }
+
+    fn check_codes(&self, codes: &PackedCodes, bits: u8) {
+        if codes.len() != self.dim {
+            panic!(
+                "{}",
+                VqError::DimensionMismatch {
+                    expected: self.dim,
+                    found: codes.len()
+                }
+            );
+        }
+        if codes.bits() != bits {
+            panic!(
+                "{}",
+                VqError::InvalidParameter(format!(
+                    "Expected {}-bit codes, got {}-bit codes",
+                    bits,
+                    codes.bits()
+                ))
+            );
+        }
+    }
+}
+
+fn max_level(bits: u8) -> u8 {
+    ((1u16 << bits) - 1) as u8
+}
+
+/// Maps a non-negative offset to the nearest level, treating a zero step as a constant vector.
+fn quantize_level(offset: f32, step: f32, max_level: u8) -> u8 {
+    if step > 0.0 {
+        (offset / step).round().clamp(0.0, max_level as f32) as u8
+    } else {
+        0
+    }
+}
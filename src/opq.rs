@@ -8,10 +8,30 @@
 //! (using a specified distance metric). The final quantized representation is obtained by concatenating
 //! the selected codewords and converting them to half-precision (`f16`).
 //!
+//! Training starts from the parametric OPQ solution: the rotation maps the data onto its
+//! principal components, which are allocated to subspaces by decreasing eigenvalue so that the
+//! variance is balanced across subspaces (eigenvalue allocation). Each OPQ iteration then learns
+//! the codebooks and, except in the last iteration, updates the rotation by solving an orthogonal
+//! Procrustes problem. `fit_parametric` skips the alternation and only learns codebooks for the
+//! eigenvalue allocation rotation, which is much faster to train.
+//!
+//! The rotation can also reduce the dimensionality. `fit_with_projection` learns a `d_out x d_in`
+//! projection with orthonormal rows, starting from the `d_out` leading principal components, and
+//! product-quantizes the projected `d_out`-dimensional vectors. Queries can be mapped to the same
+//! space with `project`. The projection update uses the same Procrustes solution as the square
+//! case, which ignores the variance removed by the projection.
+//!
+//! Like in `ProductQuantizer`, the subspaces may have different dimensions: `fit` splits the
+//! dimensions into `m` subspaces whose sizes differ by at most one, and `fit_with_boundaries`
+//! accepts explicit subspace boundaries.
+//!
 //! # Errors
 //! The `fit` and `quantize` methods panic with custom errors from the exceptions module when:
 //! - The training data is empty.
-//! - The dimension of the training vectors is less than `m` or not divisible by `m`.
+//! - The dimension of the training vectors is less than `m`, or the subspace boundaries do not
+//!   increase strictly from 0 to the dimension.
+//! - The distance metric has no suitable centroid update for codebook training
+//!   (Chebyshev, Minkowski, or Hamming).
 //! - The input vector's dimension in `quantize` does not match the expected dimension.
 //!
 //! # Example
@@ -47,22 +67,29 @@
 //! ```
 
 use crate::distances::Distance;
-use crate::exceptions::VqError;
-use crate::utils::lbg_quantize;
+use crate::exceptions::{VqError, VqResult};
+use crate::packing::{bits_for, PackedCodes};
+use crate::progress::{Progress, TrainingObserver};
+use crate::report::{ClusteringReport, RoundReport, TrainingReport};
+use crate::utils::{balanced_boundaries, check_boundaries, data_dim, lbg_quantize};
 use crate::vector::Vector;
 use half::f16;
-use nalgebra::DMatrix;
+use nalgebra::{DMatrix, DVector};
 use rayon::prelude::*;
+use std::time::Instant;
+
+/// A rotation and codebooks used as the starting point for training.
+type InitialState<'a> = (&'a DMatrix<f32>, &'a [Vec<Vector<f32>>]);
 
 pub struct OptimizedProductQuantizer {
-    /// The learned rotation matrix (of size `dim x dim`).
+    /// The learned rotation matrix (of size `d_out x dim`, square unless the quantizer reduces
+    /// the dimensionality).
     rotation: DMatrix<f32>,
     /// A vector of codebooks (one for each subspace). Each codebook is a vector of centroids.
     codebooks: Vec<Vec<Vector<f32>>>,
-    /// The dimensionality of each subspace (i.e. `dim / m`).
-    sub_dim: usize,
-    /// The number of subspaces into which the rotated vector is partitioned.
-    m: usize,
+    /// The `m + 1` subspace boundaries of the rotated space. Subspace `i` covers dimensions
+    /// `boundaries[i]..boundaries[i + 1]`.
+    boundaries: Vec<usize>,
     /// The overall dimensionality of the input vectors.
     dim: usize,
     /// The distance metric used for selecting codewords during quantization.
@@ -74,19 +101,23 @@ impl OptimizedProductQuantizer {
     ///
     /// # Parameters
     /// - `training_data`: A slice of training vectors (`Vector<f32>`) used for learning the quantizer.
-    /// - `m`: The number of subspaces into which the rotated data will be partitioned.
+    /// - `m`: The number of subspaces into which the rotated data will be partitioned. If the
+    ///   dimension is not divisible by `m`, the first subspaces get one extra dimension.
     /// - `k`: The number of centroids (codewords) per subspace.
     /// - `max_iters`: The maximum number of iterations for the LBG quantization algorithm.
     /// - `opq_iters`: The number of OPQ iterations (i.e. the number of times the algorithm alternates
-    ///    between codebook learning, reconstruction, rotation update, and re-rotation).
+    ///   between codebook learning, reconstruction, rotation update, and re-rotation). Must be at
+    ///   least 1; with 1 the quantizer is the same as with `fit_parametric`.
     /// - `distance`: The distance metric to use for comparing subvectors during codeword selection.
+    ///   Codebooks are trained for the same metric (see `lbg_quantize`).
     /// - `seed`: A random seed for initializing LBG quantization (each subspace uses `seed + i`).
     ///
     /// # Panics
     /// Panics with a custom error if:
     /// - `training_data` is empty.
+    /// - The distance metric is not supported for codebook training.
     /// - The dimension of the training vectors is less than `m`.
-    /// - The dimension of the training vectors is not divisible by `m`.
+    /// - `opq_iters` is 0.
     pub fn fit(
         training_data: &[Vector<f32>],
         m: usize,
@@ -96,62 +127,420 @@ impl OptimizedProductQuantizer {
         distance: Distance,
         seed: u64,
     ) -> Self {
-        if training_data.is_empty() {
+        Self::fit_with_report(training_data, m, k, max_iters, opq_iters, distance, seed).0
+    }
+
+    /// Constructs a new `OptimizedProductQuantizer` like `fit` and also returns a `TrainingReport`.
+    ///
+    /// The report holds one round per OPQ iteration, each with one `ClusteringReport` per
+    /// subspace. The round distortion is the mean distance between the rotated training vectors
+    /// and their reconstructions, measured before the rotation is updated.
+    ///
+    /// # Parameters
+    /// Same as `fit`.
+    ///
+    /// # Panics
+    /// Same conditions as `fit`.
+    pub fn fit_with_report(
+        training_data: &[Vector<f32>],
+        m: usize,
+        k: usize,
+        max_iters: usize,
+        opq_iters: usize,
+        distance: Distance,
+        seed: u64,
+    ) -> (Self, TrainingReport) {
+        let progress = Progress::new(None);
+        Self::train(
+            training_data,
+            &balanced_boundaries(data_dim(training_data), m),
+            k,
+            max_iters,
+            opq_iters,
+            distance,
+            seed,
+            None,
+            &progress,
+        )
+        .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Constructs a new `OptimizedProductQuantizer` without alternating optimization.
+    ///
+    /// The rotation is the eigenvalue allocation of the training data's principal components,
+    /// and the codebooks are learned once on the rotated data.
+    ///
+    /// # Parameters
+    /// Same as `fit`, without `opq_iters`.
+    ///
+    /// # Panics
+    /// Same conditions as `fit`.
+    pub fn fit_parametric(
+        training_data: &[Vector<f32>],
+        m: usize,
+        k: usize,
+        max_iters: usize,
+        distance: Distance,
+        seed: u64,
+    ) -> Self {
+        Self::fit(training_data, m, k, max_iters, 1, distance, seed)
+    }
+
+    /// Constructs a new `OptimizedProductQuantizer` that projects the data to fewer dimensions
+    /// before product quantization.
+    ///
+    /// # Parameters
+    /// - `training_data`: A slice of training vectors (`Vector<f32>`) used for learning the quantizer.
+    /// - `out_dim`: The dimension `d_out` of the projected vectors (at most the data dimension).
+    /// - `m`: The number of subspaces into which the projected data will be partitioned. If
+    ///   `out_dim` is not divisible by `m`, the first subspaces get one extra dimension.
+    /// - `k`, `max_iters`, `opq_iters`, `distance`, `seed`: Same as `fit`.
+    ///
+    /// # Panics
+    /// Panics with a custom error if `out_dim` is greater than the data dimension or less than `m`,
+    /// or under the same conditions as `fit`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn fit_with_projection(
+        training_data: &[Vector<f32>],
+        out_dim: usize,
+        m: usize,
+        k: usize,
+        max_iters: usize,
+        opq_iters: usize,
+        distance: Distance,
+        seed: u64,
+    ) -> Self {
+        let progress = Progress::new(None);
+        Self::train(
+            training_data,
+            &balanced_boundaries(out_dim, m),
+            k,
+            max_iters,
+            opq_iters,
+            distance,
+            seed,
+            None,
+            &progress,
+        )
+        .unwrap_or_else(|e| panic!("{}", e))
+        .0
+    }
+
+    /// Constructs a new `OptimizedProductQuantizer` with explicit subspace boundaries.
+    ///
+    /// # Parameters
+    /// - `training_data`: A slice of training vectors (`Vector<f32>`) used for learning the quantizer.
+    /// - `boundaries`: The `m + 1` subspace boundaries of the rotated space, increasing strictly
+    ///   from 0 to the data dimension. Subspace `i` covers dimensions `boundaries[i]..boundaries[i + 1]`.
+    ///   If the last boundary is less than the data dimension, the data is projected to that
+    ///   many dimensions (see `fit_with_projection`).
+    /// - `k`, `max_iters`, `opq_iters`, `distance`, `seed`: Same as `fit`.
+    ///
+    /// # Panics
+    /// Panics with a custom error if the boundaries are invalid for the training data dimension,
+    /// or under the same conditions as `fit`.
+    pub fn fit_with_boundaries(
+        training_data: &[Vector<f32>],
+        boundaries: &[usize],
+        k: usize,
+        max_iters: usize,
+        opq_iters: usize,
+        distance: Distance,
+        seed: u64,
+    ) -> Self {
+        let progress = Progress::new(None);
+        Self::train(
+            training_data,
+            boundaries,
+            k,
+            max_iters,
+            opq_iters,
+            distance,
+            seed,
+            None,
+            &progress,
+        )
+        .unwrap_or_else(|e| panic!("{}", e))
+        .0
+    }
+
+    /// Constructs a new `OptimizedProductQuantizer` like `fit_with_report` while reporting progress.
+    ///
+    /// The observer receives an event after every LBG iteration of every subspace and after
+    /// every OPQ iteration. Cancellation is checked after each LBG iteration and before each
+    /// rotation update, so a cancelled fit returns without finishing the current SVD.
+    ///
+    /// # Parameters
+    /// Same as `fit`, plus:
+    /// - `observer`: Receives progress events and may request cancellation.
+    ///
+    /// # Errors
+    /// Returns `VqError::Cancelled` if the observer requested cancellation.
+    ///
+    /// # Panics
+    /// Same conditions as `fit`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn fit_with_observer(
+        training_data: &[Vector<f32>],
+        m: usize,
+        k: usize,
+        max_iters: usize,
+        opq_iters: usize,
+        distance: Distance,
+        seed: u64,
+        observer: &dyn TrainingObserver,
+    ) -> VqResult<(Self, TrainingReport)> {
+        let progress = Progress::new(Some(observer));
+        Self::train(
+            training_data,
+            &balanced_boundaries(data_dim(training_data), m),
+            k,
+            max_iters,
+            opq_iters,
+            distance,
+            seed,
+            None,
+            &progress,
+        )
+    }
+
+    /// Constructs a new `OptimizedProductQuantizer` by refining an existing rotation and codebooks
+    /// on new training data.
+    ///
+    /// The training data is first rotated with the given rotation, and the LBG algorithm in each
+    /// subspace starts from the given codebook. Each OPQ iteration starts from the codebooks of
+    /// the previous one, so only a few iterations are usually needed on a new sample. With
+    /// `opq_iters = 0` the given model is returned unchanged.
+    ///
+    /// # Parameters
+    /// - `training_data`: A slice of training vectors (`Vector<f32>`) used to refine the quantizer.
+    /// - `rotation`: The initial rotation matrix (of size `d_out x dim`, for example from `rotation()`).
+    /// - `codebooks`: The initial codebooks, one per subspace (for example from `codebooks()`).
+    /// - `max_iters`: The maximum number of LBG iterations per subspace.
+    /// - `opq_iters`: The number of OPQ iterations.
+    /// - `distance`: The distance metric used for training and for comparing subvectors.
+    /// - `seed`: A random seed used to reseed empty clusters. Each subspace uses `seed + i`.
+    ///
+    /// # Panics
+    /// Panics with a custom error if:
+    /// - The training data or any codebook is empty.
+    /// - The rotation does not have one column per training data dimension.
+    /// - The total dimension of the codebooks does not match the number of rows of the rotation.
+    /// - A subspace has fewer training vectors than codewords.
+    /// - The distance metric is not supported for codebook training.
+    pub fn warm_start(
+        training_data: &[Vector<f32>],
+        rotation: &DMatrix<f32>,
+        codebooks: &[Vec<Vector<f32>>],
+        max_iters: usize,
+        opq_iters: usize,
+        distance: Distance,
+        seed: u64,
+    ) -> Self {
+        if training_data.is_empty()
+            || codebooks.is_empty()
+            || codebooks.iter().any(|c| c.is_empty())
+        {
             panic!("{}", VqError::EmptyInput);
         }
         let dim = training_data[0].len();
-        if dim < m {
+        if rotation.ncols() != dim {
+            panic!(
+                "{}",
+                VqError::DimensionMismatch {
+                    expected: dim,
+                    found: rotation.ncols()
+                }
+            );
+        }
+        let mut boundaries = vec![0];
+        for codebook in codebooks {
+            boundaries.push(boundaries[boundaries.len() - 1] + codebook[0].len());
+        }
+        let expected = boundaries[boundaries.len() - 1];
+        if expected != rotation.nrows() {
             panic!(
                 "{}",
-                VqError::InvalidParameter("Dimension must be at least m".to_string())
+                VqError::DimensionMismatch {
+                    expected,
+                    found: rotation.nrows()
+                }
             );
         }
-        if dim % m != 0 {
+        let progress = Progress::new(None);
+        Self::train(
+            training_data,
+            &boundaries,
+            codebooks[0].len(),
+            max_iters,
+            opq_iters,
+            distance,
+            seed,
+            Some((rotation, codebooks)),
+            &progress,
+        )
+        .unwrap_or_else(|e| panic!("{}", e))
+        .0
+    }
+
+    /// Refines this quantizer's rotation and codebooks on new training data (see `warm_start`).
+    ///
+    /// # Panics
+    /// Same conditions as `warm_start`.
+    pub fn refine(
+        &self,
+        training_data: &[Vector<f32>],
+        max_iters: usize,
+        opq_iters: usize,
+        seed: u64,
+    ) -> Self {
+        Self::warm_start(
+            training_data,
+            &self.rotation,
+            &self.codebooks,
+            max_iters,
+            opq_iters,
+            self.distance,
+            seed,
+        )
+    }
+
+    /// Returns the learned rotation matrix (of size `d_out x dim`).
+    pub fn rotation(&self) -> &DMatrix<f32> {
+        &self.rotation
+    }
+
+    /// Applies the learned rotation (or projection) to a vector, for example a query.
+    ///
+    /// # Returns
+    /// The rotated vector, with `d_out` dimensions.
+    ///
+    /// # Panics
+    /// Panics with a custom error if the input vector's dimension does not match the training data.
+    pub fn project(&self, vector: &Vector<f32>) -> Vector<f32> {
+        if vector.len() != self.dim {
             panic!(
                 "{}",
-                VqError::InvalidParameter("Dimension must be divisible by m".to_string())
+                VqError::DimensionMismatch {
+                    expected: self.dim,
+                    found: vector.len()
+                }
             );
         }
-        let sub_dim = dim / m;
+        let x = DMatrix::from_column_slice(self.dim, 1, &vector.data);
+        let y = &self.rotation * x;
+        Vector::new(y.column(0).iter().cloned().collect())
+    }
+
+    /// Returns the learned codebooks, one per subspace of the rotated space.
+    pub fn codebooks(&self) -> &[Vec<Vector<f32>>] {
+        &self.codebooks
+    }
+
+    /// Returns the `m + 1` subspace boundaries of the rotated space. Subspace `i` covers
+    /// dimensions `boundaries[i]..boundaries[i + 1]`.
+    pub fn boundaries(&self) -> &[usize] {
+        &self.boundaries
+    }
+
+    /// Learns the rotation and codebooks, starting from the `initial` rotation and codebooks if
+    /// given and forwarding progress to `progress`.
+    #[allow(clippy::too_many_arguments)]
+    fn train(
+        training_data: &[Vector<f32>],
+        boundaries: &[usize],
+        k: usize,
+        max_iters: usize,
+        opq_iters: usize,
+        distance: Distance,
+        seed: u64,
+        initial: Option<InitialState>,
+        progress: &Progress,
+    ) -> VqResult<(Self, TrainingReport)> {
+        let start_time = Instant::now();
+        if training_data.is_empty() {
+            panic!("{}", VqError::EmptyInput);
+        }
+        let dim = training_data[0].len();
+        let out_dim = boundaries.last().copied().unwrap_or(0);
+        if out_dim > dim {
+            panic!(
+                "{}",
+                VqError::InvalidParameter(format!(
+                    "Projected dimension {} must be at most the data dimension {}",
+                    out_dim, dim
+                ))
+            );
+        }
+        check_boundaries(boundaries, out_dim);
+        let m = boundaries.len() - 1;
         let n = training_data.len();
 
-        // Start with an identity rotation.
-        let mut rotation = DMatrix::<f32>::identity(dim, dim);
-        // Initially, no rotation is applied.
-        let mut rotated_data: Vec<Vector<f32>> = training_data.to_vec();
-        let mut codebooks = Vec::with_capacity(m);
+        let (mut rotation, mut rotated_data, mut codebooks) = match initial {
+            // Start from the given rotation and codebooks.
+            Some((rotation, codebooks)) => (
+                rotation.clone(),
+                rotate(training_data, rotation),
+                codebooks.to_vec(),
+            ),
+            // Start from the eigenvalue allocation rotation.
+            None => {
+                if opq_iters == 0 {
+                    panic!(
+                        "{}",
+                        VqError::InvalidParameter("opq_iters must be at least 1".to_string())
+                    );
+                }
+                let rotation = eigenvalue_allocation(training_data, boundaries);
+                let rotated_data = rotate(training_data, &rotation);
+                (rotation, rotated_data, Vec::with_capacity(m))
+            }
+        };
+        let mut rounds = Vec::with_capacity(opq_iters);
 
-        for _ in 0..opq_iters {
+        for round in 0..opq_iters {
             // --- Codebook Learning ---
             // Learn a codebook for each subspace in parallel.
-            codebooks = (0..m)
+            let previous = std::mem::take(&mut codebooks);
+            let clusterings: Vec<ClusteringReport>;
+            (codebooks, clusterings) = (0..m)
                 .into_par_iter()
                 .map(|i| {
                     // Extract the sub-training data for subspace `i`.
+                    let (start, end) = (boundaries[i], boundaries[i + 1]);
                     let sub_training: Vec<Vector<f32>> = rotated_data
                         .iter()
-                        .map(|v| {
-                            let start = i * sub_dim;
-                            let end = start + sub_dim;
-                            Vector::new(v.data[start..end].to_vec())
-                        })
+                        .map(|v| Vector::new(v.data[start..end].to_vec()))
                         .collect();
                     // Learn a codebook for the subspace using LBG quantization.
-                    lbg_quantize(&sub_training, k, max_iters, seed + i as u64)
+                    // When warm-starting, refine the codebooks of the previous iteration.
+                    let start_from = initial.map(|_| previous[i].as_slice());
+                    lbg_quantize(
+                        &sub_training,
+                        k,
+                        max_iters,
+                        &distance,
+                        seed + i as u64,
+                        start_from,
+                        &|iteration, distortion| {
+                            progress.iteration(round, i, iteration, distortion)
+                        },
+                    )
                 })
-                .collect();
+                .unzip();
+            if progress.is_cancelled() {
+                return Err(VqError::Cancelled);
+            }
 
             // --- Reconstruction ---
             // For each rotated vector, compute its reconstruction using the current codebooks.
-            let reconstructions: Vec<Vector<f32>> = rotated_data
+            let (reconstructions, errors): (Vec<Vector<f32>>, Vec<f32>) = rotated_data
                 .par_iter()
                 .map(|v| {
-                    let mut rec = Vec::with_capacity(dim);
+                    let mut rec = Vec::with_capacity(out_dim);
                     // Use enumerate to iterate over codebooks.
                     for (i, codebook) in codebooks.iter().enumerate() {
-                        let start = i * sub_dim;
-                        let end = start + sub_dim;
-                        let sub_vector = &v.data[start..end];
+                        let sub_vector = &v.data[boundaries[i]..boundaries[i + 1]];
                         let mut best_index = 0;
                         let mut best_dist = distance.compute(sub_vector, &codebook[0].data);
                         for (j, centroid) in codebook.iter().enumerate().skip(1) {
@@ -163,51 +552,63 @@ impl OptimizedProductQuantizer {
                         }
                         rec.extend_from_slice(&codebook[best_index].data);
                     }
-                    Vector::new(rec)
+                    let error = distance.compute(&v.data, &rec);
+                    (Vector::new(rec), error)
                 })
-                .collect();
+                .unzip();
+            let distortion = errors.iter().sum::<f32>() / n as f32;
+            rounds.push(RoundReport {
+                clusterings,
+                distortion,
+            });
+            if !progress.round(round, distortion) {
+                return Err(VqError::Cancelled);
+            }
+
+            // The codebooks of the last iteration must match the final rotation.
+            if round + 1 == opq_iters {
+                break;
+            }
 
             // --- Rotation Update ---
-            // Prepare data matrices: x_mat for rotated_data, y_mat for reconstructions.
+            // The rotation R minimizing ||Y - R X||² over the original data X and the
+            // reconstructions Y is U Vᵀ, where U Σ Vᵀ is the SVD of Y Xᵀ (orthogonal Procrustes).
+            // For a projection, the thin SVD gives a `d_out x dim` matrix with orthonormal rows.
             let mut x_data: Vec<f32> = Vec::with_capacity(dim * n);
-            let mut y_data: Vec<f32> = Vec::with_capacity(dim * n);
-            // Flatten rotated_data and reconstructions.
-            rotated_data.iter().for_each(|v| x_data.extend(&v.data));
+            let mut y_data: Vec<f32> = Vec::with_capacity(out_dim * n);
+            // Flatten the original data and the reconstructions.
+            training_data.iter().for_each(|v| x_data.extend(&v.data));
             reconstructions.iter().for_each(|v| y_data.extend(&v.data));
             let x_mat = DMatrix::from_column_slice(dim, n, &x_data);
-            let y_mat = DMatrix::from_column_slice(dim, n, &y_data);
+            let y_mat = DMatrix::from_column_slice(out_dim, n, &y_data);
             let a: DMatrix<f32> = &y_mat * x_mat.transpose();
             let svd = a.svd(true, true);
             let u = svd.u.expect("SVD failed to produce U");
             let v_t = svd.v_t.expect("SVD failed to produce Vᵀ");
-            rotation = v_t.transpose() * u.transpose();
+            rotation = u * v_t;
 
             // --- Re-rotate the Original Data ---
-            rotated_data = training_data
-                .par_iter()
-                .map(|v| {
-                    let x = DMatrix::from_column_slice(dim, 1, &v.data);
-                    let y = &rotation * x;
-                    let y_vec: Vec<f32> = y.column(0).iter().cloned().collect();
-                    Vector::new(y_vec)
-                })
-                .collect();
+            rotated_data = rotate(training_data, &rotation);
         }
 
-        Self {
+        let opq = Self {
             rotation,
             codebooks,
-            sub_dim,
-            m,
+            boundaries: boundaries.to_vec(),
             dim,
             distance,
-        }
+        };
+        let report = TrainingReport {
+            rounds,
+            elapsed: start_time.elapsed(),
+        };
+        Ok((opq, report))
     }
 
     /// Quantizes an input vector using the learned rotation and codebooks.
     ///
     /// The input vector is first rotated using the learned rotation matrix. It is then partitioned into `m`
-    /// sub-vectors, each of dimension `sub_dim`. For each subspace, the nearest codeword is selected using the
+    /// sub-vectors along the subspace boundaries. For each subspace, the nearest codeword is selected using the
     /// stored distance metric. The selected codewords (one from each subspace) are concatenated and converted
     /// to half-precision (`f16`), resulting in the final quantized representation.
     ///
@@ -215,51 +616,172 @@ impl OptimizedProductQuantizer {
     /// - `vector`: The input vector (`Vector<f32>`) to be quantized.
     ///
     /// # Returns
-    /// A quantized vector (`Vector<f16>`) representing the input vector.
+    /// A quantized vector (`Vector<f16>`) representing the input vector in the rotated space, with
+    /// `d_out` dimensions. Use `decode` for a reconstruction in the original space.
     ///
     /// # Panics
     /// Panics with a custom error if the input vector's dimension does not match the expected dimension.
     pub fn quantize(&self, vector: &Vector<f32>) -> Vector<f16> {
-        if vector.len() != self.dim {
+        let quantized_data = self
+            .encode_indices(vector)
+            .into_iter()
+            .zip(self.codebooks.iter())
+            .flat_map(|(index, codebook)| codebook[index].data.iter().map(|&v| f16::from_f32(v)))
+            .collect();
+        Vector::new(quantized_data)
+    }
+
+    /// Encodes an input vector as the packed codeword indices of its rotated subspaces.
+    ///
+    /// Each index uses `ceil(log2(k))` bits.
+    ///
+    /// # Panics
+    /// Panics with a custom error if the input vector's dimension does not match the expected dimension.
+    pub fn encode(&self, vector: &Vector<f32>) -> PackedCodes {
+        PackedCodes::from_codes(
+            self.code_bits(),
+            self.encode_indices(vector).into_iter().map(|i| i as u16),
+        )
+    }
+
+    /// Reconstructs a vector in the original space from its packed codes.
+    ///
+    /// The selected codewords are concatenated and rotated back with the transposed rotation.
+    /// With a projection, the result lies in the subspace spanned by its rows.
+    ///
+    /// # Panics
+    /// Panics with a custom error if the number of codes is not `m`, the bit width does not match
+    /// the codebooks, or a code is out of range.
+    pub fn decode(&self, codes: &PackedCodes) -> Vector<f32> {
+        if codes.len() != self.codebooks.len() {
             panic!(
                 "{}",
                 VqError::DimensionMismatch {
-                    expected: self.dim,
-                    found: vector.len()
+                    expected: self.codebooks.len(),
+                    found: codes.len()
                 }
             );
         }
-        let x = DMatrix::from_column_slice(self.dim, 1, &vector.data);
-        let y = &self.rotation * x;
-        let y_vec: Vec<f32> = y.column(0).iter().cloned().collect();
-        if y_vec.len() != self.sub_dim * self.m {
+        if codes.bits() != self.code_bits() {
             panic!(
                 "{}",
-                VqError::DimensionMismatch {
-                    expected: self.sub_dim * self.m,
-                    found: y_vec.len()
-                }
+                VqError::InvalidParameter(format!(
+                    "Expected {}-bit codes, got {}-bit codes",
+                    self.code_bits(),
+                    codes.bits()
+                ))
             );
         }
-        let mut quantized_data = Vec::with_capacity(y_vec.len());
-        // Use enumerate to iterate over the codebooks.
-        for (i, codebook) in self.codebooks.iter().enumerate() {
-            let start = i * self.sub_dim;
-            let end = start + self.sub_dim;
-            let sub_vector = &y_vec[start..end];
-            let mut best_index = 0;
-            let mut best_dist = self.distance.compute(sub_vector, &codebook[0].data);
-            for (j, centroid) in codebook.iter().enumerate().skip(1) {
-                let dist = self.distance.compute(sub_vector, &centroid.data);
-                if dist < best_dist {
-                    best_dist = dist;
-                    best_index = j;
-                }
-            }
-            for &val in &codebook[best_index].data {
-                quantized_data.push(f16::from_f32(val));
+        let mut rotated = Vec::with_capacity(self.rotation.nrows());
+        for (c, codebook) in codes.iter().zip(self.codebooks.iter()) {
+            match codebook.get(c as usize) {
+                Some(codeword) => rotated.extend_from_slice(&codeword.data),
+                None => panic!(
+                    "{}",
+                    VqError::InvalidParameter(format!(
+                        "Code {} is out of range for a codebook of {} codewords",
+                        c,
+                        codebook.len()
+                    ))
+                ),
             }
         }
-        Vector::new(quantized_data)
+        let y = DMatrix::from_column_slice(rotated.len(), 1, &rotated);
+        let x = self.rotation.transpose() * y;
+        Vector::new(x.column(0).iter().cloned().collect())
+    }
+
+    /// Rotates a vector and selects the nearest codeword of each subspace.
+    fn encode_indices(&self, vector: &Vector<f32>) -> Vec<usize> {
+        let y_vec = self.project(vector).data;
+        self.codebooks
+            .iter()
+            .enumerate()
+            .map(|(i, codebook)| {
+                let sub_vector = &y_vec[self.boundaries[i]..self.boundaries[i + 1]];
+                let mut best_index = 0;
+                let mut best_dist = self.distance.compute(sub_vector, &codebook[0].data);
+                for (j, centroid) in codebook.iter().enumerate().skip(1) {
+                    let dist = self.distance.compute(sub_vector, &centroid.data);
+                    if dist < best_dist {
+                        best_dist = dist;
+                        best_index = j;
+                    }
+                }
+                best_index
+            })
+            .collect()
+    }
+
+    /// The number of bits per subspace index.
+    fn code_bits(&self) -> u8 {
+        bits_for(self.codebooks.iter().map(|c| c.len()).max().unwrap_or(1))
+    }
+}
+
+/// Computes the parametric OPQ rotation by eigenvalue allocation.
+///
+/// The principal components of the training data are sorted by decreasing eigenvalue, and each
+/// one is assigned to the subspace with the least assigned variance (sum of eigenvalues) among
+/// the subspaces that still have free dimensions. The rows of the returned rotation are the
+/// principal components, grouped by subspace, so every subspace receives a similar share of the
+/// variance. If the boundaries end before the data dimension, only that many leading components
+/// are allocated.
+fn eigenvalue_allocation(training_data: &[Vector<f32>], boundaries: &[usize]) -> DMatrix<f32> {
+    let dim = training_data[0].len();
+    let n = training_data.len() as f64;
+    let mut mean = training_data.iter().fold(vec![0.0f64; dim], |mut acc, v| {
+        acc.iter_mut()
+            .zip(v.data.iter())
+            .for_each(|(a, &x)| *a += x as f64);
+        acc
+    });
+    mean.iter_mut().for_each(|m| *m /= n);
+    let covariance = training_data
+        .par_iter()
+        .fold(
+            || DMatrix::<f64>::zeros(dim, dim),
+            |mut acc, v| {
+                let centred = DVector::<f64>::from_iterator(
+                    dim,
+                    v.data.iter().zip(mean.iter()).map(|(&x, &m)| x as f64 - m),
+                );
+                acc.ger(1.0, &centred, &centred, 1.0);
+                acc
+            },
+        )
+        .reduce(|| DMatrix::zeros(dim, dim), |a, b| a + b)
+        / n;
+    let eigen = covariance.symmetric_eigen();
+    let mut order: Vec<usize> = (0..dim).collect();
+    order.sort_by(|&a, &b| eigen.eigenvalues[b].total_cmp(&eigen.eigenvalues[a]));
+
+    let m = boundaries.len() - 1;
+    let mut variances = vec![0.0f64; m];
+    let mut filled: Vec<usize> = boundaries[..m].to_vec();
+    let mut rotation = DMatrix::<f32>::zeros(boundaries[m], dim);
+    for &component in order.iter().take(boundaries[m]) {
+        let subspace = (0..m)
+            .filter(|&i| filled[i] < boundaries[i + 1])
+            .min_by(|&a, &b| variances[a].total_cmp(&variances[b]))
+            .unwrap();
+        variances[subspace] += eigen.eigenvalues[component].max(0.0);
+        for d in 0..dim {
+            rotation[(filled[subspace], d)] = eigen.eigenvectors[(d, component)] as f32;
+        }
+        filled[subspace] += 1;
     }
+    rotation
+}
+
+/// Applies a rotation matrix to each vector in parallel.
+fn rotate(data: &[Vector<f32>], rotation: &DMatrix<f32>) -> Vec<Vector<f32>> {
+    data.par_iter()
+        .map(|v| {
+            let x = DMatrix::from_column_slice(v.len(), 1, &v.data);
+            let y = rotation * x;
+            let y_vec: Vec<f32> = y.column(0).iter().cloned().collect();
+            Vector::new(y_vec)
+        })
+        .collect()
 }
@@ -0,0 +1,446 @@
+//! # Bit-Packed Code Arrays
+//!
+//! This module provides `PackedCodes`, a compact array of unsigned codes that all use the same
+//! bit width between 1 and 16. Codes are stored back to back in 64-bit words (least significant
+//! bits first), so a code may straddle two words. An array of `n` codes of `b` bits uses
+//! `ceil(n * b / 64)` words instead of one byte or more per code.
+//!
+//! The quantizers in this crate return their encoded output as `PackedCodes`: binary codes use
+//! 1 bit per element, scalar quantizers use `ceil(log2(levels))` bits per element, and codebook
+//! quantizers use `ceil(log2(k))` bits per codebook. `bits_for` gives the width needed for a
+//! number of levels.
+//!
+//! `MixedPackedCodes` stores a fixed number of codes whose widths differ by position, for
+//! example product quantization codes with codebooks of different sizes. The codes are stored
+//! back to back in the same way, so `n` codes use `ceil(Σ widths / 64)` words.
+//!
+//! # Errors
+//! The methods panic with custom errors from the exceptions module when:
+//! - The bit width is not between 1 and 16.
+//! - A code does not fit in the bit width.
+//! - An index is out of range.
+//! - The number of codes does not match the number of widths.
+//!
+//! # Example
+//! ```
+//! use vq::packing::{bits_for, PackedCodes};
+//!
+//! // Codes for 12 levels need 4 bits each.
+//! let bits = bits_for(12);
+//! assert_eq!(bits, 4);
+//!
+//! let mut codes = PackedCodes::from_codes(bits, [3, 11, 0, 7]);
+//! codes.push(9);
+//! codes.set(0, 5);
+//! assert_eq!(codes.get(0), 5);
+//! assert_eq!(codes.to_vec(), vec![5, 11, 0, 7, 9]);
+//! assert_eq!(codes.to_bytes().len(), 3);
+//! ```
+
+use crate::exceptions::VqError;
+
+/// Returns the number of bits needed to store codes for `levels` distinct values (at least 1).
+///
+/// # Panics
+/// Panics with a custom error if `levels` is zero or more than 65536.
+pub fn bits_for(levels: usize) -> u8 {
+    if levels == 0 || levels > 1 << 16 {
+        panic!(
+            "{}",
+            VqError::InvalidParameter(format!(
+                "Cannot pack codes for {} levels: between 1 and 65536 are supported",
+                levels
+            ))
+        );
+    }
+    ((usize::BITS - (levels - 1).leading_zeros()) as u8).max(1)
+}
+
+/// An array of codes packed with a fixed bit width between 1 and 16.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PackedCodes {
+    /// The number of bits per code.
+    bits: u8,
+    /// The number of codes.
+    len: usize,
+    /// The packed bits, least significant first.
+    words: Vec<u64>,
+}
+
+impl PackedCodes {
+    /// Creates an empty array of codes with the given bit width.
+    ///
+    /// # Panics
+    /// Panics with a custom error if `bits` is not between 1 and 16.
+    pub fn new(bits: u8) -> Self {
+        Self::with_capacity(bits, 0)
+    }
+
+    /// Creates an empty array of codes with room for `capacity` codes.
+    ///
+    /// # Panics
+    /// Panics with a custom error if `bits` is not between 1 and 16.
+    pub fn with_capacity(bits: u8, capacity: usize) -> Self {
+        if !(1..=16).contains(&bits) {
+            panic!(
+                "{}",
+                VqError::InvalidParameter(format!(
+                    "Bit width must be between 1 and 16, got {}",
+                    bits
+                ))
+            );
+        }
+        Self {
+            bits,
+            len: 0,
+            words: Vec::with_capacity((capacity * bits as usize).div_ceil(64)),
+        }
+    }
+
+    /// Packs a sequence of codes with the given bit width.
+    ///
+    /// # Panics
+    /// Panics with a custom error if `bits` is not between 1 and 16 or a code does not fit.
+    pub fn from_codes<I>(bits: u8, codes: I) -> Self
+    where
+        I: IntoIterator<Item = u16>,
+    {
+        let codes = codes.into_iter();
+        let mut packed = Self::with_capacity(bits, codes.size_hint().0);
+        for code in codes {
+            packed.push(code);
+        }
+        packed
+    }
+
+    /// Restores codes from the bytes returned by `to_bytes`.
+    ///
+    /// # Panics
+    /// Panics with a custom error if `bits` is not between 1 and 16 or `bytes` is too short for
+    /// `len` codes.
+    pub fn from_bytes(bits: u8, len: usize, bytes: &[u8]) -> Self {
+        let mut packed = Self::with_capacity(bits, len);
+        let needed = (len * bits as usize).div_ceil(8);
+        if bytes.len() < needed {
+            panic!(
+                "{}",
+                VqError::DimensionMismatch {
+                    expected: needed,
+                    found: bytes.len()
+                }
+            );
+        }
+        packed.words = words_from_bytes(&bytes[..needed]);
+        packed.len = len;
+        packed.clear_unused();
+        packed
+    }
+
+    /// Returns the number of bits per code.
+    pub fn bits(&self) -> u8 {
+        self.bits
+    }
+
+    /// Returns the number of codes.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if the array holds no codes.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the packed storage as 64-bit words.
+    pub fn words(&self) -> &[u64] {
+        &self.words
+    }
+
+    /// Returns the packed codes as `ceil(len * bits / 8)` little-endian bytes.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        bytes_from_words(&self.words, self.len * self.bits as usize)
+    }
+
+    /// Returns the code at `index`.
+    ///
+    /// # Panics
+    /// Panics with a custom error if `index` is out of range.
+    pub fn get(&self, index: usize) -> u16 {
+        self.check_index(index);
+        read_bits(&self.words, index * self.bits as usize, self.bits)
+    }
+
+    /// Replaces the code at `index`.
+    ///
+    /// # Panics
+    /// Panics with a custom error if `index` is out of range or `code` does not fit.
+    pub fn set(&mut self, index: usize, code: u16) {
+        self.check_index(index);
+        check_code(code, self.bits);
+        write_bits(&mut self.words, index * self.bits as usize, self.bits, code);
+    }
+
+    /// Appends a code.
+    ///
+    /// # Panics
+    /// Panics with a custom error if `code` does not fit in the bit width.
+    pub fn push(&mut self, code: u16) {
+        check_code(code, self.bits);
+        let end = (self.len + 1) * self.bits as usize;
+        if self.words.len() * 64 < end {
+            self.words.push(0);
+        }
+        self.len += 1;
+        self.set(self.len - 1, code);
+    }
+
+    /// Returns an iterator over the codes.
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = u16> + '_ {
+        (0..self.len).map(move |i| self.get(i))
+    }
+
+    /// Unpacks the codes into a vector.
+    pub fn to_vec(&self) -> Vec<u16> {
+        self.iter().collect()
+    }
+
+    fn check_index(&self, index: usize) {
+        if index >= self.len {
+            panic!(
+                "{}",
+                VqError::InvalidParameter(format!(
+                    "Code index {} is out of range for {} codes",
+                    index, self.len
+                ))
+            );
+        }
+    }
+
+    /// Zeroes the bits after the last code so that equal arrays compare equal.
+    fn clear_unused(&mut self) {
+        clear_unused(&mut self.words, self.len * self.bits as usize);
+    }
+}
+
+/// A fixed-length array of codes where each position has its own bit width between 1 and 16.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MixedPackedCodes {
+    /// The number of bits of the code at each position.
+    widths: Vec<u8>,
+    /// The packed bits, least significant first.
+    words: Vec<u64>,
+}
+
+impl MixedPackedCodes {
+    /// Packs one code per width.
+    ///
+    /// # Panics
+    /// Panics with a custom error if a width is not between 1 and 16, a code does not fit in its
+    /// width, or the number of codes does not match the number of widths.
+    pub fn from_codes<I>(widths: &[u8], codes: I) -> Self
+    where
+        I: IntoIterator<Item = u16>,
+    {
+        let mut packed = Self::zeros(widths);
+        let mut count = 0;
+        for (index, code) in codes.into_iter().enumerate() {
+            if index < widths.len() {
+                packed.set(index, code);
+            }
+            count += 1;
+        }
+        if count != widths.len() {
+            panic!(
+                "{}",
+                VqError::DimensionMismatch {
+                    expected: widths.len(),
+                    found: count
+                }
+            );
+        }
+        packed
+    }
+
+    /// Restores codes from the bytes returned by `to_bytes`.
+    ///
+    /// # Panics
+    /// Panics with a custom error if a width is not between 1 and 16 or `bytes` is too short.
+    pub fn from_bytes(widths: &[u8], bytes: &[u8]) -> Self {
+        let mut packed = Self::zeros(widths);
+        let needed = packed.total_bits().div_ceil(8);
+        if bytes.len() < needed {
+            panic!(
+                "{}",
+                VqError::DimensionMismatch {
+                    expected: needed,
+                    found: bytes.len()
+                }
+            );
+        }
+        packed.words = words_from_bytes(&bytes[..needed]);
+        let total = packed.total_bits();
+        clear_unused(&mut packed.words, total);
+        packed
+    }
+
+    /// Returns the bit width of each position.
+    pub fn widths(&self) -> &[u8] {
+        &self.widths
+    }
+
+    /// Returns the number of codes.
+    pub fn len(&self) -> usize {
+        self.widths.len()
+    }
+
+    /// Returns true if the array holds no codes.
+    pub fn is_empty(&self) -> bool {
+        self.widths.is_empty()
+    }
+
+    /// Returns the total number of bits used by the codes.
+    pub fn total_bits(&self) -> usize {
+        self.widths.iter().map(|&w| w as usize).sum()
+    }
+
+    /// Returns the packed storage as 64-bit words.
+    pub fn words(&self) -> &[u64] {
+        &self.words
+    }
+
+    /// Returns the packed codes as `ceil(total_bits / 8)` little-endian bytes.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        bytes_from_words(&self.words, self.total_bits())
+    }
+
+    /// Returns the code at `index`.
+    ///
+    /// # Panics
+    /// Panics with a custom error if `index` is out of range.
+    pub fn get(&self, index: usize) -> u16 {
+        read_bits(&self.words, self.offset(index), self.widths[index])
+    }
+
+    /// Replaces the code at `index`.
+    ///
+    /// # Panics
+    /// Panics with a custom error if `index` is out of range or `code` does not fit in its width.
+    pub fn set(&mut self, index: usize, code: u16) {
+        let offset = self.offset(index);
+        check_code(code, self.widths[index]);
+        write_bits(&mut self.words, offset, self.widths[index], code);
+    }
+
+    /// Returns an iterator over the codes.
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = u16> + '_ {
+        let mut offset = 0;
+        self.widths.iter().map(move |&width| {
+            let code = read_bits(&self.words, offset, width);
+            offset += width as usize;
+            code
+        })
+    }
+
+    /// Unpacks the codes into a vector.
+    pub fn to_vec(&self) -> Vec<u16> {
+        self.iter().collect()
+    }
+
+    /// Creates an array of zero codes with the given widths.
+    fn zeros(widths: &[u8]) -> Self {
+        if let Some(&width) = widths.iter().find(|w| !(1..=16).contains(*w)) {
+            panic!(
+                "{}",
+                VqError::InvalidParameter(format!(
+                    "Bit width must be between 1 and 16, got {}",
+                    width
+                ))
+            );
+        }
+        let total: usize = widths.iter().map(|&w| w as usize).sum();
+        Self {
+            widths: widths.to_vec(),
+            words: vec![0; total.div_ceil(64)],
+        }
+    }
+
+    /// Returns the position of the first bit of the code at `index`.
+    fn offset(&self, index: usize) -> usize {
+        if index >= self.widths.len() {
+            panic!(
+                "{}",
+                VqError::InvalidParameter(format!(
+                    "Code index {} is out of range for {} codes",
+                    index,
+                    self.widths.len()
+                ))
+            );
+        }
+        self.widths[..index].iter().map(|&w| w as usize).sum()
+    }
+}
+
+/// Reads the `width`-bit code starting at bit `bit`.
+fn read_bits(words: &[u64], bit: usize, width: u8) -> u16 {
+    let (word, offset) = (bit / 64, bit % 64);
+    let mut value = words[word] >> offset;
+    if offset + width as usize > 64 {
+        value |= words[word + 1] << (64 - offset);
+    }
+    (value & mask(width)) as u16
+}
+
+/// Writes the `width`-bit code starting at bit `bit`.
+fn write_bits(words: &mut [u64], bit: usize, width: u8, code: u16) {
+    let (word, offset) = (bit / 64, bit % 64);
+    let mask = mask(width);
+    let code = code as u64;
+    words[word] = (words[word] & !(mask << offset)) | (code << offset);
+    if offset + width as usize > 64 {
+        let shift = 64 - offset;
+        words[word + 1] = (words[word + 1] & !(mask >> shift)) | (code >> shift);
+    }
+}
+
+fn mask(width: u8) -> u64 {
+    (1u64 << width) - 1
+}
+
+fn check_code(code: u16, width: u8) {
+    if code as u64 > mask(width) {
+        panic!(
+            "{}",
+            VqError::InvalidParameter(format!("Code {} does not fit in {} bits", code, width))
+        );
+    }
+}
+
+fn words_from_bytes(bytes: &[u8]) -> Vec<u64> {
+    bytes
+        .chunks(8)
+        .map(|chunk| {
+            chunk
+                .iter()
+                .enumerate()
+                .fold(0u64, |word, (i, &b)| word | (b as u64) << (8 * i))
+        })
+        .collect()
+}
+
+fn bytes_from_words(words: &[u64], used_bits: usize) -> Vec<u8> {
+    words
+        .iter()
+        .flat_map(|w| w.to_le_bytes())
+        .take(used_bits.div_ceil(8))
+        .collect()
+}
+
+/// Drops the words after the last used bit and zeroes the unused bits of the last word.
+fn clear_unused(words: &mut Vec<u64>, used_bits: usize) {
+    words.truncate(used_bits.div_ceil(64));
+    if used_bits % 64 != 0 {
+        if let Some(last) = words.last_mut() {
+            *last &= (1u64 << (used_bits % 64)) - 1;
+        }
+    }
+}
@@ -6,11 +6,43 @@
 //! the best matching centroid (codeword) for each subspace using a specified distance metric,
 //! and then concatenating these codewords (converted to half-precision, `f16`).
 //!
+//! The subspaces do not need to have the same dimension. `fit` splits the dimensions into `m`
+//! contiguous subspaces whose sizes differ by at most one, so any dimension of at least `m` is
+//! supported, and `fit_with_boundaries` accepts explicit subspace boundaries.
+//!
+//! The codebooks do not need to have the same size either. `fit_with_codebook_sizes` takes one
+//! size per subspace, and `fit_with_bit_budget` splits a total number of bits between the
+//! subspaces according to their variance: each extra bit roughly divides the distortion of a
+//! `d`-dimensional subspace by `2^(2/d)`, so bits are given one at a time to the subspace where
+//! they reduce the expected distortion the most. `encode_mixed` packs each code with
+//! `ceil(log2(k_i))` bits, and the rows of `distance_table` have one entry per centroid.
+//!
+//! For maximum inner product search (MIPS), `fit_anisotropic` trains and encodes with the
+//! score-aware (anisotropic) loss of ScaNN instead of a distance metric. The quantization error
+//! `r = x - x̃` is split into a component parallel to the datapoint `x` and a component orthogonal
+//! to it, and the loss is `η ||r_∥||² + ||r_⊥||²`. The parallel error changes the inner product
+//! with queries that are similar to `x`, which are the ones that decide the ranking, so it is
+//! weighted by `η = (d - 1) T² / (1 - T²)` for a threshold `T` in `(0, 1)` on the normalized
+//! inner products that matter. Because `r_∥` couples all subspaces, encoding uses coordinate
+//! descent over the subspaces, and each centroid is updated by solving a small linear system.
+//!
+//! Codes can also be compared without a query table. `polysemous` reorders the centroids of
+//! each codebook so that the Hamming distance between two code indices approximates the distance
+//! between their centroids. The same codes can then be filtered with `hamming_distance` and
+//! ranked with asymmetric distance computation (`distance_table` and `adc_distance`).
+//!
 //! # Errors
 //! The `fit` and `quantize` methods panic with custom errors from the exceptions module when:
 //! - The training data is empty.
-//! - The dimension of the training vectors is less than `m` or not divisible by `m`.
+//! - The dimension of the training vectors is less than `m`, or the subspace boundaries do not
+//!   increase strictly from 0 to the dimension.
+//! - The number of codebook sizes does not match the number of subspaces, or a bit budget
+//!   cannot give every subspace between 1 and `min(16, floor(log2(n)))` bits.
+//! - The distance metric has no suitable centroid update for codebook training
+//!   (Chebyshev, Minkowski, or Hamming).
 //! - The input vector to `quantize` does not have the expected dimension.
+//! - The anisotropic threshold `T` is not in `(0, 1)`.
+//! - A codebook reordered by `polysemous` has more than `MAX_POLYSEMOUS_CODEBOOK_SIZE` centroids.
 //!
 //! # Example
 //! ```
@@ -43,21 +75,42 @@
 //! ```
 
 use crate::distances::Distance;
-use crate::exceptions::VqError;
-use crate::utils::lbg_quantize;
+use crate::exceptions::{VqError, VqResult};
+use crate::packing::{bits_for, MixedPackedCodes, PackedCodes};
+use crate::progress::{Progress, TrainingObserver};
+use crate::report::{ClusteringReport, RoundReport, TrainingReport};
+use crate::utils::{balanced_boundaries, check_boundaries, data_dim, lbg_quantize};
 use crate::vector::Vector;
 use half::f16;
+use nalgebra::{DMatrix, DVector};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 use rayon::prelude::*;
+use std::ops::Range;
+use std::time::Instant;
+
+/// The maximum number of coordinate descent sweeps over the subspaces when encoding with the
+/// anisotropic loss.
+const ANISOTROPIC_SWEEPS: usize = 10;
 
+/// The initial annealing temperature for the polysemous reordering, relative to the mean cost
+/// of a pair of centroids.
+const POLYSEMOUS_TEMPERATURE: f64 = 0.7;
+
+/// The largest codebook that `polysemous` reorders. The reordering keeps a `k × k` table of
+/// centroid distances, which grows too large beyond this size.
+pub const MAX_POLYSEMOUS_CODEBOOK_SIZE: usize = 4096;
+
+#[derive(Clone)]
 pub struct ProductQuantizer {
     /// A vector of codebooks (one per subspace). Each codebook is a vector of centroids.
     codebooks: Vec<Vec<Vector<f32>>>,
-    /// The dimensionality of each subspace (i.e. `n / m`).
-    sub_dim: usize,
-    /// The number of subspaces into which the input vector is partitioned.
-    m: usize,
+    /// The `m + 1` subspace boundaries. Subspace `i` covers dimensions `boundaries[i]..boundaries[i + 1]`.
+    boundaries: Vec<usize>,
     /// The distance metric used for comparing subvectors with codebook centroids.
     distance: Distance,
+    /// The weight `η` of the parallel error if the quantizer uses the anisotropic loss.
+    anisotropic: Option<f32>,
 }
 
 impl ProductQuantizer {
@@ -65,17 +118,19 @@ impl ProductQuantizer {
     ///
     /// # Parameters
     /// - `training_data`: A slice of training vectors (`Vector<f32>`) used to learn the codebooks.
-    /// - `m`: The number of subspaces into which the input vectors are partitioned.
+    /// - `m`: The number of subspaces into which the input vectors are partitioned. If the
+    ///   dimension is not divisible by `m`, the first subspaces get one extra dimension.
     /// - `k`: The number of centroids (codewords) per subspace.
     /// - `max_iters`: The maximum number of iterations for the LBG (k-means) quantization algorithm.
     /// - `distance`: The distance metric used for comparing subvectors with codebook centroids.
+    ///   Codebooks are trained for the same metric (see `lbg_quantize`).
     /// - `seed`: A random seed for initializing LBG quantization. Each subspace uses `seed + i`.
     ///
     /// # Panics
     /// Panics with a custom error if:
     /// - The training data is empty.
+    /// - The distance metric is not supported for codebook training.
     /// - The dimension of the training vectors is less than `m`.
-    /// - The dimension of the training vectors is not divisible by `m`.
     pub fn fit(
         training_data: &[Vector<f32>],
         m: usize,
@@ -84,53 +139,553 @@ impl ProductQuantizer {
         distance: Distance,
         seed: u64,
     ) -> Self {
-        if training_data.is_empty() {
+        Self::fit_with_report(training_data, m, k, max_iters, distance, seed).0
+    }
+
+    /// Constructs a new `ProductQuantizer` like `fit` and also returns a `TrainingReport`.
+    ///
+    /// The report holds a single round with one `ClusteringReport` per subspace. The round
+    /// distortion is the mean distance between the training vectors and their quantized
+    /// reconstructions.
+    ///
+    /// # Parameters
+    /// Same as `fit`.
+    ///
+    /// # Panics
+    /// Same conditions as `fit`.
+    pub fn fit_with_report(
+        training_data: &[Vector<f32>],
+        m: usize,
+        k: usize,
+        max_iters: usize,
+        distance: Distance,
+        seed: u64,
+    ) -> (Self, TrainingReport) {
+        let progress = Progress::new(None);
+        Self::train(
+            training_data,
+            &balanced_boundaries(data_dim(training_data), m),
+            &vec![k; m],
+            max_iters,
+            distance,
+            seed,
+            None,
+            &progress,
+        )
+        .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Constructs a new `ProductQuantizer` with explicit subspace boundaries.
+    ///
+    /// # Parameters
+    /// - `training_data`: A slice of training vectors (`Vector<f32>`) used to learn the codebooks.
+    /// - `boundaries`: The `m + 1` subspace boundaries, increasing strictly from 0 to the data
+    ///   dimension. Subspace `i` covers dimensions `boundaries[i]..boundaries[i + 1]`.
+    /// - `k`, `max_iters`, `distance`, `seed`: Same as `fit`.
+    ///
+    /// # Panics
+    /// Panics with a custom error if the boundaries are invalid for the training data dimension,
+    /// or under the same conditions as `fit`.
+    pub fn fit_with_boundaries(
+        training_data: &[Vector<f32>],
+        boundaries: &[usize],
+        k: usize,
+        max_iters: usize,
+        distance: Distance,
+        seed: u64,
+    ) -> Self {
+        let ks = vec![k; boundaries.len().saturating_sub(1)];
+        Self::fit_with_codebook_sizes(training_data, boundaries, &ks, max_iters, distance, seed)
+    }
+
+    /// Constructs a new `ProductQuantizer` with explicit subspace boundaries and codebook sizes.
+    ///
+    /// # Parameters
+    /// - `training_data`: A slice of training vectors (`Vector<f32>`) used to learn the codebooks.
+    /// - `boundaries`: The `m + 1` subspace boundaries (see `fit_with_boundaries`).
+    /// - `ks`: The number of centroids of each of the `m` subspaces.
+    /// - `max_iters`, `distance`, `seed`: Same as `fit`.
+    ///
+    /// # Panics
+    /// Panics with a custom error if the boundaries are invalid for the training data dimension,
+    /// `ks` does not hold one size per subspace, or under the same conditions as `fit`.
+    pub fn fit_with_codebook_sizes(
+        training_data: &[Vector<f32>],
+        boundaries: &[usize],
+        ks: &[usize],
+        max_iters: usize,
+        distance: Distance,
+        seed: u64,
+    ) -> Self {
+        let progress = Progress::new(None);
+        Self::train(
+            training_data,
+            boundaries,
+            ks,
+            max_iters,
+            distance,
+            seed,
+            None,
+            &progress,
+        )
+        .unwrap_or_else(|e| panic!("{}", e))
+        .0
+    }
+
+    /// Constructs a new `ProductQuantizer` whose codebook sizes are chosen from the subspace
+    /// variances under a total bit budget.
+    ///
+    /// Every subspace starts with 1 bit. Each remaining bit goes to the subspace with the largest
+    /// expected distortion reduction `V_i 2^(-2 b_i / d_i) (1 - 2^(-2 / d_i))`, where `V_i` is
+    /// the total variance, `d_i` the dimension, and `b_i` the current bits of subspace `i`.
+    /// Subspace `i` then gets `2^b_i` centroids.
+    ///
+    /// # Parameters
+    /// - `training_data`: A slice of training vectors (`Vector<f32>`) used to learn the codebooks.
+    /// - `m`: The number of subspaces (split as in `fit`).
+    /// - `total_bits`: The total number of bits of a code, summed over the subspaces.
+    /// - `max_iters`, `distance`, `seed`: Same as `fit`.
+    ///
+    /// # Panics
+    /// Panics with a custom error if `total_bits` is less than `m` or more than `m` times
+    /// `min(16, floor(log2(n)))` for `n` training vectors, or under the same conditions as `fit`.
+    pub fn fit_with_bit_budget(
+        training_data: &[Vector<f32>],
+        m: usize,
+        total_bits: usize,
+        max_iters: usize,
+        distance: Distance,
+        seed: u64,
+    ) -> Self {
+        let boundaries = balanced_boundaries(data_dim(training_data), m);
+        let max_bits = (training_data.len().ilog2() as usize).min(16);
+        if total_bits < m || total_bits > m * max_bits {
+            panic!(
+                "{}",
+                VqError::InvalidParameter(format!(
+                    "Bit budget must be between {} and {} for {} subspaces and {} training vectors, got {}",
+                    m,
+                    m * max_bits,
+                    m,
+                    training_data.len(),
+                    total_bits
+                ))
+            );
+        }
+        let variances = subspace_variances(training_data, &boundaries);
+        let bits = allocate_bits(&variances, &boundaries, total_bits, max_bits);
+        let ks: Vec<usize> = bits.iter().map(|&b| 1 << b).collect();
+        Self::fit_with_codebook_sizes(training_data, &boundaries, &ks, max_iters, distance, seed)
+    }
+
+    /// Constructs a new `ProductQuantizer` like `fit_with_report` while reporting progress.
+    ///
+    /// The observer receives an event after every LBG iteration of every subspace and after
+    /// the final reconstruction pass. Subspaces are trained in parallel, so events from
+    /// different subspaces may interleave.
+    ///
+    /// # Parameters
+    /// Same as `fit`, plus:
+    /// - `observer`: Receives progress events and may request cancellation.
+    ///
+    /// # Errors
+    /// Returns `VqError::Cancelled` if the observer requested cancellation.
+    ///
+    /// # Panics
+    /// Same conditions as `fit`.
+    pub fn fit_with_observer(
+        training_data: &[Vector<f32>],
+        m: usize,
+        k: usize,
+        max_iters: usize,
+        distance: Distance,
+        seed: u64,
+        observer: &dyn TrainingObserver,
+    ) -> VqResult<(Self, TrainingReport)> {
+        let progress = Progress::new(Some(observer));
+        Self::train(
+            training_data,
+            &balanced_boundaries(data_dim(training_data), m),
+            &vec![k; m],
+            max_iters,
+            distance,
+            seed,
+            None,
+            &progress,
+        )
+    }
+
+    /// Constructs a new `ProductQuantizer` that minimizes the anisotropic loss for inner product search.
+    ///
+    /// Codebooks are first learned with the LBG algorithm and the squared Euclidean distance.
+    /// Then, for up to `max_iters` iterations, every training vector is encoded with the
+    /// anisotropic loss and each centroid is set to the minimizer of the loss over its vectors.
+    /// The quantizer also uses the anisotropic loss to encode vectors in `quantize`.
+    ///
+    /// # Parameters
+    /// - `training_data`: A slice of training vectors (`Vector<f32>`) used to learn the codebooks.
+    /// - `m`: The number of subspaces into which the input vectors are partitioned.
+    /// - `k`: The number of centroids (codewords) per subspace.
+    /// - `max_iters`: The maximum number of LBG iterations and of anisotropic iterations.
+    /// - `threshold`: The threshold `T` in `(0, 1)` on the normalized inner products that should
+    ///   be preserved. Larger values weight the parallel error more.
+    /// - `seed`: A random seed for initializing LBG quantization. Each subspace uses `seed + i`.
+    ///
+    /// # Panics
+    /// Panics with a custom error if `threshold` is not in `(0, 1)` or under the same conditions as `fit`.
+    pub fn fit_anisotropic(
+        training_data: &[Vector<f32>],
+        m: usize,
+        k: usize,
+        max_iters: usize,
+        threshold: f32,
+        seed: u64,
+    ) -> Self {
+        if !(threshold > 0.0 && threshold < 1.0) {
+            panic!(
+                "{}",
+                VqError::InvalidParameter(
+                    "Anisotropic threshold must be between 0 and 1".to_string()
+                )
+            );
+        }
+        let mut pq = Self::fit(
+            training_data,
+            m,
+            k,
+            max_iters,
+            Distance::SquaredEuclidean,
+            seed,
+        );
+        let d = training_data[0].len() as f32;
+        let t2 = threshold * threshold;
+        pq.anisotropic = Some((d - 1.0) * t2 / (1.0 - t2));
+        pq.anisotropic_training(training_data, max_iters);
+        pq
+    }
+
+    /// Constructs a new `ProductQuantizer` by refining existing codebooks on new training data.
+    ///
+    /// Instead of selecting random initial centroids, the LBG algorithm in each subspace starts
+    /// from the given codebook. This is useful for adapting a trained quantizer to drifted data,
+    /// where a few iterations on a new sample are usually enough.
+    ///
+    /// # Parameters
+    /// - `training_data`: A slice of training vectors (`Vector<f32>`) used to refine the codebooks.
+    /// - `codebooks`: The initial codebooks, one per subspace (for example from `codebooks()`).
+    ///   Their number sets `m`, the dimensions of their centroids set the subspace boundaries, and
+    ///   their sizes set the number of centroids of each subspace.
+    /// - `max_iters`: The maximum number of LBG iterations per subspace.
+    /// - `distance`: The distance metric used for training and for comparing subvectors.
+    /// - `seed`: A random seed used to reseed empty clusters. Each subspace uses `seed + i`.
+    ///
+    /// # Panics
+    /// Panics with a custom error if:
+    /// - The training data or any codebook is empty.
+    /// - The training data dimension does not match the total dimension of the codebooks.
+    /// - A subspace has fewer training vectors than codewords.
+    /// - The distance metric is not supported for codebook training.
+    pub fn warm_start(
+        training_data: &[Vector<f32>],
+        codebooks: &[Vec<Vector<f32>>],
+        max_iters: usize,
+        distance: Distance,
+        seed: u64,
+    ) -> Self {
+        if training_data.is_empty()
+            || codebooks.is_empty()
+            || codebooks.iter().any(|c| c.is_empty())
+        {
             panic!("{}", VqError::EmptyInput);
         }
-        let n = training_data[0].len();
-        if n < m {
+        let mut boundaries = vec![0];
+        for codebook in codebooks {
+            boundaries.push(boundaries[boundaries.len() - 1] + codebook[0].len());
+        }
+        let expected = boundaries[boundaries.len() - 1];
+        if training_data[0].len() != expected {
+            panic!(
+                "{}",
+                VqError::DimensionMismatch {
+                    expected,
+                    found: training_data[0].len()
+                }
+            );
+        }
+        let progress = Progress::new(None);
+        let ks: Vec<usize> = codebooks.iter().map(|c| c.len()).collect();
+        Self::train(
+            training_data,
+            &boundaries,
+            &ks,
+            max_iters,
+            distance,
+            seed,
+            Some(codebooks),
+            &progress,
+        )
+        .unwrap_or_else(|e| panic!("{}", e))
+        .0
+    }
+
+    /// Refines this quantizer's codebooks on new training data (see `warm_start`).
+    ///
+    /// A quantizer trained with `fit_anisotropic` keeps its anisotropic loss, and the refined
+    /// codebooks are also updated with it.
+    ///
+    /// # Panics
+    /// Same conditions as `warm_start`.
+    pub fn refine(&self, training_data: &[Vector<f32>], max_iters: usize, seed: u64) -> Self {
+        let mut pq = Self::warm_start(
+            training_data,
+            &self.codebooks,
+            max_iters,
+            self.distance,
+            seed,
+        );
+        if self.anisotropic.is_some() {
+            pq.anisotropic = self.anisotropic;
+            pq.anisotropic_training(training_data, max_iters);
+        }
+        pq
+    }
+
+    /// Returns the learned codebooks, one per subspace.
+    pub fn codebooks(&self) -> &[Vec<Vector<f32>>] {
+        &self.codebooks
+    }
+
+    /// Returns the `m + 1` subspace boundaries. Subspace `i` covers dimensions
+    /// `boundaries[i]..boundaries[i + 1]`.
+    pub fn boundaries(&self) -> &[usize] {
+        &self.boundaries
+    }
+
+    /// Returns the number of bits of each subspace code, `ceil(log2(k_i))`, as used by
+    /// `encode_mixed`.
+    pub fn code_widths(&self) -> Vec<u8> {
+        self.codebooks.iter().map(|c| bits_for(c.len())).collect()
+    }
+
+    /// Returns a copy of this quantizer whose codebooks are reordered for polysemous codes.
+    ///
+    /// In each subspace, the centroids are permuted so that the Hamming distance between two
+    /// code indices approximates the Euclidean distance between their centroids (rescaled to
+    /// the range of Hamming distances). The permutation is found by simulated annealing over
+    /// swaps of two centroids. Only the order of the centroids changes, so `quantize` returns
+    /// the same reconstructions as before.
+    ///
+    /// # Parameters
+    /// - `max_iters`: The number of swaps tried in each subspace.
+    /// - `seed`: A random seed for the annealing. Each subspace uses `seed + i`.
+    ///
+    /// # Panics
+    /// Panics with a custom error if a codebook has more than `MAX_POLYSEMOUS_CODEBOOK_SIZE`
+    /// centroids.
+    pub fn polysemous(&self, max_iters: usize, seed: u64) -> Self {
+        if let Some(codebook) = self
+            .codebooks
+            .iter()
+            .find(|c| c.len() > MAX_POLYSEMOUS_CODEBOOK_SIZE)
+        {
             panic!(
                 "{}",
-                VqError::InvalidParameter("Data dimension must be at least m".to_string())
+                VqError::InvalidParameter(format!(
+                    "Polysemous codes support at most {} centroids per codebook, got {}",
+                    MAX_POLYSEMOUS_CODEBOOK_SIZE,
+                    codebook.len()
+                ))
             );
         }
-        if n % m != 0 {
+        let codebooks = self
+            .codebooks
+            .par_iter()
+            .enumerate()
+            .map(|(i, codebook)| {
+                let codes = polysemous_codes(codebook, max_iters, seed + i as u64);
+                let mut reordered = codebook.clone();
+                for (centroid, &code) in codebook.iter().zip(codes.iter()) {
+                    reordered[code] = centroid.clone();
+                }
+                reordered
+            })
+            .collect();
+        Self {
+            codebooks,
+            ..self.clone()
+        }
+    }
+
+    /// Encodes an input vector as the index of the selected centroid in each subspace.
+    ///
+    /// The `m` indices are packed with `ceil(log2(k))` bits each, where `k` is the size of the
+    /// largest codebook. Use `encode_mixed` to give each subspace only the bits it needs.
+    ///
+    /// # Panics
+    /// Panics with a custom error if the input vector's dimension does not match the training data.
+    pub fn encode(&self, vector: &Vector<f32>) -> PackedCodes {
+        let k = self.codebooks.iter().map(|c| c.len()).max().unwrap_or(1);
+        PackedCodes::from_codes(
+            bits_for(k),
+            self.nearest_indices(vector).into_iter().map(|j| j as u16),
+        )
+    }
+
+    /// Encodes an input vector like `encode`, but packs the index of subspace `i` with
+    /// `ceil(log2(k_i))` bits (see `code_widths`).
+    ///
+    /// # Panics
+    /// Panics with a custom error if the input vector's dimension does not match the training data.
+    pub fn encode_mixed(&self, vector: &Vector<f32>) -> MixedPackedCodes {
+        MixedPackedCodes::from_codes(
+            &self.code_widths(),
+            self.nearest_indices(vector).into_iter().map(|j| j as u16),
+        )
+    }
+
+    /// Reconstructs a vector by concatenating the centroids selected by `codes`.
+    ///
+    /// # Panics
+    /// Panics with a custom error if the number of codes does not equal `m` or a code is out of range.
+    pub fn decode(&self, codes: &PackedCodes) -> Vector<f32> {
+        self.reconstruct(&self.unpack_codes(codes.iter()))
+    }
+
+    /// Reconstructs a vector from codes returned by `encode_mixed`.
+    ///
+    /// # Panics
+    /// Panics with a custom error if the number of codes does not equal `m` or a code is out of range.
+    pub fn decode_mixed(&self, codes: &MixedPackedCodes) -> Vector<f32> {
+        self.reconstruct(&self.unpack_codes(codes.iter()))
+    }
+
+    /// Computes the squared Euclidean distance between each query subvector and every centroid
+    /// of its subspace, for asymmetric distance computation (ADC).
+    ///
+    /// # Returns
+    /// A table with one row per subspace and one entry per centroid.
+    ///
+    /// # Panics
+    /// Panics with a custom error if the query's dimension does not match the training data.
+    pub fn distance_table(&self, query: &Vector<f32>) -> Vec<Vec<f32>> {
+        self.check_dim(query.len());
+        self.codebooks
+            .iter()
+            .enumerate()
+            .map(|(i, codebook)| {
+                let sub = &query.data[self.subspace(i)];
+                codebook
+                    .iter()
+                    .map(|c| Distance::SquaredEuclidean.compute(sub, &c.data))
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Returns the squared Euclidean distance between a query and an encoded vector by summing
+    /// the entries of the query's `distance_table` selected by `codes`.
+    ///
+    /// # Panics
+    /// Panics with a custom error if the number of codes does not equal `m` or a code is out of range.
+    pub fn adc_distance(&self, table: &[Vec<f32>], codes: &PackedCodes) -> f32 {
+        table_sum(table, &self.unpack_codes(codes.iter()))
+    }
+
+    /// Returns the squared Euclidean distance between a query and a vector encoded with
+    /// `encode_mixed` (see `adc_distance`).
+    ///
+    /// # Panics
+    /// Panics with a custom error if the number of codes does not equal `m` or a code is out of range.
+    pub fn adc_distance_mixed(&self, table: &[Vec<f32>], codes: &MixedPackedCodes) -> f32 {
+        table_sum(table, &self.unpack_codes(codes.iter()))
+    }
+
+    /// Trains the codebooks, starting from `initial` codebooks if given and forwarding
+    /// progress to `progress`.
+    #[allow(clippy::too_many_arguments)]
+    fn train(
+        training_data: &[Vector<f32>],
+        boundaries: &[usize],
+        ks: &[usize],
+        max_iters: usize,
+        distance: Distance,
+        seed: u64,
+        initial: Option<&[Vec<Vector<f32>>]>,
+        progress: &Progress,
+    ) -> VqResult<(Self, TrainingReport)> {
+        let start = Instant::now();
+        if training_data.is_empty() {
+            panic!("{}", VqError::EmptyInput);
+        }
+        check_boundaries(boundaries, training_data[0].len());
+        let m = boundaries.len() - 1;
+        if ks.len() != m {
             panic!(
                 "{}",
-                VqError::InvalidParameter("Data dimension must be divisible by m".to_string())
+                VqError::InvalidParameter(format!(
+                    "Expected {} codebook sizes, one per subspace, got {}",
+                    m,
+                    ks.len()
+                ))
             );
         }
-        let sub_dim = n / m;
 
         // Learn a codebook for each subspace in parallel.
-        let codebooks: Vec<Vec<Vector<f32>>> = (0..m)
+        let (codebooks, clusterings): (Vec<Vec<Vector<f32>>>, Vec<ClusteringReport>) = (0..m)
             .into_par_iter()
             .map(|i| {
                 // Extract the sub-training data for subspace `i`.
+                let (start, end) = (boundaries[i], boundaries[i + 1]);
                 let sub_training: Vec<Vector<f32>> = training_data
                     .iter()
-                    .map(|v| {
-                        let start = i * sub_dim;
-                        let end = start + sub_dim;
-                        Vector::new(v.data[start..end].to_vec())
-                    })
+                    .map(|v| Vector::new(v.data[start..end].to_vec()))
                     .collect();
                 // Learn a codebook for the subspace using LBG quantization.
-                lbg_quantize(&sub_training, k, max_iters, seed + i as u64)
+                lbg_quantize(
+                    &sub_training,
+                    ks[i],
+                    max_iters,
+                    &distance,
+                    seed + i as u64,
+                    initial.map(|c| c[i].as_slice()),
+                    &|iteration, distortion| progress.iteration(0, i, iteration, distortion),
+                )
             })
-            .collect();
+            .unzip();
+        if progress.is_cancelled() {
+            return Err(VqError::Cancelled);
+        }
 
-        Self {
+        let pq = Self {
             codebooks,
-            sub_dim,
-            m,
+            boundaries: boundaries.to_vec(),
             distance,
+            anisotropic: None,
+        };
+        let distortion = training_data
+            .par_iter()
+            .map(|v| {
+                let reconstruction = pq.reconstruct(&pq.nearest_indices(v));
+                distance.compute(&v.data, &reconstruction.data)
+            })
+            .sum::<f32>()
+            / training_data.len() as f32;
+        if !progress.round(0, distortion) {
+            return Err(VqError::Cancelled);
         }
+        let report = TrainingReport {
+            rounds: vec![RoundReport {
+                clusterings,
+                distortion,
+            }],
+            elapsed: start.elapsed(),
+        };
+        Ok((pq, report))
     }
 
     /// Quantizes an input vector using the learned codebooks.
     ///
-    /// The input vector is partitioned into `m` sub-vectors (each of dimension `sub_dim`).
+    /// The input vector is partitioned into `m` sub-vectors along the subspace boundaries.
     /// For each subspace, the best matching codeword is selected using the stored distance metric.
     /// The selected codewords are converted to half-precision (`f16`) and concatenated to form
     /// the final quantized representation.
@@ -142,26 +697,96 @@ impl ProductQuantizer {
     /// A quantized vector (`Vector<f16>`) representing the input vector.
     ///
     /// # Panics
-    /// Panics with a custom error if the input vector's dimension does not equal `m * sub_dim`.
+    /// Panics with a custom error if the input vector's dimension does not match the training data.
     pub fn quantize(&self, vector: &Vector<f32>) -> Vector<f16> {
-        let n = vector.len();
-        if n != self.sub_dim * self.m {
+        // Convert the chosen centroids' sub-vectors from f32 to f16 and concatenate them.
+        let quantized_data: Vec<f16> = self
+            .nearest_indices(vector)
+            .iter()
+            .enumerate()
+            .flat_map(|(i, &j)| self.codebooks[i][j].data.iter())
+            .map(|&val| f16::from_f32(val))
+            .collect();
+        Vector::new(quantized_data)
+    }
+
+    /// Concatenates the centroids selected by `codes`.
+    fn reconstruct(&self, codes: &[usize]) -> Vector<f32> {
+        Vector::new(
+            codes
+                .iter()
+                .enumerate()
+                .flat_map(|(i, &j)| self.codebooks[i][j].data.iter().copied())
+                .collect(),
+        )
+    }
+
+    fn unpack_codes(&self, codes: impl ExactSizeIterator<Item = u16>) -> Vec<usize> {
+        if codes.len() != self.codebooks.len() {
             panic!(
                 "{}",
                 VqError::DimensionMismatch {
-                    expected: self.sub_dim * self.m,
-                    found: n
+                    expected: self.codebooks.len(),
+                    found: codes.len()
                 }
             );
         }
+        let codes: Vec<usize> = codes.map(|j| j as usize).collect();
+        if codes
+            .iter()
+            .zip(self.codebooks.iter())
+            .any(|(&j, codebook)| j >= codebook.len())
+        {
+            panic!(
+                "{}",
+                VqError::InvalidParameter("Code is out of range for its codebook".to_string())
+            );
+        }
+        codes
+    }
+
+    /// Returns the dimensions covered by subspace `i`.
+    fn subspace(&self, i: usize) -> Range<usize> {
+        self.boundaries[i]..self.boundaries[i + 1]
+    }
+
+    fn check_dim(&self, len: usize) {
+        let dim = self.boundaries[self.boundaries.len() - 1];
+        if len != dim {
+            panic!(
+                "{}",
+                VqError::DimensionMismatch {
+                    expected: dim,
+                    found: len
+                }
+            );
+        }
+    }
+
+    /// Selects the index of the best matching centroid in each subspace.
+    ///
+    /// # Panics
+    /// Panics with a custom error if the input vector's dimension does not match the training data.
+    fn nearest_indices(&self, vector: &Vector<f32>) -> Vec<usize> {
+        let codes = self.metric_indices(vector);
+        match self.anisotropic {
+            Some(eta) => self.anisotropic_indices(&vector.data, codes, eta),
+            None => codes,
+        }
+    }
+
+    /// Selects the nearest centroid in each subspace using the distance metric alone.
+    ///
+    /// # Panics
+    /// Panics with a custom error if the input vector's dimension does not match the training data.
+    fn metric_indices(&self, vector: &Vector<f32>) -> Vec<usize> {
+        self.check_dim(vector.len());
 
         // Process each subspace in parallel to quantize the corresponding sub-vector.
-        let quantized_subs: Vec<Vec<f16>> = (0..self.m)
+        (0..self.codebooks.len())
             .into_par_iter()
             .map(|i| {
-                let start = i * self.sub_dim;
-                let end = start + self.sub_dim;
-                let sub_vector = &vector.data[start..end];
+                let sub_vector = &vector.data[self.subspace(i)];
                 let codebook = &self.codebooks[i];
                 let mut best_index = 0;
                 let mut best_dist = self.distance.compute(sub_vector, &codebook[0].data);
@@ -172,17 +797,308 @@ impl ProductQuantizer {
                         best_index = j;
                     }
                 }
-                // Convert the chosen centroid's sub-vector from f32 to f16.
-                codebook[best_index]
-                    .data
-                    .iter()
-                    .map(|&val| f16::from_f32(val))
-                    .collect()
+                best_index
             })
+            .collect()
+    }
+
+    /// Improves `codes` for the anisotropic loss by coordinate descent over the subspaces.
+    ///
+    /// With `w = (η - 1) / ||x||²`, the loss is `||r||² + w (r · x)²`. Each step picks the
+    /// centroid of one subspace that minimizes the loss while the other subspaces are fixed.
+    fn anisotropic_indices(&self, x: &[f32], mut codes: Vec<usize>, eta: f32) -> Vec<usize> {
+        let norm_sq: f32 = x.iter().map(|v| v * v).sum();
+        if norm_sq == 0.0 {
+            return codes;
+        }
+        let w = (eta - 1.0) / norm_sq;
+        // The parallel error r_i · x_i contributed by each subspace.
+        let mut parallel: Vec<f32> = codes
+            .iter()
+            .enumerate()
+            .map(|(i, &j)| self.residual_projection(x, i, j))
             .collect();
+        let mut total: f32 = parallel.iter().sum();
 
-        // Flatten the quantized sub-vectors into one contiguous vector.
-        let quantized_data: Vec<f16> = quantized_subs.into_iter().flatten().collect();
-        Vector::new(quantized_data)
+        for _ in 0..ANISOTROPIC_SWEEPS {
+            let mut changed = false;
+            for i in 0..self.codebooks.len() {
+                let others = total - parallel[i];
+                let sub = &x[self.subspace(i)];
+                let mut best = (codes[i], f32::INFINITY, parallel[i]);
+                for (j, centroid) in self.codebooks[i].iter().enumerate() {
+                    let error = Distance::SquaredEuclidean.compute(sub, &centroid.data);
+                    let p = self.residual_projection(x, i, j);
+                    let loss = error + w * (others + p) * (others + p);
+                    if loss < best.1 {
+                        best = (j, loss, p);
+                    }
+                }
+                if best.0 != codes[i] {
+                    codes[i] = best.0;
+                    changed = true;
+                }
+                total = others + best.2;
+                parallel[i] = best.2;
+            }
+            if !changed {
+                break;
+            }
+        }
+        codes
+    }
+
+    /// Returns `(x_i - c_ij) · x_i`, the parallel error of centroid `j` in subspace `i`.
+    fn residual_projection(&self, x: &[f32], i: usize, j: usize) -> f32 {
+        x[self.subspace(i)]
+            .iter()
+            .zip(self.codebooks[i][j].data.iter())
+            .map(|(&a, &c)| (a - c) * a)
+            .sum()
+    }
+
+    /// Alternates anisotropic encoding and centroid updates for up to `max_iters` iterations.
+    ///
+    /// For the vectors `X_c` assigned to centroid `c` of subspace `i`, setting the gradient of
+    /// the loss to zero gives the linear system
+    /// `(|X_c| I + Σ w x_i x_iᵀ) c = Σ x_i + Σ w (x_i · x_i + s) x_i`, where `s` is the parallel
+    /// error of the other subspaces. Centroids without vectors keep their value.
+    fn anisotropic_training(&mut self, training_data: &[Vector<f32>], max_iters: usize) {
+        let eta = match self.anisotropic {
+            Some(eta) => eta,
+            None => return,
+        };
+        let mut codes: Vec<Vec<usize>> = training_data
+            .par_iter()
+            .map(|v| self.nearest_indices(v))
+            .collect();
+
+        for _ in 0..max_iters {
+            let codebooks: Vec<Vec<Vector<f32>>> = (0..self.codebooks.len())
+                .into_par_iter()
+                .map(|i| {
+                    let k = self.codebooks[i].len();
+                    let range = self.subspace(i);
+                    let sub_dim = range.len();
+                    let mut systems: Vec<(DMatrix<f64>, DVector<f64>, usize)> =
+                        vec![(DMatrix::zeros(sub_dim, sub_dim), DVector::zeros(sub_dim), 0); k];
+                    for (v, code) in training_data.iter().zip(codes.iter()) {
+                        let norm_sq: f32 = v.data.iter().map(|x| x * x).sum();
+                        let w = if norm_sq > 0.0 {
+                            ((eta - 1.0) / norm_sq) as f64
+                        } else {
+                            0.0
+                        };
+                        let others: f32 = code
+                            .iter()
+                            .enumerate()
+                            .filter(|&(l, _)| l != i)
+                            .map(|(l, &j)| self.residual_projection(&v.data, l, j))
+                            .sum();
+                        let sub = DVector::<f64>::from_iterator(
+                            sub_dim,
+                            v.data[range.clone()].iter().map(|&x| x as f64),
+                        );
+                        let (a, b, count) = &mut systems[code[i]];
+                        *a += &sub * sub.transpose() * w;
+                        *b += &sub * (1.0 + w * (sub.dot(&sub) + others as f64));
+                        *count += 1;
+                    }
+                    systems
+                        .into_iter()
+                        .zip(self.codebooks[i].iter())
+                        .map(|((mut a, b, count), previous)| {
+                            if count == 0 {
+                                return previous.clone();
+                            }
+                            for d in 0..sub_dim {
+                                a[(d, d)] += count as f64;
+                            }
+                            match a.lu().solve(&b) {
+                                Some(c) => Vector::new(c.iter().map(|&x| x as f32).collect()),
+                                None => previous.clone(),
+                            }
+                        })
+                        .collect()
+                })
+                .collect();
+            self.codebooks = codebooks;
+
+            let updated: Vec<Vec<usize>> = training_data
+                .par_iter()
+                .map(|v| self.nearest_indices(v))
+                .collect();
+            let converged = updated == codes;
+            codes = updated;
+            if converged {
+                break;
+            }
+        }
+    }
+}
+
+/// Sums the entries of an ADC table selected by `codes`.
+fn table_sum(table: &[Vec<f32>], codes: &[usize]) -> f32 {
+    codes.iter().zip(table.iter()).map(|(&j, row)| row[j]).sum()
+}
+
+/// Returns the total variance of the training data within each subspace.
+fn subspace_variances(training_data: &[Vector<f32>], boundaries: &[usize]) -> Vec<f64> {
+    let dim = boundaries[boundaries.len() - 1];
+    let n = training_data.len() as f64;
+    let mut sum = vec![0.0f64; dim];
+    let mut sum_sq = vec![0.0f64; dim];
+    for v in training_data {
+        for (d, &x) in v.data.iter().enumerate() {
+            sum[d] += x as f64;
+            sum_sq[d] += (x as f64) * (x as f64);
+        }
+    }
+    let variance: Vec<f64> = (0..dim)
+        .map(|d| (sum_sq[d] / n - (sum[d] / n).powi(2)).max(0.0))
+        .collect();
+    boundaries
+        .windows(2)
+        .map(|w| variance[w[0]..w[1]].iter().sum())
+        .collect()
+}
+
+/// Splits `total_bits` between the subspaces, giving each bit to the subspace whose expected
+/// distortion `V_i 2^(-2 b_i / d_i)` it reduces the most. Every subspace gets between 1 and
+/// `max_bits` bits; the caller checks that the budget fits these limits.
+fn allocate_bits(
+    variances: &[f64],
+    boundaries: &[usize],
+    total_bits: usize,
+    max_bits: usize,
+) -> Vec<u8> {
+    let dims: Vec<f64> = boundaries
+        .windows(2)
+        .map(|w| (w[1] - w[0]) as f64)
+        .collect();
+    let mut bits = vec![1usize; variances.len()];
+    for _ in variances.len()..total_bits {
+        let gain = |i: usize| {
+            let distortion = variances[i] * (-2.0 * bits[i] as f64 / dims[i]).exp2();
+            distortion * (1.0 - (-2.0 / dims[i]).exp2())
+        };
+        let best = (0..bits.len())
+            .filter(|&i| bits[i] < max_bits)
+            .max_by(|&a, &b| gain(a).total_cmp(&gain(b)))
+            .expect("Bit budget exceeds the per-subspace limit");
+        bits[best] += 1;
+    }
+    bits.into_iter().map(|b| b as u8).collect()
+}
+
+/// Returns the number of differing bits between two codes, summed over the subspaces.
+///
+/// For a quantizer reordered with `ProductQuantizer::polysemous`, this approximates the distance
+/// between the encoded vectors and can be used to filter candidates before ADC.
+///
+/// # Panics
+/// Panics with a custom error if the codes have different lengths or bit widths.
+pub fn hamming_distance(a: &PackedCodes, b: &PackedCodes) -> u32 {
+    if a.len() != b.len() {
+        panic!(
+            "{}",
+            VqError::DimensionMismatch {
+                expected: a.len(),
+                found: b.len()
+            }
+        );
+    }
+    if a.bits() != b.bits() {
+        panic!(
+            "{}",
+            VqError::InvalidParameter(format!(
+                "Cannot compare codes of {} and {} bits",
+                a.bits(),
+                b.bits()
+            ))
+        );
+    }
+    a.iter()
+        .zip(b.iter())
+        .map(|(x, y)| (x ^ y).count_ones())
+        .sum()
+}
+
+/// Assigns a code to every centroid so that Hamming distances between codes approximate the
+/// distances between centroids.
+///
+/// The cost is `Σ (H(code_a, code_b) - f(d(a, b)))²` over all centroid pairs, where `f` rescales
+/// the centroid distances to the mean Hamming distance between codes. Simulated annealing tries
+/// `max_iters` swaps of the codes of two centroids and returns the best assignment found.
+fn polysemous_codes(codebook: &[Vector<f32>], max_iters: usize, seed: u64) -> Vec<usize> {
+    let k = codebook.len();
+    let mut codes: Vec<usize> = (0..k).collect();
+    if k < 3 {
+        return codes;
+    }
+    let hamming = |a: usize, b: usize| (a ^ b).count_ones() as f64;
+
+    let mut target = vec![vec![0.0f64; k]; k];
+    let (mut distance_sum, mut hamming_sum) = (0.0, 0.0);
+    for a in 0..k {
+        for b in (a + 1)..k {
+            let d = Distance::Euclidean.compute(&codebook[a].data, &codebook[b].data) as f64;
+            target[a][b] = d;
+            target[b][a] = d;
+            distance_sum += d;
+            hamming_sum += hamming(a, b);
+        }
+    }
+    if distance_sum == 0.0 {
+        return codes;
+    }
+    let ratio = hamming_sum / distance_sum;
+    target.iter_mut().flatten().for_each(|d| *d *= ratio);
+
+    let pair_cost = |codes: &[usize], a: usize, c: usize| {
+        let e = hamming(codes[a], codes[c]) - target[a][c];
+        e * e
+    };
+    let total_cost = |codes: &[usize]| {
+        (0..k)
+            .flat_map(|a| ((a + 1)..k).map(move |b| (a, b)))
+            .map(|(a, b)| pair_cost(codes, a, b))
+            .sum::<f64>()
+    };
+    let mut cost = total_cost(&codes);
+    let mut best = (codes.clone(), cost);
+    let pairs = (k * (k - 1) / 2) as f64;
+
+    let mut rng = StdRng::seed_from_u64(seed);
+    for t in 0..max_iters {
+        let a = rng.random_range(0..k);
+        let b = rng.random_range(0..k);
+        if a == b {
+            continue;
+        }
+        // Only pairs involving `a` or `b` change; the pair (a, b) keeps its Hamming distance.
+        let before: f64 = (0..k)
+            .filter(|&c| c != a && c != b)
+            .map(|c| pair_cost(&codes, a, c) + pair_cost(&codes, b, c))
+            .sum();
+        codes.swap(a, b);
+        let after: f64 = (0..k)
+            .filter(|&c| c != a && c != b)
+            .map(|c| pair_cost(&codes, a, c) + pair_cost(&codes, b, c))
+            .sum();
+        let delta = after - before;
+
+        let temperature =
+            POLYSEMOUS_TEMPERATURE * (1.0 - t as f64 / max_iters as f64) * cost / pairs;
+        if delta <= 0.0 || (temperature > 0.0 && rng.random::<f64>() < (-delta / temperature).exp())
+        {
+            cost += delta;
+            if cost < best.1 {
+                best = (codes.clone(), cost);
+            }
+        } else {
+            codes.swap(a, b);
+        }
     }
+    best.0
 }
@@ -0,0 +1,145 @@
+//! # Training Progress and Cancellation
+//!
+//! This module lets callers watch long training runs and stop them cleanly. A
+//! `TrainingObserver` receives a `ProgressEvent` after every LBG iteration and after every
+//! training round (a pass over all PQ subspaces, an OPQ iteration, or an RVQ stage). The
+//! observer answers with a `TrainingControl`; returning `TrainingControl::Cancel` makes the
+//! `fit_with_observer` methods stop at the next check and return `VqError::Cancelled`
+//! instead of a model. The training thread is never aborted.
+//!
+//! Closures of type `Fn(&ProgressEvent) -> TrainingControl` implement `TrainingObserver`.
+//! Codebooks of different subspaces are trained in parallel, so the observer may be called
+//! from several threads at once.
+//!
+//! # Example
+//! ```
+//! use std::sync::atomic::{AtomicUsize, Ordering};
+//! use vq::distances::Distance;
+//! use vq::exceptions::VqError;
+//! use vq::pq::ProductQuantizer;
+//! use vq::progress::{ProgressEvent, TrainingControl};
+//! use vq::vector::Vector;
+//!
+//! let training_data: Vec<Vector<f32>> = (0..100)
+//!     .map(|i| Vector::new(vec![i as f32, (i % 7) as f32, (i % 3) as f32, 1.0]))
+//!     .collect();
+//!
+//! // Stop after the first LBG iteration.
+//! let seen = AtomicUsize::new(0);
+//! let observer = |event: &ProgressEvent| {
+//!     if let ProgressEvent::Iteration { .. } = event {
+//!         seen.fetch_add(1, Ordering::SeqCst);
+//!     }
+//!     TrainingControl::Cancel
+//! };
+//! let result = ProductQuantizer::fit_with_observer(
+//!     &training_data, 2, 4, 10, Distance::Euclidean, 42, &observer,
+//! );
+//! assert!(matches!(result, Err(VqError::Cancelled)));
+//! assert!(seen.load(Ordering::SeqCst) >= 1);
+//! ```
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// A progress notification sent to a `TrainingObserver`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ProgressEvent {
+    /// An LBG iteration finished for one codebook.
+    Iteration {
+        /// The training round (OPQ iteration or RVQ stage; always 0 for PQ).
+        round: usize,
+        /// The codebook within the round (the subspace for PQ and OPQ; always 0 for RVQ).
+        codebook: usize,
+        /// The LBG iteration, starting at 0.
+        iteration: usize,
+        /// The mean assignment cost of the iteration.
+        distortion: f32,
+    },
+    /// A training round finished.
+    Round {
+        /// The training round that finished.
+        round: usize,
+        /// The mean distance between the training vectors and their reconstructions.
+        distortion: f32,
+    },
+}
+
+/// The observer's answer to a progress event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrainingControl {
+    /// Keep training.
+    Continue,
+    /// Stop training as soon as possible and return `VqError::Cancelled`.
+    Cancel,
+}
+
+/// Receives progress events during training and decides whether training continues.
+pub trait TrainingObserver: Sync {
+    /// Called after every LBG iteration and every training round.
+    fn on_progress(&self, event: &ProgressEvent) -> TrainingControl;
+}
+
+impl<F> TrainingObserver for F
+where
+    F: Fn(&ProgressEvent) -> TrainingControl + Sync,
+{
+    fn on_progress(&self, event: &ProgressEvent) -> TrainingControl {
+        self(event)
+    }
+}
+
+/// Forwards progress events to an optional observer and remembers a cancellation request.
+pub(crate) struct Progress<'a> {
+    observer: Option<&'a dyn TrainingObserver>,
+    cancelled: AtomicBool,
+}
+
+impl<'a> Progress<'a> {
+    /// Creates a progress tracker for the given observer, if any.
+    pub(crate) fn new(observer: Option<&'a dyn TrainingObserver>) -> Self {
+        Self {
+            observer,
+            cancelled: AtomicBool::new(false),
+        }
+    }
+
+    /// Returns true once the observer has requested cancellation.
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Reports an event and returns true if training should continue.
+    pub(crate) fn notify(&self, event: ProgressEvent) -> bool {
+        if self.is_cancelled() {
+            return false;
+        }
+        if let Some(observer) = self.observer {
+            if observer.on_progress(&event) == TrainingControl::Cancel {
+                self.cancelled.store(true, Ordering::Relaxed);
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Reports a finished LBG iteration and returns true if training should continue.
+    pub(crate) fn iteration(
+        &self,
+        round: usize,
+        codebook: usize,
+        iteration: usize,
+        distortion: f32,
+    ) -> bool {
+        self.notify(ProgressEvent::Iteration {
+            round,
+            codebook,
+            iteration,
+            distortion,
+        })
+    }
+
+    /// Reports a finished training round and returns true if training should continue.
+    pub(crate) fn round(&self, round: usize, distortion: f32) -> bool {
+        self.notify(ProgressEvent::Round { round, distortion })
+    }
+}
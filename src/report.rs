@@ -0,0 +1,106 @@
+//! # Training Reports
+//!
+//! This module defines the diagnostics that quantizers can return from training. A
+//! `TrainingReport` records how each LBG (k-means) run went: the distortion after every
+//! iteration, whether the run converged, and how often empty clusters had to be reseeded.
+//! Runs are grouped into rounds, where a round is one pass over all subspaces (PQ), one
+//! OPQ iteration, or one RVQ stage. The report also stores the wall time of the whole fit.
+//!
+//! # Example
+//! ```
+//! use vq::vector::Vector;
+//! use vq::distances::Distance;
+//! use vq::pq::ProductQuantizer;
+//!
+//! let training_data = vec![
+//!     Vector::new(vec![0.0, 0.0, 0.0, 0.0]),
+//!     Vector::new(vec![1.0, 1.0, 1.0, 1.0]),
+//!     Vector::new(vec![0.5, 0.5, 0.5, 0.5]),
+//! ];
+//! let (pq, report) =
+//!     ProductQuantizer::fit_with_report(&training_data, 2, 2, 10, Distance::Euclidean, 42);
+//! println!(
+//!     "converged: {}, final distortion: {:?}, took {:?}",
+//!     report.converged(),
+//!     report.final_distortion(),
+//!     report.elapsed
+//! );
+//! ```
+
+use std::time::Duration;
+
+/// Diagnostics of a single LBG (k-means) run that trains one codebook.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClusteringReport {
+    /// The mean assignment cost (distortion) of the training data in each iteration.
+    ///
+    /// The cost is the one minimized by the centroid update: squared Euclidean distance for
+    /// Euclidean metrics, cosine distance for cosine, and Manhattan distance for Manhattan.
+    pub distortions: Vec<f32>,
+    /// Whether the assignments stopped changing before the iteration limit was reached.
+    pub converged: bool,
+    /// The number of times an empty cluster was reseeded with a random data point.
+    pub empty_clusters: usize,
+}
+
+impl ClusteringReport {
+    /// Returns the number of iterations that were run.
+    pub fn iterations(&self) -> usize {
+        self.distortions.len()
+    }
+
+    /// Returns the distortion of the last iteration, if any iteration was run.
+    pub fn final_distortion(&self) -> Option<f32> {
+        self.distortions.last().copied()
+    }
+}
+
+/// Diagnostics of one training round.
+///
+/// A round is one pass over all subspaces for PQ, one alternation for OPQ, and one stage
+/// for RVQ.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RoundReport {
+    /// One report per codebook trained in this round (one per subspace for PQ and OPQ,
+    /// a single entry for an RVQ stage).
+    pub clusterings: Vec<ClusteringReport>,
+    /// The mean distance between the training vectors and their reconstructions after the
+    /// round, measured with the quantizer's distance metric.
+    pub distortion: f32,
+}
+
+/// Diagnostics of a complete training run, returned by the `fit_with_report` methods.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrainingReport {
+    /// The training rounds, in the order they were run.
+    pub rounds: Vec<RoundReport>,
+    /// The wall time of the whole fit.
+    pub elapsed: Duration,
+}
+
+impl TrainingReport {
+    /// Returns true if every LBG run in every round converged.
+    pub fn converged(&self) -> bool {
+        self.clusterings().all(|c| c.converged)
+    }
+
+    /// Returns the total number of empty clusters that were reseeded during training.
+    pub fn empty_clusters(&self) -> usize {
+        self.clusterings().map(|c| c.empty_clusters).sum()
+    }
+
+    /// Returns the total number of LBG iterations run during training.
+    pub fn iterations(&self) -> usize {
+        self.clusterings().map(|c| c.iterations()).sum()
+    }
+
+    /// Returns the distortion after the last round, if any round was run.
+    pub fn final_distortion(&self) -> Option<f32> {
+        self.rounds.last().map(|r| r.distortion)
+    }
+
+    /// Iterates over the reports of all LBG runs, in training order.
+    pub fn clusterings(&self) -> impl Iterator<Item = &ClusteringReport> {
+        self.rounds.iter().flat_map(|r| r.clusterings.iter())
+    }
+}
@@ -6,13 +6,37 @@
 //! stages. The final quantized approximation is the sum of the selected codewords from each stage.
 //!
 //! The quantizer uses a specified distance metric to compare vectors and supports early termination
-//! if the average residual norm falls below a given threshold during training.
+//! if the average distance between the training vectors and their reconstructions falls below a
+//! given threshold during training. Encoding stops in the same way once a vector's reconstruction
+//! is within that distance.
+//!
+//! By default each stage greedily picks the codeword nearest to the current residual, which can be
+//! far from the best combination once there are more than a few stages. `fit_with_beam` instead
+//! keeps the `beam_width` best partial reconstructions after every stage (see
+//! `with_beam_width`). The beam is also used during training: after each stage, the training
+//! vectors are re-encoded with beam search over the stages learned so far, and the next codebook
+//! is learned on the resulting residuals.
+//!
+//! Stage codebooks are trained once, each on the residuals of the earlier stages. `fine_tune`
+//! revisits them all: it alternates between encoding the training vectors and updating every
+//! stage codebook jointly by least squares for the fixed codes, as additive quantization does.
+//! This lowers the reconstruction error of deep stacks without changing the code size.
+//!
+//! `encode_batch` chooses the number of stages of each vector with a `RateControl`: a fixed
+//! number of stages, a target error relative to the vector's distance from the origin, or a bit
+//! budget for the whole batch. The codes of a vector are a prefix of its full codes, so the same
+//! codes can be stored at several quality levels by truncating them.
 //!
 //! # Errors
 //! Methods in this module panic with custom errors from the exceptions module when:
 //! - The training data is empty.
 //! - The training vectors are not all of the same dimension.
-//! - An input vector passed to `quantize` does not have the expected dimension.
+//! - The beam width is zero.
+//! - A target relative error is negative or not finite.
+//! - The distance metric has no suitable centroid update for codebook training
+//!   (Chebyshev, Minkowski, or Hamming).
+//! - An input vector passed to `quantize` or `encode` does not have the expected dimension.
+//! - Codes passed to `decode` do not match the codebooks.
 //!
 //! # Example
 //! ```
@@ -45,15 +69,40 @@
 //! let input = Vector::new(vec![0.2, 0.8, 0.3]);
 //! let quantized = rq.quantize(&input);
 //! println!("Quantized vector: {:?}", quantized);
+//!
+//! // Encode the stage indices with 2 bits each and reconstruct them.
+//! let codes = rq.encode(&input);
+//! let reconstruction = rq.decode(&codes);
 //! ```
 
+use crate::additive::{beam_search, least_squares_codebooks};
 use crate::distances::Distance;
-use crate::exceptions::VqError;
+use crate::exceptions::{VqError, VqResult};
+use crate::packing::{bits_for, PackedCodes};
+use crate::progress::{Progress, TrainingObserver};
+use crate::report::{RoundReport, TrainingReport};
 use crate::utils::lbg_quantize;
 use crate::vector::Vector;
 use half::f16;
 use rayon::prelude::*;
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::time::Instant;
 
+/// How `ResidualQuantizer::encode_batch` chooses the number of stages of each vector.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RateControl {
+    /// Encode every vector with the same number of stages (at most the number of trained stages).
+    Stages(usize),
+    /// Encode each vector with the fewest stages whose error is at most this fraction of the
+    /// distance between the vector and the origin. Vectors that never reach it use all stages.
+    RelativeError(f32),
+    /// Spend at most this many bits on the whole batch. Stages are given one at a time to the
+    /// vector whose error the next stage reduces the most.
+    BitBudget(usize),
+}
+
+#[derive(Clone)]
 pub struct ResidualQuantizer {
     /// Maximum number of quantization stages.
     stages: usize,
@@ -63,8 +112,10 @@ pub struct ResidualQuantizer {
     dim: usize,
     /// Distance metric used to evaluate the quality of a quantization.
     distance: Distance,
-    /// Early termination threshold: if the residual norm falls below this value, training stops.
+    /// Early termination threshold on the distance between vectors and their reconstructions.
     epsilon: f32,
+    /// The number of partial reconstructions kept after each stage when encoding (1 is greedy).
+    beam_width: usize,
 }
 
 impl ResidualQuantizer {
@@ -75,14 +126,23 @@ impl ResidualQuantizer {
     /// - `stages`: The number of quantization stages. Each stage learns a codebook for the residual error.
     /// - `k`: The number of centroids per stage (i.e. the size of each codebook).
     /// - `max_iters`: The maximum number of iterations for the LBG (k-means) algorithm.
-    /// - `epsilon`: The early termination threshold. If the average residual norm falls below this value during training,
-    ///   the training loop terminates early.
+    /// - `epsilon`: The early termination threshold. If the average distance between the training vectors and their
+    ///   reconstructions falls below this value during training, the training loop terminates early. Encoding also
+    ///   stops at the first stage whose reconstruction is closer than `epsilon` to the input vector.
+    ///   Distances are measured with `distance`, not as the Euclidean norm of the residual as in
+    ///   earlier versions. With `Distance::SquaredEuclidean`, `epsilon` is a squared distance, so
+    ///   a residual norm threshold `r` becomes `epsilon = r²`. With `CosineDistance`, it is a
+    ///   threshold on the cosine distance and does not bound the residual norm at all.
     /// - `distance`: The distance metric used to compute distances between vectors.
+    ///   Codebooks are trained for the same metric (see `lbg_quantize`), except that with
+    ///   `CosineDistance` only the first stage uses normalized centroids: later stages quantize
+    ///   residuals, whose norms matter, and use arithmetic means.
     /// - `seed`: The random seed used for initializing the LBG algorithm (each stage uses `seed + stage`).
     ///
     /// # Panics
     /// Panics with a custom error if:
     /// - `training_data` is empty.
+    /// - The distance metric is not supported for codebook training.
     /// - The training data vectors are not all of the same dimension.
     pub fn fit(
         training_data: &[Vector<f32>],
@@ -93,50 +153,363 @@ impl ResidualQuantizer {
         distance: Distance,
         seed: u64,
     ) -> Self {
+        Self::fit_with_report(training_data, stages, k, max_iters, epsilon, distance, seed).0
+    }
+
+    /// Constructs a new `ResidualQuantizer` like `fit` and also returns a `TrainingReport`.
+    ///
+    /// The report holds one round per trained stage, each with a single `ClusteringReport`.
+    /// The round distortion is the mean distance between the training vectors and their
+    /// reconstructions from the stages trained so far.
+    ///
+    /// # Parameters
+    /// Same as `fit`.
+    ///
+    /// # Panics
+    /// Same conditions as `fit`.
+    pub fn fit_with_report(
+        training_data: &[Vector<f32>],
+        stages: usize,
+        k: usize,
+        max_iters: usize,
+        epsilon: f32,
+        distance: Distance,
+        seed: u64,
+    ) -> (Self, TrainingReport) {
+        let progress = Progress::new(None);
+        Self::train(
+            training_data,
+            stages,
+            k,
+            max_iters,
+            epsilon,
+            1,
+            distance,
+            seed,
+            None,
+            &progress,
+        )
+        .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Constructs a new `ResidualQuantizer` that encodes with beam search.
+    ///
+    /// After every stage, the training vectors are encoded with beam search over the stages
+    /// learned so far, and the next stage's codebook is learned on the residuals of those codes.
+    /// `quantize` and `encode` use the same beam width.
+    ///
+    /// # Parameters
+    /// Same as `fit`, plus:
+    /// - `beam_width`: The number of partial reconstructions kept after each stage. A width of 1
+    ///   gives the greedy encoder of `fit`.
+    ///
+    /// # Panics
+    /// Panics with a custom error if `beam_width` is zero or under the same conditions as `fit`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn fit_with_beam(
+        training_data: &[Vector<f32>],
+        stages: usize,
+        k: usize,
+        max_iters: usize,
+        epsilon: f32,
+        beam_width: usize,
+        distance: Distance,
+        seed: u64,
+    ) -> Self {
+        let progress = Progress::new(None);
+        Self::train(
+            training_data,
+            stages,
+            k,
+            max_iters,
+            epsilon,
+            beam_width,
+            distance,
+            seed,
+            None,
+            &progress,
+        )
+        .unwrap_or_else(|e| panic!("{}", e))
+        .0
+    }
+
+    /// Constructs a new `ResidualQuantizer` like `fit_with_report` while reporting progress.
+    ///
+    /// The observer receives an event after every LBG iteration and after every stage.
+    ///
+    /// # Parameters
+    /// Same as `fit`, plus:
+    /// - `observer`: Receives progress events and may request cancellation.
+    ///
+    /// # Errors
+    /// Returns `VqError::Cancelled` if the observer requested cancellation.
+    ///
+    /// # Panics
+    /// Same conditions as `fit`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn fit_with_observer(
+        training_data: &[Vector<f32>],
+        stages: usize,
+        k: usize,
+        max_iters: usize,
+        epsilon: f32,
+        distance: Distance,
+        seed: u64,
+        observer: &dyn TrainingObserver,
+    ) -> VqResult<(Self, TrainingReport)> {
+        let progress = Progress::new(Some(observer));
+        Self::train(
+            training_data,
+            stages,
+            k,
+            max_iters,
+            epsilon,
+            1,
+            distance,
+            seed,
+            None,
+            &progress,
+        )
+    }
+
+    /// Constructs a new `ResidualQuantizer` by refining existing stage codebooks on new training data.
+    ///
+    /// Each stage's LBG algorithm starts from the given codebook of that stage instead of random
+    /// centroids, and is trained on the residuals left by the refined earlier stages. This is
+    /// useful for adapting a trained quantizer to drifted data, where a few iterations on a new
+    /// sample are usually enough.
+    ///
+    /// # Parameters
+    /// - `training_data`: A slice of training vectors (`Vector<f32>`) used to refine the quantizer.
+    /// - `codebooks`: The initial codebooks, one per stage (for example from `codebooks()`).
+    ///   Their number sets the maximum number of stages.
+    /// - `max_iters`: The maximum number of LBG iterations per stage.
+    /// - `epsilon`: The early termination threshold (see `fit`).
+    /// - `distance`: The distance metric used for training and for comparing vectors.
+    /// - `seed`: A random seed used to reseed empty clusters. Each stage uses `seed + stage`.
+    ///
+    /// # Panics
+    /// Panics with a custom error if:
+    /// - The training data or any codebook is empty.
+    /// - The codebook dimension does not match the training data dimension.
+    /// - There are fewer training vectors than codewords in a stage.
+    /// - The distance metric is not supported for codebook training.
+    pub fn warm_start(
+        training_data: &[Vector<f32>],
+        codebooks: &[Vec<Vector<f32>>],
+        max_iters: usize,
+        epsilon: f32,
+        distance: Distance,
+        seed: u64,
+    ) -> Self {
+        if codebooks.is_empty() || codebooks.iter().any(|c| c.is_empty()) {
+            panic!("{}", VqError::EmptyInput);
+        }
+        let progress = Progress::new(None);
+        Self::train(
+            training_data,
+            codebooks.len(),
+            codebooks[0].len(),
+            max_iters,
+            epsilon,
+            1,
+            distance,
+            seed,
+            Some(codebooks),
+            &progress,
+        )
+        .unwrap_or_else(|e| panic!("{}", e))
+        .0
+    }
+
+    /// Refines this quantizer's stage codebooks on new training data (see `warm_start`).
+    ///
+    /// The refined quantizer keeps this quantizer's beam width, and the training residuals are
+    /// computed with it.
+    ///
+    /// # Panics
+    /// Same conditions as `warm_start`.
+    pub fn refine(&self, training_data: &[Vector<f32>], max_iters: usize, seed: u64) -> Self {
+        let progress = Progress::new(None);
+        Self::train(
+            training_data,
+            self.codebooks.len(),
+            self.codebooks[0].len(),
+            max_iters,
+            self.epsilon,
+            self.beam_width,
+            self.distance,
+            seed,
+            Some(&self.codebooks),
+            &progress,
+        )
+        .unwrap_or_else(|e| panic!("{}", e))
+        .0
+    }
+
+    /// Returns a copy of this quantizer whose stage codebooks are fine-tuned jointly.
+    ///
+    /// Each of the `iters` iterations updates all codebooks by least squares for the current
+    /// codes of the training vectors, then re-encodes the training vectors with this quantizer's
+    /// encoder, keeping a vector's previous codes if they reconstruct it better. The update
+    /// minimizes the squared Euclidean reconstruction error whatever the configured distance.
+    ///
+    /// After fine-tuning, a stage codebook no longer fits the residuals of the greedy encoder
+    /// alone, so a beam width above 1 (see `with_beam_width`) usually works best.
+    ///
+    /// # Parameters
+    /// - `training_data`: A slice of training vectors (`Vector<f32>`).
+    /// - `iters`: The number of alternations between the codebook update and re-encoding.
+    ///
+    /// # Panics
+    /// Panics with a custom error if the training data is empty or a training vector's
+    /// dimension does not match the quantizer.
+    pub fn fine_tune(&self, training_data: &[Vector<f32>], iters: usize) -> Self {
         if training_data.is_empty() {
             panic!("{}", VqError::EmptyInput);
         }
+        let mut rq = self.clone();
+        let mut codes: Vec<Vec<usize>> = training_data
+            .par_iter()
+            .map(|v| rq.encode_indices(v))
+            .collect();
+        for _ in 0..iters {
+            rq.codebooks = least_squares_codebooks(training_data, &codes, &rq.codebooks);
+            codes = training_data
+                .par_iter()
+                .zip(codes.par_iter())
+                .map(|(v, previous)| {
+                    let candidate = rq.encode_indices(v);
+                    let error = |codes: &[usize]| v.distance2(&rq.reconstruct(codes));
+                    if error(&candidate) <= error(previous) {
+                        candidate
+                    } else {
+                        previous.clone()
+                    }
+                })
+                .collect();
+        }
+        rq
+    }
+
+    /// Returns a copy of this quantizer that encodes with the given beam width.
+    ///
+    /// The codebooks are unchanged; only `quantize` and `encode` are affected.
+    ///
+    /// # Panics
+    /// Panics with a custom error if `beam_width` is zero.
+    pub fn with_beam_width(&self, beam_width: usize) -> Self {
+        check_beam_width(beam_width);
+        Self {
+            beam_width,
+            ..self.clone()
+        }
+    }
+
+    /// Returns the learned codebooks, one per stage.
+    pub fn codebooks(&self) -> &[Vec<Vector<f32>>] {
+        &self.codebooks
+    }
+
+    /// Returns the number of partial reconstructions kept after each stage when encoding.
+    pub fn beam_width(&self) -> usize {
+        self.beam_width
+    }
+
+    /// Trains the stage codebooks, starting from `initial` codebooks if given and forwarding
+    /// progress to `progress`.
+    #[allow(clippy::too_many_arguments)]
+    fn train(
+        training_data: &[Vector<f32>],
+        stages: usize,
+        k: usize,
+        max_iters: usize,
+        epsilon: f32,
+        beam_width: usize,
+        distance: Distance,
+        seed: u64,
+        initial: Option<&[Vec<Vector<f32>>]>,
+        progress: &Progress,
+    ) -> VqResult<(Self, TrainingReport)> {
+        let start = Instant::now();
+        if training_data.is_empty() {
+            panic!("{}", VqError::EmptyInput);
+        }
+        check_beam_width(beam_width);
         let dim = training_data[0].len();
         // (Optionally, you could check that all training vectors have the same dimension here)
         let mut codebooks = Vec::with_capacity(stages);
         // Clone training data into residuals. Initially, each residual equals the original vector.
         let mut residuals = training_data.to_vec();
+        let mut rounds = Vec::with_capacity(stages);
 
         for stage in 0..stages {
             // Learn a codebook on the current residuals.
-            let codebook = lbg_quantize(&residuals, k, max_iters, seed + stage as u64);
-            codebooks.push(codebook.clone());
-
-            // Update residuals in parallel by subtracting the best matching centroid from each residual.
-            residuals.par_iter_mut().for_each(|res| {
-                let codebook = &codebooks[stage];
-                let best_index = if codebook.len() < 2 {
-                    0
-                } else {
-                    let mut best_index = 0;
-                    let mut best_dist = distance.compute(&res.data, &codebook[0].data);
-                    for (j, centroid) in codebook.iter().enumerate().skip(1) {
-                        let dist = distance.compute(&res.data, &centroid.data);
-                        if dist < best_dist {
-                            best_dist = dist;
-                            best_index = j;
+            let (codebook, clustering) = lbg_quantize(
+                &residuals,
+                k,
+                max_iters,
+                &training_distance(&distance, stage),
+                seed + stage as u64,
+                initial.map(|c| c[stage].as_slice()),
+                &|iteration, distortion| progress.iteration(stage, 0, iteration, distortion),
+            );
+            if progress.is_cancelled() {
+                return Err(VqError::Cancelled);
+            }
+            codebooks.push(codebook);
+
+            if beam_width > 1 {
+                // Re-encode every training vector with beam search over the stages so far.
+                residuals = training_data
+                    .par_iter()
+                    .map(|v| {
+                        let (codes, _) = beam_search(&codebooks, &v.data, beam_width, &distance);
+                        let mut residual = v.clone();
+                        for (codebook, &code) in codebooks.iter().zip(codes.iter()) {
+                            residual = &residual - &codebook[code];
                         }
-                    }
-                    best_index
-                };
-                *res = &*res - &codebooks[stage][best_index];
-            });
+                        residual
+                    })
+                    .collect();
+            } else {
+                // Update residuals in parallel by subtracting the best matching centroid from each residual.
+                residuals.par_iter_mut().for_each(|res| {
+                    let codebook = &codebooks[stage];
+                    let best_index = if codebook.len() < 2 {
+                        0
+                    } else {
+                        let mut best_index = 0;
+                        let mut best_dist = distance.compute(&res.data, &codebook[0].data);
+                        for (j, centroid) in codebook.iter().enumerate().skip(1) {
+                            let dist = distance.compute(&res.data, &centroid.data);
+                            if dist < best_dist {
+                                best_dist = dist;
+                                best_index = j;
+                            }
+                        }
+                        best_index
+                    };
+                    *res = &*res - &codebooks[stage][best_index];
+                });
+            }
 
-            // Compute the average residual norm (in parallel) to check for early termination.
-            let avg_norm: f32 = residuals
+            // The reconstruction of each training vector is the vector minus its residual.
+            let distortion = training_data
                 .par_iter()
-                .map(|r| {
-                    let norm_sq: f32 = r.data.iter().map(|&x| x * x).sum();
-                    norm_sq.sqrt()
-                })
+                .zip(residuals.par_iter())
+                .map(|(v, r)| distance.compute(&v.data, &(v - r).data))
                 .sum::<f32>()
                 / residuals.len() as f32;
-            if avg_norm < epsilon {
+            rounds.push(RoundReport {
+                clusterings: vec![clustering],
+                distortion,
+            });
+            if !progress.round(stage, distortion) {
+                return Err(VqError::Cancelled);
+            }
+
+            if distortion < epsilon {
                 break;
             }
         }
@@ -144,21 +517,29 @@ impl ResidualQuantizer {
         // Use the actual number of stages performed (codebooks generated)
         let actual_stages = codebooks.len();
 
-        Self {
+        let rq = Self {
             stages: actual_stages,
             codebooks,
             dim,
             distance,
             epsilon,
-        }
+            beam_width,
+        };
+        let report = TrainingReport {
+            rounds,
+            elapsed: start.elapsed(),
+        };
+        Ok((rq, report))
     }
 
     /// Quantizes an input vector using the residual quantizer.
     ///
     /// The input vector is approximated as the sum of codewords selected from each stage.
     /// At each stage, the codeword that minimizes the distance (as defined by the stored `distance`)
-    /// is chosen, and its contribution is subtracted from the residual.
-    /// Early termination occurs if the residual norm falls below the stored `epsilon`.
+    /// is chosen, and its contribution is subtracted from the residual. With a beam width above 1,
+    /// the codewords of all stages are instead chosen together by beam search.
+    /// Early termination occurs once the distance between the input vector and its reconstruction
+    /// falls below the stored `epsilon`.
     ///
     /// # Parameters
     /// - `vector`: The input vector (`Vector<f32>`) to quantize. Its dimension must equal the training data.
@@ -169,6 +550,153 @@ impl ResidualQuantizer {
     /// # Panics
     /// Panics with a custom error if the input vector's dimension does not equal the expected dimension.
     pub fn quantize(&self, vector: &Vector<f32>) -> Vector<f16> {
+        let quantized_sum = self.reconstruct(&self.encode_indices(vector));
+        let quantized_f16: Vec<f16> = quantized_sum
+            .data
+            .iter()
+            .map(|&x| f16::from_f32(x))
+            .collect();
+        Vector::new(quantized_f16)
+    }
+
+    /// Encodes an input vector as the packed codeword indices of its stages.
+    ///
+    /// Stages are selected like in `quantize`, and each index uses `ceil(log2(k))` bits. If the
+    /// reconstruction comes within `epsilon` of the vector, encoding stops early and fewer codes than stages
+    /// are returned.
+    ///
+    /// # Panics
+    /// Panics with a custom error if the input vector's dimension does not equal the expected dimension.
+    pub fn encode(&self, vector: &Vector<f32>) -> PackedCodes {
+        PackedCodes::from_codes(
+            self.code_bits(),
+            self.encode_indices(vector).into_iter().map(|i| i as u16),
+        )
+    }
+
+    /// Reconstructs a vector by summing the codewords of the encoded stages.
+    ///
+    /// # Panics
+    /// Panics with a custom error if there are more codes than stages, the bit width does not
+    /// match the codebooks, or a code is out of range.
+    pub fn decode(&self, codes: &PackedCodes) -> Vector<f32> {
+        if codes.len() > self.stages {
+            panic!(
+                "{}",
+                VqError::DimensionMismatch {
+                    expected: self.stages,
+                    found: codes.len()
+                }
+            );
+        }
+        if codes.bits() != self.code_bits() {
+            panic!(
+                "{}",
+                VqError::InvalidParameter(format!(
+                    "Expected {}-bit codes, got {}-bit codes",
+                    self.code_bits(),
+                    codes.bits()
+                ))
+            );
+        }
+        let indices: Vec<usize> = codes
+            .iter()
+            .zip(self.codebooks.iter())
+            .map(|(c, codebook)| {
+                if c as usize >= codebook.len() {
+                    panic!(
+                        "{}",
+                        VqError::InvalidParameter(format!(
+                            "Code {} is out of range for a codebook of {} codewords",
+                            c,
+                            codebook.len()
+                        ))
+                    );
+                }
+                c as usize
+            })
+            .collect();
+        self.reconstruct(&indices)
+    }
+
+    /// Encodes a batch of vectors with a number of stages chosen by `rate`.
+    ///
+    /// Every vector is first encoded with all stages (with the greedy or beam encoder, ignoring
+    /// `epsilon`). Its codes are then truncated to the number of stages chosen by `rate`, where
+    /// errors are distances under the configured metric between the vector and the
+    /// reconstructions of its code prefixes.
+    ///
+    /// # Parameters
+    /// - `vectors`: The input vectors (`Vector<f32>`) to encode.
+    /// - `rate`: How the number of stages of each vector is chosen.
+    ///
+    /// # Returns
+    /// The packed codes of each vector. The number of stages chosen for vector `i` is
+    /// `codes[i].len()`, and `decode` accepts codes of any length.
+    ///
+    /// # Panics
+    /// Panics with a custom error if a vector's dimension does not equal the expected dimension
+    /// or a target relative error is negative or not finite.
+    pub fn encode_batch(&self, vectors: &[Vector<f32>], rate: RateControl) -> Vec<PackedCodes> {
+        let encoded: Vec<(Vec<usize>, Vec<f32>)> = vectors
+            .par_iter()
+            .map(|v| {
+                let codes = self.full_indices(v);
+                let errors = self.prefix_errors(v, &codes);
+                (codes, errors)
+            })
+            .collect();
+        let stages: Vec<usize> = match rate {
+            RateControl::Stages(stages) => vec![stages.min(self.stages); vectors.len()],
+            RateControl::RelativeError(target) => {
+                if !(target.is_finite() && target >= 0.0) {
+                    panic!(
+                        "{}",
+                        VqError::InvalidParameter(format!(
+                            "Target relative error must be finite and non-negative, got {}",
+                            target
+                        ))
+                    );
+                }
+                encoded
+                    .iter()
+                    .map(|(codes, errors)| {
+                        errors
+                            .iter()
+                            .position(|&e| e <= target * errors[0])
+                            .unwrap_or(codes.len())
+                    })
+                    .collect()
+            }
+            RateControl::BitBudget(bits) => {
+                let errors: Vec<&[f32]> = encoded.iter().map(|(_, e)| e.as_slice()).collect();
+                allocate_stages(&errors, bits / self.code_bits() as usize)
+            }
+        };
+        encoded
+            .into_iter()
+            .zip(stages)
+            .map(|((codes, _), stages)| {
+                PackedCodes::from_codes(self.code_bits(), codes[..stages].iter().map(|&i| i as u16))
+            })
+            .collect()
+    }
+
+    /// Selects the codeword index of each stage, stopping early once the reconstruction is
+    /// within `epsilon` of the vector.
+    fn encode_indices(&self, vector: &Vector<f32>) -> Vec<usize> {
+        self.select_indices(vector, Some(self.epsilon))
+    }
+
+    /// Selects the codeword index of every stage.
+    fn full_indices(&self, vector: &Vector<f32>) -> Vec<usize> {
+        self.select_indices(vector, None)
+    }
+
+    /// Selects the codeword index of each stage. With an `epsilon`, selection stops at the first
+    /// stage whose reconstruction is closer than `epsilon` to the vector; the greedy encoder
+    /// then skips the remaining stages.
+    fn select_indices(&self, vector: &Vector<f32>, epsilon: Option<f32>) -> Vec<usize> {
         if vector.len() != self.dim {
             panic!(
                 "{}",
@@ -178,8 +706,25 @@ impl ResidualQuantizer {
                 }
             );
         }
+        if self.beam_width > 1 {
+            let (codes, _) = beam_search(
+                &self.codebooks,
+                &vector.data,
+                self.beam_width,
+                &self.distance,
+            );
+            let Some(epsilon) = epsilon else {
+                return codes;
+            };
+            let errors = self.prefix_errors(vector, &codes);
+            return match errors[1..].iter().position(|&e| e < epsilon) {
+                Some(stage) => codes[..=stage].to_vec(),
+                None => codes,
+            };
+        }
         let mut residual = vector.clone();
-        let mut quantized_sum = Vector::new(vec![0.0; self.dim]);
+        let mut reconstruction = Vector::new(vec![0.0; self.dim]);
+        let mut indices = Vec::with_capacity(self.stages);
 
         for stage in 0..self.stages {
             let codebook = &self.codebooks[stage];
@@ -197,20 +742,122 @@ impl ResidualQuantizer {
                 }
                 best_index
             };
-            let chosen = &codebook[best_index];
-            quantized_sum = &quantized_sum + chosen;
-            residual = &residual - chosen;
-            // Early termination if the residual norm is small.
-            let norm: f32 = residual.data.iter().map(|&x| x * x).sum::<f32>().sqrt();
-            if norm < self.epsilon {
+            indices.push(best_index);
+            residual = &residual - &codebook[best_index];
+            reconstruction = &reconstruction + &codebook[best_index];
+
+            // Early termination once the reconstruction is close enough.
+            if epsilon
+                .is_some_and(|e| self.distance.compute(&vector.data, &reconstruction.data) < e)
+            {
                 break;
             }
         }
-        let quantized_f16: Vec<f16> = quantized_sum
-            .data
-            .iter()
-            .map(|&x| f16::from_f32(x))
-            .collect();
-        Vector::new(quantized_f16)
+        indices
+    }
+
+    /// Returns the distance between `vector` and the reconstruction from each prefix of
+    /// `codes`, starting with the empty prefix (the origin).
+    fn prefix_errors(&self, vector: &Vector<f32>, codes: &[usize]) -> Vec<f32> {
+        let mut reconstruction = Vector::new(vec![0.0; self.dim]);
+        let mut errors = Vec::with_capacity(codes.len() + 1);
+        errors.push(self.distance.compute(&vector.data, &reconstruction.data));
+        for (codebook, &code) in self.codebooks.iter().zip(codes.iter()) {
+            reconstruction = &reconstruction + &codebook[code];
+            errors.push(self.distance.compute(&vector.data, &reconstruction.data));
+        }
+        errors
+    }
+
+    /// Sums the selected codewords of the first `indices.len()` stages.
+    fn reconstruct(&self, indices: &[usize]) -> Vector<f32> {
+        let mut sum = Vector::new(vec![0.0; self.dim]);
+        for (codebook, &index) in self.codebooks.iter().zip(indices.iter()) {
+            sum = &sum + &codebook[index];
+        }
+        sum
+    }
+
+    /// The number of bits per stage index.
+    fn code_bits(&self) -> u8 {
+        bits_for(self.codebooks.iter().map(|c| c.len()).max().unwrap_or(1))
+    }
+}
+
+/// Returns the metric whose centroid update trains the codebook of `stage`.
+///
+/// Residuals keep their magnitude, so normalized centroids are only used for the first stage.
+fn training_distance(distance: &Distance, stage: usize) -> Distance {
+    match distance {
+        Distance::CosineDistance if stage > 0 => Distance::SquaredEuclidean,
+        other => *other,
+    }
+}
+
+fn check_beam_width(beam_width: usize) {
+    if beam_width == 0 {
+        panic!(
+            "{}",
+            VqError::InvalidParameter("beam_width must be greater than 0".to_string())
+        );
+    }
+}
+
+/// The error reduction of a vector's next stage, ordered for the bit budget heap.
+struct StageGain {
+    gain: f32,
+    vector: usize,
+}
+
+impl PartialEq for StageGain {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for StageGain {}
+
+impl PartialOrd for StageGain {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for StageGain {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Larger gains first; ties go to the earlier vector.
+        self.gain
+            .total_cmp(&other.gain)
+            .then_with(|| other.vector.cmp(&self.vector))
+    }
+}
+
+/// Splits `total_stages` stages between vectors, given each vector's error after every prefix
+/// of its codes. Each stage goes to the vector whose error the next stage reduces the most.
+fn allocate_stages(errors: &[&[f32]], total_stages: usize) -> Vec<usize> {
+    let mut stages = vec![0; errors.len()];
+    let gain = |e: &[f32], s: usize| e[s] - e[s + 1];
+    let mut heap: BinaryHeap<StageGain> = errors
+        .iter()
+        .enumerate()
+        .filter(|(_, e)| e.len() > 1)
+        .map(|(vector, e)| StageGain {
+            gain: gain(e, 0),
+            vector,
+        })
+        .collect();
+    for _ in 0..total_stages {
+        let Some(StageGain { vector, .. }) = heap.pop() else {
+            break;
+        };
+        stages[vector] += 1;
+        let e = errors[vector];
+        if stages[vector] + 1 < e.len() {
+            heap.push(StageGain {
+                gain: gain(e, stages[vector]),
+                vector,
+            });
+        }
     }
+    stages
 }
@@ -3,8 +3,10 @@
 //! This module provides a scalar quantizer that maps floating-point values to a set of discrete values (or levels).
 //! The quantizer is configured with a minimum and maximum value and a specified number of levels.
 //! Each input value is first clamped to the `[min, max]` range and then uniformly quantized into one of the levels.
-//! The quantized result is represented as a `u8`. For large input vectors, parallel processing is used
-//! to improve performance.
+//! `quantize` represents each level as a `u8`, while `encode` packs the levels with `ceil(log2(levels))`
+//! bits each. Quantizers with more than 256 levels (up to 65536) are built with `fit_wide` and produce
+//! `u16` codes through `quantize_wide` or `encode`.
+//! For large input vectors, parallel processing is used to improve performance.
 //!
 //! Custom error handling is integrated to validate parameters. For example, the `fit` method will panic
 //! with a custom error if the parameters are invalid (e.g. `max` is not greater than `min`, or if the number of levels
@@ -19,9 +21,17 @@
 //! let input = Vector::new(vec![0.0, 0.5, 1.0]);
 //! let output = quantizer.quantize(&input);
 //! // output is a Vector<u8> with quantized values.
+//!
+//! // Finer quantization with 12-bit codes.
+//! let fine = ScalarQuantizer::fit_wide(0.0, 1.0, 4096);
+//! let codes = fine.encode(&input);
+//! assert_eq!(fine.quantize_wide(&input).len(), 3);
+//! assert_eq!(codes.bits(), 12);
+//! let reconstruction = fine.decode(&codes);
 //! ```
 
 use crate::exceptions::VqError;
+use crate::packing::{bits_for, PackedCodes};
 use crate::vector::{Vector, PARALLEL_THRESHOLD};
 use rayon::prelude::*;
 
@@ -31,7 +41,7 @@ pub struct ScalarQuantizer {
     pub min: f32,
     /// The maximum value in the quantizer range.
     pub max: f32,
-    /// The number of quantization levels (must be at least 2 and no more than 256).
+    /// The number of quantization levels (at least 2; at most 256 for `fit` and 65536 for `fit_wide`).
     pub levels: usize,
     /// The step size computed as `(max - min) / (levels - 1)`.
     pub step: f32,
@@ -48,6 +58,28 @@ impl ScalarQuantizer {
     /// # Panics
     /// Panics with a custom error if `max` is not greater than `min`, or if `levels` is not within the valid range.
     pub fn fit(min: f32, max: f32, levels: usize) -> Self {
+        if levels > 256 {
+            panic!(
+                "{}",
+                VqError::InvalidParameter("levels must be no more than 256".to_string())
+            );
+        }
+        Self::fit_wide(min, max, levels)
+    }
+
+    /// Creates a new `ScalarQuantizer` with up to 65536 levels.
+    ///
+    /// Levels that do not fit in `u8` are produced by `quantize_wide` and `encode`; `quantize`
+    /// accepts only quantizers with at most 256 levels.
+    ///
+    /// # Parameters
+    /// - `min`: The minimum value in the quantizer's range.
+    /// - `max`: The maximum value in the quantizer's range. Must be greater than `min`.
+    /// - `levels`: The number of quantization levels. Must be between 2 and 65536.
+    ///
+    /// # Panics
+    /// Panics with a custom error if `max` is not greater than `min`, or if `levels` is not within the valid range.
+    pub fn fit_wide(min: f32, max: f32, levels: usize) -> Self {
         if max <= min {
             panic!(
                 "{}",
@@ -60,10 +92,10 @@ impl ScalarQuantizer {
                 VqError::InvalidParameter("levels must be at least 2".to_string())
             );
         }
-        if levels > 256 {
+        if levels > 1 << 16 {
             panic!(
                 "{}",
-                VqError::InvalidParameter("levels must be no more than 256".to_string())
+                VqError::InvalidParameter("levels must be no more than 65536".to_string())
             );
         }
         let step = (max - min) / (levels - 1) as f32;
@@ -86,7 +118,20 @@ impl ScalarQuantizer {
     ///
     /// # Returns
     /// A new vector (`Vector<u8>`) containing the quantized values.
+    ///
+    /// # Panics
+    /// Panics with a custom error if the quantizer was built by `fit_wide` with more than 256
+    /// levels, which do not fit in `u8`. Use `quantize_wide` or `encode` instead.
     pub fn quantize(&self, vector: &Vector<f32>) -> Vector<u8> {
+        if self.levels > 256 {
+            panic!(
+                "{}",
+                VqError::InvalidParameter(
+                    "quantize supports at most 256 levels; use quantize_wide or encode for more"
+                        .to_string()
+                )
+            );
+        }
         let quantized_vector: Vec<u8> = if vector.data.len() > PARALLEL_THRESHOLD {
             // Use parallel iteration for large vectors.
             vector
@@ -105,6 +150,69 @@ impl ScalarQuantizer {
         Vector::new(quantized_vector)
     }
 
+    /// Quantizes an input vector like `quantize`, but returns the level indices as `u16`.
+    ///
+    /// Unlike `quantize`, this works for every number of levels a quantizer can have.
+    ///
+    /// # Parameters
+    /// - `vector`: A reference to the input vector (`Vector<f32>`) to quantize.
+    ///
+    /// # Returns
+    /// The quantized values (`Vec<u16>`).
+    pub fn quantize_wide(&self, vector: &Vector<f32>) -> Vec<u16> {
+        if vector.data.len() > PARALLEL_THRESHOLD {
+            vector
+                .data
+                .par_iter()
+                .map(|&x| self.quantize_scalar(x) as u16)
+                .collect()
+        } else {
+            vector
+                .data
+                .iter()
+                .map(|&x| self.quantize_scalar(x) as u16)
+                .collect()
+        }
+    }
+
+    /// Encodes an input vector as packed level indices.
+    ///
+    /// Each element is quantized like in `quantize_wide`, and the indices are packed with
+    /// `ceil(log2(levels))` bits each.
+    ///
+    /// # Parameters
+    /// - `vector`: A reference to the input vector (`Vector<f32>`) to encode.
+    ///
+    /// # Returns
+    /// The packed level indices (`PackedCodes`).
+    pub fn encode(&self, vector: &Vector<f32>) -> PackedCodes {
+        let indices = self.quantize_wide(vector);
+        PackedCodes::from_codes(bits_for(self.levels), indices)
+    }
+
+    /// Maps packed level indices back to their values `min + index * step`.
+    ///
+    /// # Panics
+    /// Panics with a custom error if a code is not a valid level index.
+    pub fn decode(&self, codes: &PackedCodes) -> Vector<f32> {
+        let data = codes
+            .iter()
+            .map(|c| {
+                if c as usize >= self.levels {
+                    panic!(
+                        "{}",
+                        VqError::InvalidParameter(format!(
+                            "Code {} is out of range for {} levels",
+                            c, self.levels
+                        ))
+                    );
+                }
+                self.min + c as f32 * self.step
+            })
+            .collect();
+        Vector::new(data)
+    }
+
     /// Quantizes a single scalar value.
     ///
     /// The value is clamped to the `[min, max]` range and then uniformly quantized using the step size.
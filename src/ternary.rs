@@ -0,0 +1,377 @@
+//! # Ternary Quantizer Implementation
+//!
+//! This module provides a ternary quantizer that maps each value to `-1`, `0`, or `+1`. Values
+//! whose magnitude does not exceed a dead-zone threshold become `0`, and the others keep their
+//! sign. Each ternary value `t` represents `α · t`, where `α` is a learned scale.
+//!
+//! The threshold and scale are learned from training data, either once for all dimensions or
+//! separately for each dimension. For a set of values, the quantizer keeps the `k` values of
+//! largest magnitude nonzero and chooses `k` to minimize the squared reconstruction error. With
+//! `S` the sum of the `k` largest magnitudes, the best scale is `α = S / k` and the error is
+//! smallest when `S² / k` is largest. The threshold is placed halfway between the smallest kept
+//! magnitude and the largest dropped one.
+//!
+//! `encode` returns 2-bit `PackedCodes` (`0` for `0`, `1` for `+1` and `2` for `-1`). For storage,
+//! `pack` turns them into bytes either at 2 bits per dimension (four values per byte) or in base 3
+//! (five values per byte, 1.6 bits per dimension), and `unpack` restores them. Dot products
+//! between packed bytes and float queries are computed directly from the bytes using a per-byte
+//! lookup table.
+//!
+//! # Errors
+//! The methods panic with custom errors from the exceptions module when:
+//! - The training data is empty.
+//! - An input vector's dimension or a packed code does not match the quantizer.
+//!
+//! # Example
+//! ```
+//! use vq::vector::Vector;
+//! use vq::ternary::{TernaryPacking, TernaryQuantizer, ThresholdMode};
+//!
+//! let training_data: Vec<Vector<f32>> = (0..50)
+//!     .map(|i| Vector::new(vec![(i as f32 * 0.3).sin(), (i as f32 * 0.7).cos(), 0.1]))
+//!     .collect();
+//!
+//! let quantizer =
+//!     TernaryQuantizer::fit(&training_data, ThresholdMode::PerDimension, TernaryPacking::Base3);
+//!
+//! let input = Vector::new(vec![0.9, -0.05, -0.6]);
+//! let ternary = quantizer.quantize(&input);
+//! let codes = quantizer.encode(&input);
+//! let bytes = quantizer.pack(&codes);
+//! assert_eq!(quantizer.unpack(&bytes), codes);
+//! let query = Vector::new(vec![1.0, 2.0, 3.0]);
+//! println!("Ternary: {}, dot product: {}", ternary, quantizer.dot(&bytes, &query));
+//! ```
+
+use crate::exceptions::VqError;
+use crate::packing::PackedCodes;
+use crate::vector::Vector;
+
+/// How dead-zone thresholds and scales are learned.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ThresholdMode {
+    /// One threshold and scale shared by all dimensions.
+    Global,
+    /// A separate threshold and scale for each dimension.
+    PerDimension,
+}
+
+/// How ternary codes are packed into bytes by `pack`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TernaryPacking {
+    /// Four values per byte, 2 bits each.
+    TwoBit,
+    /// Five values per byte as base-3 digits, 1.6 bits per value.
+    Base3,
+}
+
+impl TernaryPacking {
+    /// Returns the number of ternary values stored in one byte.
+    pub fn values_per_byte(&self) -> usize {
+        match self {
+            TernaryPacking::TwoBit => 4,
+            TernaryPacking::Base3 => 5,
+        }
+    }
+
+    /// Returns the number of bits used per dimension.
+    pub fn bits_per_dimension(&self) -> f32 {
+        8.0 / self.values_per_byte() as f32
+    }
+}
+
+/// A ternary quantizer with learned dead-zone thresholds and scales.
+pub struct TernaryQuantizer {
+    /// The dead-zone threshold of each dimension (all equal in global mode).
+    thresholds: Vec<f32>,
+    /// The reconstruction scale of each dimension (all equal in global mode).
+    scales: Vec<f32>,
+    /// How codes are packed into bytes.
+    packing: TernaryPacking,
+    /// The ternary values encoded by each possible byte.
+    table: Vec<[i8; 5]>,
+}
+
+impl TernaryQuantizer {
+    /// Creates a new `TernaryQuantizer` by learning thresholds and scales from training data.
+    ///
+    /// # Parameters
+    /// - `training_data`: A slice of training vectors (`Vector<f32>`).
+    /// - `mode`: Whether to learn one threshold for all dimensions or one per dimension.
+    /// - `packing`: How codes are packed into bytes by `pack`.
+    ///
+    /// # Panics
+    /// Panics with a custom error if `training_data` is empty or the vectors have different dimensions.
+    pub fn fit(
+        training_data: &[Vector<f32>],
+        mode: ThresholdMode,
+        packing: TernaryPacking,
+    ) -> Self {
+        if training_data.is_empty() {
+            panic!("{}", VqError::EmptyInput);
+        }
+        let dim = training_data[0].len();
+        if let Some(v) = training_data.iter().find(|v| v.len() != dim) {
+            panic!(
+                "{}",
+                VqError::DimensionMismatch {
+                    expected: dim,
+                    found: v.len()
+                }
+            );
+        }
+
+        let (thresholds, scales) = match mode {
+            ThresholdMode::Global => {
+                let values: Vec<f32> = training_data
+                    .iter()
+                    .flat_map(|v| v.data.iter().copied())
+                    .collect();
+                let (threshold, scale) = fit_threshold(values);
+                (vec![threshold; dim], vec![scale; dim])
+            }
+            ThresholdMode::PerDimension => (0..dim)
+                .map(|d| fit_threshold(training_data.iter().map(|v| v.data[d]).collect()))
+                .unzip(),
+        };
+
+        Self {
+            thresholds,
+            scales,
+            packing,
+            table: decode_table(packing),
+        }
+    }
+
+    /// Maps each element to `-1`, `0`, or `+1` using the learned thresholds.
+    ///
+    /// # Panics
+    /// Panics with a custom error if the input vector's dimension does not match the training data.
+    pub fn quantize(&self, vector: &Vector<f32>) -> Vector<i8> {
+        self.check_dim(vector.len());
+        let data = vector
+            .data
+            .iter()
+            .zip(self.thresholds.iter())
+            .map(|(&x, &t)| {
+                if x > t {
+                    1
+                } else if x < -t {
+                    -1
+                } else {
+                    0
+                }
+            })
+            .collect();
+        Vector::new(data)
+    }
+
+    /// Quantizes a vector into 2-bit codes: `0` for `0`, `1` for `+1` and `2` for `-1`.
+    ///
+    /// # Panics
+    /// Panics with a custom error if the input vector's dimension does not match the training data.
+    pub fn encode(&self, vector: &Vector<f32>) -> PackedCodes {
+        let ternary = self.quantize(vector);
+        PackedCodes::from_codes(2, ternary.data.iter().map(|&t| ternary_code(t)))
+    }
+
+    /// Reconstructs a vector from codes as `α · t` per dimension.
+    ///
+    /// # Panics
+    /// Panics with a custom error if the number of codes does not match the quantizer's dimension
+    /// or a code is not a ternary code.
+    pub fn decode(&self, codes: &PackedCodes) -> Vector<f32> {
+        self.check_dim(codes.len());
+        let data = codes
+            .iter()
+            .zip(self.scales.iter())
+            .map(|(c, &s)| ternary_value(c) as f32 * s)
+            .collect();
+        Vector::new(data)
+    }
+
+    /// Packs codes into bytes with the quantizer's packing.
+    ///
+    /// # Returns
+    /// `ceil(dim / values_per_byte)` bytes. Unused values of the last byte are zero.
+    ///
+    /// # Panics
+    /// Panics with a custom error if the number of codes does not match the quantizer's dimension
+    /// or a code is not a ternary code.
+    pub fn pack(&self, codes: &PackedCodes) -> Vec<u8> {
+        self.check_dim(codes.len());
+        let ternary: Vec<i8> = codes.iter().map(ternary_value).collect();
+        ternary
+            .chunks(self.packing.values_per_byte())
+            .map(|chunk| match self.packing {
+                TernaryPacking::TwoBit => chunk.iter().enumerate().fold(0u8, |byte, (i, &t)| {
+                    byte | ((ternary_code(t) as u8) << (2 * i))
+                }),
+                // The first value is the least significant digit; missing values are zeros.
+                TernaryPacking::Base3 => (0..5).rev().fold(0u8, |byte, i| {
+                    byte * 3 + (chunk.get(i).unwrap_or(&0) + 1) as u8
+                }),
+            })
+            .collect()
+    }
+
+    /// Restores codes from the bytes returned by `pack`.
+    ///
+    /// # Panics
+    /// Panics with a custom error if the number of bytes does not match the quantizer's dimension.
+    pub fn unpack(&self, bytes: &[u8]) -> PackedCodes {
+        self.check_bytes(bytes);
+        PackedCodes::from_codes(2, self.values(bytes).map(ternary_code))
+    }
+
+    /// Computes the dot product between codes packed by `pack` and a float query.
+    ///
+    /// The ternary values are read from the packed bytes through a lookup table, so the dot
+    /// product only adds or subtracts (scaled) query components.
+    ///
+    /// # Panics
+    /// Panics with a custom error if the bytes or the query do not match the quantizer's dimension.
+    pub fn dot(&self, bytes: &[u8], query: &Vector<f32>) -> f32 {
+        self.check_bytes(bytes);
+        self.check_dim(query.len());
+        self.values(bytes)
+            .zip(query.data.iter().zip(self.scales.iter()))
+            .map(|(t, (&q, &s))| match t {
+                1 => q * s,
+                -1 => -q * s,
+                _ => 0.0,
+            })
+            .sum()
+    }
+
+    /// Returns the learned dead-zone threshold of each dimension.
+    pub fn thresholds(&self) -> &[f32] {
+        &self.thresholds
+    }
+
+    /// Returns the learned reconstruction scale of each dimension.
+    pub fn scales(&self) -> &[f32] {
+        &self.scales
+    }
+
+    fn values<'a>(&'a self, bytes: &'a [u8]) -> impl Iterator<Item = i8> + 'a {
+        let per_byte = self.packing.values_per_byte();
+        bytes
+            .iter()
+            .flat_map(move |&b| self.table[b as usize][..per_byte].iter().copied())
+            .take(self.thresholds.len())
+    }
+
+    fn check_dim(&self, len: usize) {
+        if len != self.thresholds.len() {
+            panic!(
+                "{}",
+                VqError::DimensionMismatch {
+                    expected: self.thresholds.len(),
+                    found: len
+                }
+            );
+        }
+    }
+
+    fn check_bytes(&self, bytes: &[u8]) {
+        let expected = self
+            .thresholds
+            .len()
+            .div_ceil(self.packing.values_per_byte());
+        if bytes.len() != expected {
+            panic!(
+                "{}",
+                VqError::DimensionMismatch {
+                    expected,
+                    found: bytes.len()
+                }
+            );
+        }
+    }
+}
+
+/// Returns the 2-bit code of a ternary value.
+fn ternary_code(t: i8) -> u16 {
+    match t {
+        1 => 1,
+        -1 => 2,
+        _ => 0,
+    }
+}
+
+/// Returns the ternary value of a 2-bit code.
+///
+/// # Panics
+/// Panics with a custom error if the code is not `0`, `1` or `2`.
+fn ternary_value(code: u16) -> i8 {
+    match code {
+        0 => 0,
+        1 => 1,
+        2 => -1,
+        _ => panic!(
+            "{}",
+            VqError::InvalidParameter(format!("Code {} is not a ternary code", code))
+        ),
+    }
+}
+
+/// Learns the dead-zone threshold and scale that minimize the squared ternary error of `values`.
+fn fit_threshold(values: Vec<f32>) -> (f32, f32) {
+    let mut magnitudes: Vec<f32> = values.into_iter().map(f32::abs).collect();
+    magnitudes.sort_by(|a, b| b.partial_cmp(a).unwrap());
+
+    // Keeping the k largest magnitudes reduces the error by S_k² / k.
+    let mut best_k = 0;
+    let mut best_gain = 0.0f64;
+    let mut sum = 0.0f64;
+    let mut best_sum = 0.0f64;
+    for (i, &m) in magnitudes.iter().enumerate() {
+        sum += m as f64;
+        let gain = sum * sum / (i + 1) as f64;
+        if gain > best_gain {
+            best_gain = gain;
+            best_k = i + 1;
+            best_sum = sum;
+        }
+    }
+    if best_k == 0 {
+        // Every value is zero: nothing to keep.
+        return (0.0, 0.0);
+    }
+    let smallest_kept = magnitudes[best_k - 1];
+    let largest_dropped = magnitudes.get(best_k).copied().unwrap_or(0.0);
+    let threshold = (smallest_kept + largest_dropped) / 2.0;
+    (threshold, (best_sum / best_k as f64) as f32)
+}
+
+/// Builds the table of ternary values encoded by every byte value.
+fn decode_table(packing: TernaryPacking) -> Vec<[i8; 5]> {
+    (0..=255u8)
+        .map(|byte| {
+            let mut values = [0i8; 5];
+            match packing {
+                TernaryPacking::TwoBit => {
+                    for (i, v) in values.iter_mut().take(4).enumerate() {
+                        *v = match (byte >> (2 * i)) & 0b11 {
+                            1 => 1,
+                            2 => -1,
+                            _ => 0,
+                        };
+                    }
+                }
+                TernaryPacking::Base3 => {
+                    // Bytes above 3⁵ - 1 = 242 are never produced and decode to zeros.
+                    if byte < 243 {
+                        let mut rest = byte;
+                        for v in values.iter_mut() {
+                            *v = (rest % 3) as i8 - 1;
+                            rest /= 3;
+                        }
+                    }
+                }
+            }
+            values
+        })
+        .collect()
+}
@@ -1,15 +1,35 @@
 //! # Tree-Structured Vector Quantizer Implementation
 //!
 //! This module implements a Tree-Structured Vector Quantizer (TSVQ) that builds a binary tree
-//! by recursively partitioning training data along the dimension with maximum variance. Each node
-//! stores the centroid (mean) of its data, and leaf nodes provide the final quantized representations.
-//! During quantization, the TSVQ tree is traversed (using a given distance metric) to select the leaf
-//! whose centroid best approximates the input vector. The final quantized vector is obtained by
-//! converting the leaf centroid from `f32` to half-precision (`f16`).
+//! by recursively splitting the training data in two. Each node stores the centroid (mean) of its
+//! data, and leaf nodes provide the final quantized representations. The `SplitStrategy` decides
+//! how a node is split:
+//! - `AxisMedian`: at the median of the coordinate with the largest variance (the default).
+//! - `TwoMeans`: with 2-means (LBG) under the configured distance metric.
+//! - `PrincipalDirection`: by the hyperplane through the centroid orthogonal to the principal
+//!   direction of the data, which suits correlated data.
+//!
+//! Each inner node stores the routing rule of its split (a threshold, a hyperplane, or the two
+//! 2-means centroids). During quantization, the tree is traversed with the same rules to select
+//! a leaf, and the leaf centroid is converted from `f32` to half-precision (`f16`).
+//!
+//! A single wrong turn near the root can lead to a poor leaf. `nearest_leaves` trades query cost
+//! for accuracy with a `Traversal`: a beam that keeps the `B` nodes nearest to the vector at each
+//! level, or a best-first search that backtracks through a priority queue until it has visited a
+//! given number of nodes. It returns the best leaves found, nearest first.
+//!
+//! Every node records the number of training vectors that reached it and their distortion, so a
+//! deep tree can be pruned afterwards. `prune` applies the generalized BFOS algorithm, which
+//! repeatedly removes the subtree that saves the most rate per unit of added distortion, until
+//! a target number of leaves or average rate is met.
 //!
 //! # Errors
 //! The methods in this module panic with custom errors from the exceptions module when:
 //! - The training data is empty.
+//! - A beam width or node budget is zero.
+//! - A pruning target is zero leaves or a negative rate.
+//! - The 2-means split strategy is used with a distance metric that has no suitable centroid
+//!   update (Chebyshev, Minkowski, or Hamming).
 //! - The input vector’s dimension does not match the expected dimension.
 //!
 //! # Example
@@ -38,17 +58,187 @@
 
 use crate::distances::Distance;
 use crate::exceptions::VqError;
+use crate::packing::PackedCodes;
+use crate::utils::lbg_quantize;
 use crate::vector::{mean_vector, Vector};
 use half::f16;
+use nalgebra::{DMatrix, DVector};
 use rayon::prelude::*;
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+/// How a TSVQ node splits its training data into two children.
+///
+/// The node stores the resulting routing rule, and encoding routes vectors with the same rule.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SplitStrategy {
+    /// Split at the median of the coordinate with the largest variance. Vectors whose coordinate
+    /// is above the median go to the right child.
+    #[default]
+    AxisMedian,
+    /// Split with 2-means (LBG with `k = 2`) under the configured distance metric. Vectors go to
+    /// the child whose 2-means centroid is nearer.
+    TwoMeans {
+        /// The maximum number of LBG iterations at each node.
+        max_iters: usize,
+        /// The random seed for initializing LBG.
+        seed: u64,
+    },
+    /// Split by the hyperplane through the centroid orthogonal to the principal direction of the
+    /// data. Vectors on the positive side of the hyperplane go to the right child.
+    PrincipalDirection,
+}
+
+/// The routing rule of an inner node, learned when the node was split.
+#[derive(Clone)]
+enum Split {
+    /// Go right if `vector[dim] > threshold`.
+    Axis { dim: usize, threshold: f32 },
+    /// Go right if `vector · normal > offset`.
+    Hyperplane { normal: Vec<f32>, offset: f32 },
+    /// Go right if the vector is nearer to `right` than to `left` under the distance metric.
+    Centroids {
+        left: Vector<f32>,
+        right: Vector<f32>,
+    },
+}
+
+impl Split {
+    /// Learns a routing rule for `training_data` with the given strategy.
+    fn fit(
+        training_data: &[Vector<f32>],
+        centroid: &Vector<f32>,
+        strategy: SplitStrategy,
+        distance: &Distance,
+    ) -> Self {
+        match strategy {
+            SplitStrategy::AxisMedian => {
+                let dim = centroid.len();
+                // Compute variances in parallel for each dimension.
+                let variances: Vec<f32> = (0..dim)
+                    .into_par_iter()
+                    .map(|i| {
+                        training_data
+                            .iter()
+                            .map(|v| {
+                                let diff = v.data[i] - centroid.data[i];
+                                diff * diff
+                            })
+                            .sum()
+                    })
+                    .collect();
+
+                // Select the dimension with maximum variance for splitting.
+                let (split_dim, _) = variances
+                    .iter()
+                    .enumerate()
+                    .max_by(|a, b| a.1.partial_cmp(b.1).unwrap())
+                    .unwrap();
+
+                // Extract the values along the chosen dimension and sort them.
+                let mut values: Vec<f32> =
+                    training_data.iter().map(|v| v.data[split_dim]).collect();
+                values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+                // Compute the median: if even number of elements, use the average of the two middle values.
+                let threshold = if values.len() % 2 == 0 {
+                    (values[values.len() / 2 - 1] + values[values.len() / 2]) / 2.0
+                } else {
+                    values[values.len() / 2]
+                };
+                Split::Axis {
+                    dim: split_dim,
+                    threshold,
+                }
+            }
+            SplitStrategy::TwoMeans { max_iters, seed } => {
+                let (centroids, _) = lbg_quantize(
+                    training_data,
+                    2,
+                    max_iters,
+                    distance,
+                    seed,
+                    None,
+                    &|_, _| true,
+                );
+                let mut centroids = centroids.into_iter();
+                Split::Centroids {
+                    left: centroids.next().unwrap(),
+                    right: centroids.next().unwrap(),
+                }
+            }
+            SplitStrategy::PrincipalDirection => {
+                let dim = centroid.len();
+                let covariance = training_data
+                    .par_iter()
+                    .fold(
+                        || DMatrix::<f64>::zeros(dim, dim),
+                        |mut acc, v| {
+                            let centred = DVector::<f64>::from_iterator(
+                                dim,
+                                v.data
+                                    .iter()
+                                    .zip(centroid.data.iter())
+                                    .map(|(&x, &c)| (x - c) as f64),
+                            );
+                            acc.ger(1.0, &centred, &centred, 1.0);
+                            acc
+                        },
+                    )
+                    .reduce(|| DMatrix::zeros(dim, dim), |a, b| a + b);
+                let eigen = covariance.symmetric_eigen();
+                let principal = eigen.eigenvalues.imax();
+                let normal: Vec<f32> = eigen
+                    .eigenvectors
+                    .column(principal)
+                    .iter()
+                    .map(|&x| x as f32)
+                    .collect();
+                let offset = dot(&normal, &centroid.data);
+                Split::Hyperplane { normal, offset }
+            }
+        }
+    }
+
+    /// Returns true if `vector` is routed to the right child.
+    fn goes_right(&self, vector: &Vector<f32>, distance: &Distance) -> bool {
+        match self {
+            Split::Axis { dim, threshold } => vector.data[*dim] > *threshold,
+            Split::Hyperplane { normal, offset } => dot(normal, &vector.data) > *offset,
+            Split::Centroids { left, right } => {
+                distance.compute(&vector.data, &left.data)
+                    > distance.compute(&vector.data, &right.data)
+            }
+        }
+    }
+}
+
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b.iter()).map(|(&x, &y)| x * y).sum()
+}
 
 /// A node in the Tree-Structured Vector Quantizer (TSVQ) tree.
 ///
-/// Each node holds a centroid (the mean of the training data at that node)
-/// and optionally left/right child nodes representing further splits.
+/// Each node holds a centroid (the mean of the training data at that node). An inner node also
+/// holds the rule that routes vectors to its left or right child.
+#[derive(Clone)]
 struct TSVQNode {
     /// The centroid of the training data at this node.
     pub centroid: Vector<f32>,
+    /// The number of training vectors at this node.
+    count: usize,
+    /// The summed distance between the training vectors at this node and its centroid.
+    distortion: f64,
+    /// The summed distortion of the leaves of this subtree.
+    subtree_distortion: f64,
+    /// The summed depth of the training vectors below this node.
+    subtree_rate: f64,
+    /// The number of leaves of this subtree.
+    leaves: usize,
+    /// The smallest slope `ΔD / ΔR` of an inner node of this subtree (infinite for a leaf).
+    min_slope: f64,
+    /// The routing rule of an inner node (`None` for a leaf).
+    split: Option<Split>,
     /// Left subtree (if any).
     pub left: Option<Box<TSVQNode>>,
     /// Right subtree (if any).
@@ -62,144 +252,252 @@ impl TSVQNode {
     /// - `training_data`: A slice of training vectors used to build this node.
     /// - `max_depth`: The maximum depth of recursion. When 0 or if there is only one
     ///   training vector, the node is a leaf.
+    /// - `strategy`: How the training data is split between the children.
+    /// - `distance`: The distance metric used by the 2-means split.
     ///
     /// # Returns
-    /// A `TSVQNode` containing the centroid and (optionally) left/right child nodes.
+    /// A `TSVQNode` containing the centroid and (optionally) left/right child nodes. A node
+    /// whose split would leave a child empty becomes a leaf.
     ///
     /// # Panics
     /// Panics with a custom error if `training_data` is empty.
-    pub fn fit(training_data: &[Vector<f32>], max_depth: usize) -> Self {
+    pub fn fit(
+        training_data: &[Vector<f32>],
+        max_depth: usize,
+        strategy: SplitStrategy,
+        distance: &Distance,
+    ) -> Self {
         if training_data.is_empty() {
             panic!("{}", VqError::EmptyInput);
         }
-        // Compute the centroid of the training data.
+        // Compute the centroid of the training data and its distortion.
         let centroid = mean_vector(training_data);
+        let count = training_data.len();
+        let distortion = training_data
+            .par_iter()
+            .map(|v| distance.compute(&v.data, &centroid.data) as f64)
+            .sum();
+        let leaf = |centroid| TSVQNode {
+            centroid,
+            count,
+            distortion,
+            subtree_distortion: distortion,
+            subtree_rate: 0.0,
+            leaves: 1,
+            min_slope: f64::INFINITY,
+            split: None,
+            left: None,
+            right: None,
+        };
         // If we've reached maximum depth or have one or fewer vectors, make a leaf.
         if max_depth == 0 || training_data.len() <= 1 {
-            return TSVQNode {
-                centroid,
-                left: None,
-                right: None,
-            };
+            return leaf(centroid);
         }
-        let dim = centroid.len();
-
-        // Compute variances in parallel for each dimension.
-        let variances: Vec<f32> = (0..dim)
-            .into_par_iter()
-            .map(|i| {
-                training_data
-                    .iter()
-                    .map(|v| {
-                        let diff = v.data[i] - centroid.data[i];
-                        diff * diff
-                    })
-                    .sum()
-            })
-            .collect();
 
-        // Select the dimension with maximum variance for splitting.
-        let (split_dim, _) = variances
-            .iter()
-            .enumerate()
-            .max_by(|a, b| a.1.partial_cmp(b.1).unwrap())
-            .unwrap();
-
-        // Extract the values along the chosen dimension and sort them.
-        let mut values: Vec<f32> = training_data.iter().map(|v| v.data[split_dim]).collect();
-        values.sort_by(|a, b| a.partial_cmp(b).unwrap());
-
-        // Compute the median: if even number of elements, use the average of the two middle values.
-        let median = if values.len() % 2 == 0 {
-            (values[values.len() / 2 - 1] + values[values.len() / 2]) / 2.0
-        } else {
-            values[values.len() / 2]
-        };
-
-        // Partition the training data based on the median along the split dimension.
-        let (left_data, right_data): (Vec<Vector<f32>>, Vec<Vector<f32>>) = training_data
+        // Partition the training data with the same rule that routes vectors when encoding.
+        let split = Split::fit(training_data, &centroid, strategy, distance);
+        let (right_data, left_data): (Vec<Vector<f32>>, Vec<Vector<f32>>) = training_data
             .iter()
             .cloned()
-            .partition(|v| v.data[split_dim] <= median);
+            .partition(|v| split.goes_right(v, distance));
+        if left_data.is_empty() || right_data.is_empty() {
+            return leaf(centroid);
+        }
 
         // Recursively build left and right children in parallel.
         let (left, right) = rayon::join(
-            || {
-                if !left_data.is_empty() && left_data.len() < training_data.len() {
-                    Some(Box::new(TSVQNode::fit(&left_data, max_depth - 1)))
-                } else {
-                    None
-                }
-            },
-            || {
-                if !right_data.is_empty() && right_data.len() < training_data.len() {
-                    Some(Box::new(TSVQNode::fit(&right_data, max_depth - 1)))
-                } else {
-                    None
-                }
-            },
+            || TSVQNode::fit(&left_data, max_depth - 1, strategy, distance),
+            || TSVQNode::fit(&right_data, max_depth - 1, strategy, distance),
         );
 
-        TSVQNode {
-            centroid,
-            left,
-            right,
-        }
+        let mut node = leaf(centroid);
+        node.split = Some(split);
+        node.left = Some(Box::new(left));
+        node.right = Some(Box::new(right));
+        node.update_stats();
+        node
     }
 
     /// Recursively traverses the TSVQ tree to quantize an input vector.
     ///
-    /// At each node, the distance between the input vector and the centroids of
-    /// the child nodes is computed using the provided distance metric. The traversal
-    /// proceeds into the child with the smaller distance until a leaf node is reached.
+    /// At each inner node, the routing rule learned during training selects the child to
+    /// descend into, until a leaf node is reached.
     ///
     /// # Parameters
     /// - `vector`: The input vector to quantize.
-    /// - `distance`: A reference to the distance metric used for comparing vectors.
+    /// - `distance`: A reference to the distance metric used by 2-means routing rules.
     ///
     /// # Returns
-    /// A reference to the leaf `TSVQNode` whose centroid best approximates the input.
+    /// A reference to the leaf `TSVQNode` whose centroid approximates the input.
     pub fn quantize_with_distance<'a>(
         &'a self,
         vector: &Vector<f32>,
         distance: &Distance,
     ) -> &'a TSVQNode {
-        match (&self.left, &self.right) {
-            (Some(left), Some(right)) => {
-                let dist_left = distance.compute(&vector.data, &left.centroid.data);
-                let dist_right = distance.compute(&vector.data, &right.centroid.data);
-                if dist_left <= dist_right {
-                    left.quantize_with_distance(vector, distance)
-                } else {
+        match (&self.split, &self.left, &self.right) {
+            (Some(split), Some(left), Some(right)) => {
+                if split.goes_right(vector, distance) {
                     right.quantize_with_distance(vector, distance)
+                } else {
+                    left.quantize_with_distance(vector, distance)
                 }
             }
-            (Some(left), None) => left.quantize_with_distance(vector, distance),
-            (None, Some(right)) => right.quantize_with_distance(vector, distance),
-            (None, None) => self,
+            _ => self,
         }
     }
+
+    /// Traverses the tree like `quantize_with_distance`, recording one bit per visited node
+    /// (0 for the left child, 1 for the right child).
+    fn encode_path(&self, vector: &Vector<f32>, distance: &Distance, path: &mut PackedCodes) {
+        let go_right = match &self.split {
+            Some(split) => split.goes_right(vector, distance),
+            None => return,
+        };
+        path.push(go_right as u16);
+        let child = if go_right { &self.right } else { &self.left };
+        child.as_ref().unwrap().encode_path(vector, distance, path);
+    }
+
+    /// Returns the left and right children of an inner node.
+    fn children(&self) -> Option<(&TSVQNode, &TSVQNode)> {
+        match (&self.left, &self.right) {
+            (Some(left), Some(right)) => Some((left, right)),
+            _ => None,
+        }
+    }
+
+    /// Recomputes the cached subtree statistics of this node from its children.
+    ///
+    /// Pruning node `t` adds `ΔD(t) = D(t) - D(T_t)` of distortion and saves `ΔR(t)` of rate,
+    /// where `ΔR(t)` is the summed depth of the training vectors below `t`: every training vector
+    /// of the subtree sits one level deeper than in its child subtree.
+    fn update_stats(&mut self) {
+        match (&self.left, &self.right) {
+            (Some(left), Some(right)) => {
+                self.subtree_distortion = left.subtree_distortion + right.subtree_distortion;
+                self.subtree_rate = left.subtree_rate + right.subtree_rate + self.count as f64;
+                self.leaves = left.leaves + right.leaves;
+                let slope = (self.distortion - self.subtree_distortion) / self.subtree_rate;
+                self.min_slope = slope.min(left.min_slope).min(right.min_slope);
+            }
+            _ => {
+                self.subtree_distortion = self.distortion;
+                self.subtree_rate = 0.0;
+                self.leaves = 1;
+                self.min_slope = f64::INFINITY;
+            }
+        }
+    }
+
+    /// Turns the inner node with the smallest slope (the weakest link of the BFOS algorithm)
+    /// into a leaf and updates the statistics of its ancestors.
+    ///
+    /// The weakest link is found by following the cached minimum slopes from this node, so only
+    /// one path of the tree is visited. Ties go to the left subtree, then the right subtree, and
+    /// then this node.
+    fn prune_weakest_link(&mut self) {
+        match (&mut self.left, &mut self.right) {
+            (Some(left), _) if left.min_slope == self.min_slope => left.prune_weakest_link(),
+            (_, Some(right)) if right.min_slope == self.min_slope => right.prune_weakest_link(),
+            _ => {
+                self.split = None;
+                self.left = None;
+                self.right = None;
+            }
+        }
+        self.update_stats();
+    }
+}
+
+/// The size that `TSVQ::prune` reduces a tree to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PruneTarget {
+    /// Prune until the tree has at most this many leaves.
+    Leaves(usize),
+    /// Prune until the average path length of the training vectors, in bits, is at most this value.
+    AverageRate(f32),
+}
+
+/// How `TSVQ` searches the tree for the leaves of an input vector.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Traversal {
+    /// Follow the routing rule of each node down to a single leaf (as `quantize` does).
+    Greedy,
+    /// Keep the `beam_width` nodes whose centroids are nearest to the vector at each level.
+    /// Leaves reached on the way stay candidates.
+    Beam {
+        /// The number of nodes kept at each level.
+        beam_width: usize,
+    },
+    /// Descend greedily to a leaf while queueing every child that was not taken, then backtrack
+    /// from the queued node whose centroid is nearest to the vector, until `max_nodes` nodes
+    /// have been visited. The first descent always reaches its leaf, so a budget of one node
+    /// gives the greedy traversal.
+    BestFirst {
+        /// The number of nodes visited before the search stops.
+        max_nodes: usize,
+    },
+}
+
+/// A queued subtree in the best-first traversal, ordered so that the nearest pops first.
+struct Candidate<'a> {
+    distance: f32,
+    node: &'a TSVQNode,
+    path: PackedCodes,
+}
+
+impl PartialEq for Candidate<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Candidate<'_> {}
+
+impl PartialOrd for Candidate<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.distance.total_cmp(&self.distance)
+    }
+}
+
+/// Appends one bit to a copy of `path`.
+fn extend_path(path: &PackedCodes, bit: u16) -> PackedCodes {
+    let mut extended = path.clone();
+    extended.push(bit);
+    extended
 }
 
 /// A Tree-Structured Vector Quantizer (TSVQ) that builds a binary tree for quantization.
 ///
-/// The TSVQ is constructed from a set of training data by recursively partitioning
-/// the data along the dimension of maximum variance. Each node stores the mean
-/// (centroid) of its data, and leaf nodes provide the final quantized representations.
+/// The TSVQ is constructed from a set of training data by recursively splitting the data in
+/// two with a `SplitStrategy`. Each node stores the mean (centroid) of its data, and leaf nodes
+/// provide the final quantized representations.
+#[derive(Clone)]
 pub struct TSVQ {
     /// The root node of the TSVQ tree.
     root: TSVQNode,
-    /// The distance metric used for traversing the tree.
+    /// The distance metric used by `TwoMeans` splits, by beam and best-first traversals to rank
+    /// nodes, and for the node distortions that pruning uses. Greedy routing follows the split
+    /// rule stored at each node.
     pub distance: Distance,
 }
 
 impl TSVQ {
-    /// Constructs a new TSVQ from the given training data.
+    /// Constructs a new TSVQ from the given training data, splitting nodes at the median of
+    /// the coordinate with the largest variance (`SplitStrategy::AxisMedian`).
     ///
     /// # Parameters
     /// - `training_data`: A slice of training vectors used to build the tree.
     /// - `max_depth`: The maximum depth of the TSVQ tree. A larger value allows finer partitions.
-    /// - `distance`: The distance metric to use for comparing vectors during tree traversal.
+    /// - `distance`: The distance metric used to measure node distortions and to rank nodes in
+    ///   beam and best-first traversals. Vectors are routed by the axis threshold of each node,
+    ///   not by the nearest child centroid.
     ///
     /// # Returns
     /// A new `TSVQ` instance with the constructed tree and stored distance metric.
@@ -207,16 +505,43 @@ impl TSVQ {
     /// # Panics
     /// Panics with a custom error if the training data is empty.
     pub fn new(training_data: &[Vector<f32>], max_depth: usize, distance: Distance) -> Self {
+        Self::with_split_strategy(
+            training_data,
+            max_depth,
+            distance,
+            SplitStrategy::AxisMedian,
+        )
+    }
+
+    /// Constructs a new TSVQ whose nodes are split with the given strategy.
+    ///
+    /// # Parameters
+    /// - `training_data`: A slice of training vectors used to build the tree.
+    /// - `max_depth`: The maximum depth of the TSVQ tree.
+    /// - `distance`: The distance metric used by `SplitStrategy::TwoMeans`, both for training
+    ///   the split and for routing vectors. Like in `new`, it also measures node distortions and
+    ///   ranks nodes in beam and best-first traversals.
+    /// - `strategy`: How each node splits its training data.
+    ///
+    /// # Panics
+    /// Panics with a custom error if the training data is empty, or if the strategy is
+    /// `TwoMeans` and the distance metric is not supported for codebook training.
+    pub fn with_split_strategy(
+        training_data: &[Vector<f32>],
+        max_depth: usize,
+        distance: Distance,
+        strategy: SplitStrategy,
+    ) -> Self {
         if training_data.is_empty() {
             panic!("{}", VqError::EmptyInput);
         }
-        let root = TSVQNode::fit(training_data, max_depth);
+        let root = TSVQNode::fit(training_data, max_depth, strategy, &distance);
         TSVQ { root, distance }
     }
 
     /// Quantizes an input vector by traversing the TSVQ tree.
     ///
-    /// The traversal uses the stored distance metric to determine which branch to follow at each node.
+    /// The traversal follows the split rule stored at each node to determine which branch to take.
     /// Once a leaf node is reached, its centroid is returned as the quantized representation.
     /// The resulting vector is converted to half-precision (`f16`).
     ///
@@ -229,15 +554,7 @@ impl TSVQ {
     /// # Panics
     /// Panics with a custom error if the input vector's dimension does not match the expected dimension.
     pub fn quantize(&self, vector: &Vector<f32>) -> Vector<f16> {
-        if vector.len() != self.root.centroid.len() {
-            panic!(
-                "{}",
-                VqError::DimensionMismatch {
-                    expected: self.root.centroid.len(),
-                    found: vector.len()
-                }
-            );
-        }
+        self.check_dim(vector.len());
         let leaf = self.root.quantize_with_distance(vector, &self.distance);
         let centroid_f16: Vec<f16> = leaf
             .centroid
@@ -247,4 +564,252 @@ impl TSVQ {
             .collect();
         Vector::new(centroid_f16)
     }
+
+    /// Encodes an input vector as the path from the root to its leaf, one bit per level
+    /// (0 for the left child, 1 for the right child).
+    ///
+    /// # Panics
+    /// Panics with a custom error if the input vector's dimension does not match the expected dimension.
+    pub fn encode(&self, vector: &Vector<f32>) -> PackedCodes {
+        self.check_dim(vector.len());
+        let mut path = PackedCodes::new(1);
+        self.root.encode_path(vector, &self.distance, &mut path);
+        path
+    }
+
+    /// Returns the centroid of the node reached by following an encoded path from the root.
+    ///
+    /// # Panics
+    /// Panics with a custom error if the codes are not 1-bit or the path leaves the tree.
+    pub fn decode(&self, path: &PackedCodes) -> Vector<f32> {
+        if path.bits() != 1 {
+            panic!(
+                "{}",
+                VqError::InvalidParameter(format!(
+                    "Expected 1-bit codes, got {}-bit codes",
+                    path.bits()
+                ))
+            );
+        }
+        let mut node = &self.root;
+        for bit in path.iter() {
+            let child = if bit == 1 { &node.right } else { &node.left };
+            node = match child {
+                Some(child) => child,
+                None => panic!(
+                    "{}",
+                    VqError::InvalidParameter("Path does not exist in the tree".to_string())
+                ),
+            };
+        }
+        node.centroid.clone()
+    }
+
+    /// Searches the tree for the leaves nearest to an input vector.
+    ///
+    /// Leaves are ranked by the configured distance between the input vector and their
+    /// centroids. Wider beams and larger node budgets visit more nodes and are more likely to
+    /// find the nearest leaf of the whole tree.
+    ///
+    /// # Parameters
+    /// - `vector`: The input vector to search for.
+    /// - `traversal`: How the tree is searched.
+    /// - `n`: The maximum number of leaves to return.
+    ///
+    /// # Returns
+    /// Up to `n` of the reached leaves as `(path, distance)` pairs sorted by increasing distance.
+    /// Paths have the format returned by `encode`.
+    ///
+    /// # Panics
+    /// Panics with a custom error if the input vector's dimension does not match the expected
+    /// dimension, or the beam width or node budget is zero.
+    pub fn nearest_leaves(
+        &self,
+        vector: &Vector<f32>,
+        traversal: Traversal,
+        n: usize,
+    ) -> Vec<(PackedCodes, f32)> {
+        self.check_dim(vector.len());
+        let node_distance =
+            |node: &TSVQNode| self.distance.compute(&vector.data, &node.centroid.data);
+        let mut leaves: Vec<(PackedCodes, f32)> = Vec::new();
+        match traversal {
+            Traversal::Greedy => {
+                let path = self.encode(vector);
+                let leaf = self.root.quantize_with_distance(vector, &self.distance);
+                leaves.push((path, node_distance(leaf)));
+            }
+            Traversal::Beam { beam_width } => {
+                check_positive(beam_width, "beam_width");
+                let mut beam = vec![(&self.root, PackedCodes::new(1))];
+                while !beam.is_empty() {
+                    let mut next: Vec<(&TSVQNode, PackedCodes, f32)> = Vec::new();
+                    for (node, path) in beam {
+                        match node.children() {
+                            Some((left, right)) => {
+                                next.push((left, extend_path(&path, 0), node_distance(left)));
+                                next.push((right, extend_path(&path, 1), node_distance(right)));
+                            }
+                            None => leaves.push((path, node_distance(node))),
+                        }
+                    }
+                    next.sort_by(|a, b| a.2.total_cmp(&b.2));
+                    next.truncate(beam_width);
+                    beam = next
+                        .into_iter()
+                        .map(|(node, path, _)| (node, path))
+                        .collect();
+                }
+            }
+            Traversal::BestFirst { max_nodes } => {
+                check_positive(max_nodes, "max_nodes");
+                let mut queue = BinaryHeap::new();
+                queue.push(Candidate {
+                    distance: node_distance(&self.root),
+                    node: &self.root,
+                    path: PackedCodes::new(1),
+                });
+                let mut visited = 0;
+                while visited < max_nodes {
+                    let Some(Candidate {
+                        mut node, mut path, ..
+                    }) = queue.pop()
+                    else {
+                        break;
+                    };
+                    visited += 1;
+                    // Descend with the routing rules, queueing the children not taken. Only the
+                    // first descent may exceed the budget, so that a leaf is always found.
+                    while let (Some(split), Some((left, right))) = (&node.split, node.children()) {
+                        if visited >= max_nodes && !leaves.is_empty() {
+                            break;
+                        }
+                        let go_right = split.goes_right(vector, &self.distance);
+                        let (taken, other) = if go_right {
+                            (right, left)
+                        } else {
+                            (left, right)
+                        };
+                        queue.push(Candidate {
+                            distance: node_distance(other),
+                            node: other,
+                            path: extend_path(&path, !go_right as u16),
+                        });
+                        path.push(go_right as u16);
+                        node = taken;
+                        visited += 1;
+                    }
+                    if node.split.is_none() {
+                        leaves.push((path, node_distance(node)));
+                    }
+                }
+            }
+        }
+        leaves.sort_by(|a, b| a.1.total_cmp(&b.1));
+        leaves.truncate(n);
+        leaves
+    }
+
+    /// Encodes an input vector as the path to the nearest leaf found by `traversal`.
+    ///
+    /// # Panics
+    /// Same conditions as `nearest_leaves`.
+    pub fn encode_with_traversal(&self, vector: &Vector<f32>, traversal: Traversal) -> PackedCodes {
+        self.nearest_leaves(vector, traversal, 1).swap_remove(0).0
+    }
+
+    /// Quantizes an input vector to the centroid of the nearest leaf found by `traversal`,
+    /// converted to half-precision (`f16`).
+    ///
+    /// # Panics
+    /// Same conditions as `nearest_leaves`.
+    pub fn quantize_with_traversal(
+        &self,
+        vector: &Vector<f32>,
+        traversal: Traversal,
+    ) -> Vector<f16> {
+        let centroid = self.decode(&self.encode_with_traversal(vector, traversal));
+        Vector::new(centroid.data.iter().map(|&x| f16::from_f32(x)).collect())
+    }
+
+    /// Returns a copy of this tree pruned with the generalized BFOS algorithm.
+    ///
+    /// Each inner node `t` would save `ΔR(t)` bits of average rate and cost `ΔD(t)` of average
+    /// distortion if its subtree were replaced by a single leaf, both measured on the training
+    /// vectors. The subtree with the smallest slope `ΔD(t) / ΔR(t)` is pruned first, and pruning
+    /// repeats until the target is met. The pruned subtrees lie on the lower convex hull of the
+    /// rate-distortion curve, so a deep tree pruned to a given size usually has lower distortion
+    /// than a tree grown to a fixed depth with the same number of leaves.
+    ///
+    /// # Parameters
+    /// - `target`: The number of leaves or the average rate to prune to.
+    ///
+    /// # Panics
+    /// Panics with a custom error if the target number of leaves is zero or the target rate is
+    /// negative or not finite.
+    pub fn prune(&self, target: PruneTarget) -> Self {
+        match target {
+            PruneTarget::Leaves(0) => panic!(
+                "{}",
+                VqError::InvalidParameter("Target number of leaves must be at least 1".to_string())
+            ),
+            PruneTarget::AverageRate(rate) if !(rate.is_finite() && rate >= 0.0) => panic!(
+                "{}",
+                VqError::InvalidParameter(format!(
+                    "Target average rate must be finite and non-negative, got {}",
+                    rate
+                ))
+            ),
+            _ => {}
+        }
+        let mut tree = self.clone();
+        loop {
+            let done = match target {
+                PruneTarget::Leaves(max_leaves) => tree.root.leaves <= max_leaves,
+                PruneTarget::AverageRate(max_rate) => {
+                    tree.root.subtree_rate / tree.root.count as f64 <= max_rate as f64
+                }
+            };
+            if done || tree.root.split.is_none() {
+                return tree;
+            }
+            tree.root.prune_weakest_link();
+        }
+    }
+
+    /// Returns the number of leaves of the tree.
+    pub fn leaf_count(&self) -> usize {
+        self.root.leaves
+    }
+
+    /// Returns the average path length in bits of the training vectors.
+    pub fn average_rate(&self) -> f32 {
+        (self.root.subtree_rate / self.root.count as f64) as f32
+    }
+
+    /// Returns the mean distance between the training vectors and the centroids of their leaves.
+    pub fn distortion(&self) -> f32 {
+        (self.root.subtree_distortion / self.root.count as f64) as f32
+    }
+
+    fn check_dim(&self, len: usize) {
+        if len != self.root.centroid.len() {
+            panic!(
+                "{}",
+                VqError::DimensionMismatch {
+                    expected: self.root.centroid.len(),
+                    found: len
+                }
+            );
+        }
+    }
+}
+
+fn check_positive(value: usize, name: &str) {
+    if value == 0 {
+        panic!(
+            "{}",
+            VqError::InvalidParameter(format!("{} must be greater than 0", name))
+        );
+    }
 }
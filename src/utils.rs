@@ -3,38 +3,148 @@
 //! This module contains helper functions for vector quantization.
 //! The main function here is `lbg_quantize`, which implements the Linde-Buzo-Gray (LBG)
 //! algorithm for vector quantization using parallel operations when it is beneficial.
+//! The centroid update follows the configured distance metric, so codebooks are optimized
+//! for the same metric that is later used for encoding.
 
+use crate::distances::Distance;
 use crate::exceptions::VqError;
+use crate::report::ClusteringReport;
 use crate::vector::{mean_vector, Vector};
 use rand::prelude::IndexedRandom;
 use rand::rngs::StdRng;
 use rand::SeedableRng;
 use rayon::prelude::*;
 
+/// The rule used to recompute a cluster centroid from its members.
+///
+/// Each rule minimizes the within-cluster distortion for a family of distance metrics:
+/// - `Mean`: the arithmetic mean (k-means), for squared and plain Euclidean distance.
+/// - `SphericalMean`: the normalized mean (spherical k-means), for cosine distance.
+/// - `Median`: the coordinate-wise median (k-medians), for Manhattan distance.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum CentroidUpdate {
+    Mean,
+    SphericalMean,
+    Median,
+}
+
+impl CentroidUpdate {
+    /// Selects the centroid update that matches the given distance metric.
+    ///
+    /// # Errors
+    /// Returns `VqError::UnsupportedDistance` for metrics without a suitable centroid update
+    /// (Chebyshev, Minkowski, and Hamming).
+    pub(crate) fn for_distance(distance: &Distance) -> Result<Self, VqError> {
+        match distance {
+            Distance::SquaredEuclidean | Distance::Euclidean => Ok(CentroidUpdate::Mean),
+            Distance::CosineDistance => Ok(CentroidUpdate::SphericalMean),
+            Distance::Manhattan => Ok(CentroidUpdate::Median),
+            other => Err(VqError::UnsupportedDistance {
+                metric: format!("{:?}", other),
+                details: "no centroid update is defined for codebook training".to_string(),
+            }),
+        }
+    }
+
+    /// Computes the assignment cost between a vector and a centroid.
+    ///
+    /// For the mean update the squared Euclidean distance is used, which yields the same
+    /// assignments as the Euclidean distance while avoiding the square root.
+    fn cost(&self, distance: &Distance, v: &Vector<f32>, centroid: &Vector<f32>) -> f32 {
+        match self {
+            CentroidUpdate::Mean => v.distance2(centroid),
+            _ => distance.compute(&v.data, &centroid.data),
+        }
+    }
+
+    /// Prepares a data point for use as a centroid (used for initialization and reseeding).
+    fn seed_centroid(&self, v: &Vector<f32>) -> Vector<f32> {
+        match self {
+            CentroidUpdate::SphericalMean => normalize(v),
+            _ => v.clone(),
+        }
+    }
+
+    /// Recomputes the centroid of a non-empty cluster.
+    fn update(&self, cluster: &[Vector<f32>]) -> Vector<f32> {
+        match self {
+            CentroidUpdate::Mean => mean_vector(cluster),
+            CentroidUpdate::SphericalMean => normalize(&mean_vector(cluster)),
+            CentroidUpdate::Median => median_vector(cluster),
+        }
+    }
+}
+
+/// Scales a vector to unit Euclidean norm. A zero vector is returned unchanged.
+fn normalize(v: &Vector<f32>) -> Vector<f32> {
+    let norm = v.norm();
+    if norm > 0.0 {
+        v * (1.0 / norm)
+    } else {
+        v.clone()
+    }
+}
+
+/// Computes the coordinate-wise median of a non-empty slice of vectors.
+///
+/// For an even number of vectors, the average of the two middle values is used.
+fn median_vector(vectors: &[Vector<f32>]) -> Vector<f32> {
+    let dim = vectors[0].len();
+    let n = vectors.len();
+    let data = (0..dim)
+        .map(|i| {
+            let mut values: Vec<f32> = vectors.iter().map(|v| v.data[i]).collect();
+            values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            if n % 2 == 0 {
+                (values[n / 2 - 1] + values[n / 2]) / 2.0
+            } else {
+                values[n / 2]
+            }
+        })
+        .collect();
+    Vector::new(data)
+}
+
 /// Quantizes the input data into `k` clusters using the LBG algorithm.
 ///
 /// The function randomly selects `k` initial centroids and iteratively refines them by
 /// assigning each data point to the nearest centroid and then recomputing the centroids.
 /// Parallel iteration is used for assignments and cluster grouping when possible.
 ///
+/// Both steps follow the given distance metric: Euclidean metrics use the arithmetic mean,
+/// cosine distance uses spherical k-means (unit-norm centroids), and Manhattan distance uses
+/// k-medians.
+///
 /// # Parameters
 /// - `data`: A slice of vectors to quantize.
 /// - `k`: The number of clusters (must be > 0 and ≤ number of data points).
 /// - `max_iters`: Maximum iterations for the refinement process.
+/// - `distance`: The distance metric the codebook is optimized for.
 /// - `seed`: A seed for random number generation to ensure reproducibility.
+/// - `initial`: Optional initial centroids (for example an existing codebook to refine). When
+///   `None`, `k` data points are selected at random.
+/// - `on_iteration`: Called with the iteration number and its distortion after every
+///   iteration. Returning `false` stops the refinement early (used for cancellation).
 ///
 /// # Returns
-/// A vector of centroids (quantized vectors).
+/// A vector of centroids (quantized vectors), together with a `ClusteringReport` holding the
+/// distortion of each iteration, the convergence flag, and the number of reseeded empty clusters.
 ///
 /// # Panics
+/// - If the distance metric has no suitable centroid update (Chebyshev, Minkowski, Hamming).
 /// - If `k` is 0.
 /// - If there are fewer data points than clusters.
+/// - If `initial` does not hold `k` centroids of the data dimension.
 pub fn lbg_quantize(
     data: &[Vector<f32>],
     k: usize,
     max_iters: usize,
+    distance: &Distance,
     seed: u64,
-) -> Vec<Vector<f32>> {
+    initial: Option<&[Vector<f32>]>,
+    on_iteration: &dyn Fn(usize, f32) -> bool,
+) -> (Vec<Vector<f32>>, ClusteringReport) {
+    let update = CentroidUpdate::for_distance(distance).unwrap_or_else(|e| panic!("{}", e));
     let n = data.len();
     if k == 0 {
         panic!(
@@ -50,27 +160,61 @@ pub fn lbg_quantize(
     }
 
     let mut rng = StdRng::seed_from_u64(seed);
-    // Randomly select k initial centroids.
-    let mut centroids: Vec<Vector<f32>> = data.choose_multiple(&mut rng, k).cloned().collect();
+    let mut centroids: Vec<Vector<f32>> = match initial {
+        // Start from the given centroids.
+        Some(initial) => {
+            if initial.len() != k {
+                panic!(
+                    "{}",
+                    VqError::InvalidParameter(
+                        "Initial centroids must hold exactly k vectors".to_string()
+                    )
+                );
+            }
+            let dim = data[0].len();
+            if let Some(c) = initial.iter().find(|c| c.len() != dim) {
+                panic!(
+                    "{}",
+                    VqError::DimensionMismatch {
+                        expected: dim,
+                        found: c.len()
+                    }
+                );
+            }
+            initial.iter().map(|c| update.seed_centroid(c)).collect()
+        }
+        // Randomly select k initial centroids.
+        None => data
+            .choose_multiple(&mut rng, k)
+            .map(|v| update.seed_centroid(v))
+            .collect(),
+    };
     let mut assignments = vec![0; n];
+    let mut report = ClusteringReport {
+        distortions: Vec::with_capacity(max_iters),
+        converged: false,
+        empty_clusters: 0,
+    };
 
-    for _ in 0..max_iters {
+    for iteration in 0..max_iters {
         // Assignment step: assign each vector to the nearest centroid.
-        let new_assignments: Vec<usize> = data
+        let (new_assignments, costs): (Vec<usize>, Vec<f32>) = data
             .par_iter()
             .map(|v| {
                 let mut best = 0;
-                let mut best_dist = v.distance2(&centroids[0]);
+                let mut best_dist = update.cost(distance, v, &centroids[0]);
                 for (j, centroid) in centroids.iter().enumerate().skip(1) {
-                    let dist = v.distance2(centroid);
+                    let dist = update.cost(distance, v, centroid);
                     if dist < best_dist {
                         best = j;
                         best_dist = dist;
                     }
                 }
-                best
+                (best, best_dist)
             })
-            .collect();
+            .unzip();
+        let distortion = costs.iter().sum::<f32>() / n as f32;
+        report.distortions.push(distortion);
 
         // Check if any assignment changed.
         let changed = new_assignments
@@ -94,18 +238,84 @@ pub fn lbg_quantize(
         // Recompute centroids for each cluster.
         for j in 0..k {
             if !clusters[j].is_empty() {
-                centroids[j] = mean_vector(&clusters[j]);
+                centroids[j] = update.update(&clusters[j]);
             } else {
                 // Reinitialize an empty cluster with a random data point.
-                centroids[j] = data.choose(&mut rng).unwrap().clone();
+                centroids[j] = update.seed_centroid(data.choose(&mut rng).unwrap());
+                report.empty_clusters += 1;
             }
         }
 
         if !changed {
+            report.converged = true;
+        }
+        if !on_iteration(iteration, distortion) || report.converged {
             break;
         }
     }
-    centroids
+    (centroids, report)
+}
+
+/// Returns the dimension of the training data.
+///
+/// # Panics
+/// Panics with a custom error if the training data is empty.
+pub(crate) fn data_dim(training_data: &[Vector<f32>]) -> usize {
+    match training_data.first() {
+        Some(v) => v.len(),
+        None => panic!("{}", VqError::EmptyInput),
+    }
+}
+
+/// Splits `dim` dimensions into `m` contiguous subspaces whose sizes differ by at most one.
+///
+/// # Returns
+/// The `m + 1` subspace boundaries, from 0 to `dim`. Subspace `i` covers the dimensions
+/// `boundaries[i]..boundaries[i + 1]`, and the first `dim % m` subspaces get one extra dimension.
+///
+/// # Panics
+/// Panics with a custom error if `m` is 0 or `dim` is less than `m`.
+pub(crate) fn balanced_boundaries(dim: usize, m: usize) -> Vec<usize> {
+    if m == 0 {
+        panic!(
+            "{}",
+            VqError::InvalidParameter("m must be greater than 0".to_string())
+        );
+    }
+    if dim < m {
+        panic!(
+            "{}",
+            VqError::InvalidParameter("Data dimension must be at least m".to_string())
+        );
+    }
+    let (base, extra) = (dim / m, dim % m);
+    let mut boundaries = Vec::with_capacity(m + 1);
+    boundaries.push(0);
+    for i in 0..m {
+        boundaries.push(boundaries[i] + base + usize::from(i < extra));
+    }
+    boundaries
+}
+
+/// Checks that subspace boundaries start at 0, end at `dim`, and are strictly increasing.
+///
+/// # Panics
+/// Panics with a custom error if the boundaries do not describe at least one non-empty subspace
+/// covering all `dim` dimensions.
+pub(crate) fn check_boundaries(boundaries: &[usize], dim: usize) {
+    if boundaries.len() < 2
+        || boundaries[0] != 0
+        || boundaries[boundaries.len() - 1] != dim
+        || boundaries.windows(2).any(|w| w[0] >= w[1])
+    {
+        panic!(
+            "{}",
+            VqError::InvalidParameter(format!(
+                "Subspace boundaries must increase strictly from 0 to the dimension {}, got {:?}",
+                dim, boundaries
+            ))
+        );
+    }
 }
 
 #[cfg(test)]
@@ -126,28 +336,75 @@ mod tests {
     #[test]
     fn lbg_quantize_basic_functionality() {
         let data = get_data();
-        let centroids = lbg_quantize(&data, 2, 10, 42);
+        let centroids = lbg_quantize(
+            &data,
+            2,
+            10,
+            &Distance::SquaredEuclidean,
+            42,
+            None,
+            &|_, _| true,
+        )
+        .0;
         assert_eq!(centroids.len(), 2);
     }
 
+    #[test]
+    fn balanced_boundaries_spread_remainder() {
+        assert_eq!(balanced_boundaries(10, 3), vec![0, 4, 7, 10]);
+        assert_eq!(balanced_boundaries(8, 4), vec![0, 2, 4, 6, 8]);
+        check_boundaries(&balanced_boundaries(100, 16), 100);
+    }
+
+    #[test]
+    #[should_panic(expected = "Subspace boundaries must increase strictly")]
+    fn check_boundaries_rejects_empty_subspace() {
+        check_boundaries(&[0, 3, 3, 6], 6);
+    }
+
     #[test]
     #[should_panic(expected = "k must be greater than 0")]
     fn lbg_quantize_k_zero() {
         let data = vec![Vector::new(vec![1.0, 2.0]), Vector::new(vec![2.0, 3.0])];
-        lbg_quantize(&data, 0, 10, 42);
+        lbg_quantize(
+            &data,
+            0,
+            10,
+            &Distance::SquaredEuclidean,
+            42,
+            None,
+            &|_, _| true,
+        );
     }
 
     #[test]
     #[should_panic(expected = "Not enough data points for k clusters")]
     fn lbg_quantize_not_enough_data_points() {
         let data = vec![Vector::new(vec![1.0, 2.0])];
-        lbg_quantize(&data, 2, 10, 42);
+        lbg_quantize(
+            &data,
+            2,
+            10,
+            &Distance::SquaredEuclidean,
+            42,
+            None,
+            &|_, _| true,
+        );
     }
 
     #[test]
     fn lbg_quantize_single_data_point() {
         let data = vec![Vector::new(vec![1.0, 2.0])];
-        let centroids = lbg_quantize(&data, 1, 10, 42);
+        let centroids = lbg_quantize(
+            &data,
+            1,
+            10,
+            &Distance::SquaredEuclidean,
+            42,
+            None,
+            &|_, _| true,
+        )
+        .0;
         assert_eq!(centroids.len(), 1);
         assert_eq!(centroids[0], Vector::new(vec![1.0, 2.0]));
     }
@@ -155,7 +412,89 @@ mod tests {
     #[test]
     fn lbg_quantize_multiple_iterations() {
         let data = get_data();
-        let centroids = lbg_quantize(&data, 2, 100, 42);
+        let centroids = lbg_quantize(
+            &data,
+            2,
+            100,
+            &Distance::SquaredEuclidean,
+            42,
+            None,
+            &|_, _| true,
+        )
+        .0;
         assert_eq!(centroids.len(), 2);
     }
+
+    #[test]
+    fn lbg_quantize_cosine_centroids_are_normalized() {
+        let data = get_data();
+        let centroids = lbg_quantize(
+            &data,
+            2,
+            10,
+            &Distance::CosineDistance,
+            42,
+            None,
+            &|_, _| true,
+        )
+        .0;
+        for c in &centroids {
+            assert!((c.norm() - 1.0).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn lbg_quantize_manhattan_uses_median() {
+        let data = vec![
+            Vector::new(vec![0.0, 0.0]),
+            Vector::new(vec![1.0, 1.0]),
+            Vector::new(vec![100.0, 100.0]),
+        ];
+        let centroids = lbg_quantize(&data, 1, 10, &Distance::Manhattan, 42, None, &|_, _| true).0;
+        assert_eq!(centroids[0], Vector::new(vec![1.0, 1.0]));
+    }
+
+    #[test]
+    #[should_panic(expected = "Unsupported distance metric Chebyshev")]
+    fn lbg_quantize_unsupported_distance() {
+        let data = get_data();
+        lbg_quantize(&data, 2, 10, &Distance::Chebyshev, 42, None, &|_, _| true);
+    }
+
+    #[test]
+    fn lbg_quantize_reports_iterations() {
+        let data = get_data();
+        let (centroids, report) = lbg_quantize(
+            &data,
+            2,
+            100,
+            &Distance::SquaredEuclidean,
+            42,
+            None,
+            &|_, _| true,
+        );
+        assert_eq!(centroids.len(), 2);
+        assert!(report.converged);
+        assert!(report.iterations() >= 1 && report.iterations() <= 100);
+        assert!(report.final_distortion().unwrap().is_finite());
+    }
+
+    #[test]
+    fn lbg_quantize_from_initial_centroids() {
+        let data = get_data();
+        let initial = vec![Vector::new(vec![1.5, 2.5]), Vector::new(vec![3.5, 4.5])];
+        let (centroids, report) = lbg_quantize(
+            &data,
+            2,
+            10,
+            &Distance::SquaredEuclidean,
+            42,
+            Some(&initial),
+            &|_, _| true,
+        );
+        // The initial centroids are already optimal, so the assignments settle immediately.
+        assert!(report.converged);
+        assert!(report.iterations() <= 2);
+        assert_eq!(centroids, initial);
+    }
 }
@@ -145,6 +145,27 @@ impl Real for u8 {
     }
 }
 
+impl Real for i8 {
+    fn zero() -> Self {
+        0
+    }
+    fn one() -> Self {
+        1
+    }
+    fn sqrt(self) -> Self {
+        (self as f32).sqrt() as i8
+    }
+    fn abs(self) -> Self {
+        self.saturating_abs()
+    }
+    fn powf(self, n: Self) -> Self {
+        f32::from(self).powf(f32::from(n)) as i8
+    }
+    fn from_f64(x: f64) -> Self {
+        x as i8
+    }
+}
+
 /// A vector of real numbers.
 #[derive(Debug, Clone, PartialEq)]
 pub struct Vector<T: Real> {
@@ -0,0 +1,55 @@
+#[path = "utils.rs"]
+mod utils;
+
+use utils::{generate_test_data, mean_squared_error, seeded_rng};
+use vq::aq::AdditiveQuantizer;
+use vq::distances::Distance;
+use vq::rvq::ResidualQuantizer;
+
+#[test]
+fn test_aq_dimension_and_codes() {
+    let mut rng = seeded_rng();
+    let training_data = generate_test_data(&mut rng, 300, 10);
+    let aq = AdditiveQuantizer::fit(&training_data, 3, 4, 20, 2, 4, 42);
+    for vector in training_data.iter().take(20) {
+        let codes = aq.encode(vector);
+        assert_eq!(codes.len(), 3);
+        assert_eq!(codes.bits(), 2);
+        assert!(codes.iter().all(|c| c < 4));
+        assert_eq!(aq.decode(&codes).len(), vector.len());
+        assert_eq!(aq.quantize(vector).len(), vector.len());
+    }
+}
+
+#[test]
+fn test_aq_not_worse_than_rvq() {
+    let mut rng = seeded_rng();
+    let training_data = generate_test_data(&mut rng, 300, 8);
+    let (m, k, max_iters, seed) = (3, 8, 20, 42);
+    let rvq = ResidualQuantizer::fit(
+        &training_data,
+        m,
+        k,
+        max_iters,
+        0.0,
+        Distance::SquaredEuclidean,
+        seed,
+    );
+    let aq = AdditiveQuantizer::fit(&training_data, m, k, max_iters, 3, 8, seed);
+    let rvq_error = mean_squared_error(&training_data, |v| rvq.quantize(v));
+    let aq_error = mean_squared_error(&training_data, |v| aq.quantize(v));
+    assert!(
+        aq_error <= rvq_error * 1.001,
+        "AQ error {} should not exceed RVQ error {}",
+        aq_error,
+        rvq_error
+    );
+}
+
+#[test]
+#[should_panic(expected = "beam_width must be greater than 0")]
+fn test_aq_zero_beam_width() {
+    let mut rng = seeded_rng();
+    let training_data = generate_test_data(&mut rng, 50, 4);
+    AdditiveQuantizer::fit(&training_data, 2, 4, 10, 1, 0, 42);
+}
@@ -0,0 +1,99 @@
+#[path = "utils.rs"]
+mod utils;
+
+use utils::{generate_test_data, seeded_rng};
+use vq::fp8::{Fp8Code, Fp8Format, Fp8Quantizer, Fp8Scaling};
+use vq::vector::Vector;
+
+#[test]
+fn test_fp8_known_values() {
+    assert_eq!(Fp8Format::E4M3.max_value(), 448.0);
+    assert_eq!(Fp8Format::E5M2.max_value(), 57344.0);
+    assert_eq!(Fp8Format::E4M3.encode_value(1.0), 0x38);
+    assert_eq!(Fp8Format::E4M3.encode_value(-2.0), 0xC0);
+    assert_eq!(Fp8Format::E5M2.encode_value(1.0), 0x3C);
+    // Smallest subnormals: 2^-9 and 2^-16.
+    assert_eq!(Fp8Format::E4M3.decode_value(0x01), 2.0f32.powi(-9));
+    assert_eq!(Fp8Format::E5M2.decode_value(0x01), 2.0f32.powi(-16));
+    assert_eq!(Fp8Format::E4M3.encode_value(2.0f32.powi(-11)), 0x00);
+    // 1.0625 lies halfway between 1.0 and 1.125 and rounds to the even mantissa.
+    assert_eq!(Fp8Format::E4M3.encode_value(1.0625), 0x38);
+    assert_eq!(Fp8Format::E4M3.encode_value(1.1875), 0x3A);
+}
+
+#[test]
+fn test_fp8_round_trip_every_code() {
+    for format in [Fp8Format::E4M3, Fp8Format::E5M2] {
+        for code in 0..=255u8 {
+            let value = format.decode_value(code);
+            if value.is_nan() {
+                assert_eq!(format.encode_value(value) & 0x7F, 0x7F);
+            } else if value.is_infinite() {
+                assert_eq!(
+                    format.decode_value(format.encode_value(value)).abs(),
+                    format.max_value()
+                );
+            } else {
+                assert_eq!(format.encode_value(value), code, "{:?} {:#x}", format, code);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_fp8_saturation_and_nan() {
+    for format in [Fp8Format::E4M3, Fp8Format::E5M2] {
+        let max = format.max_value();
+        for x in [max * 1.5, 1e30, f32::INFINITY] {
+            assert_eq!(format.decode_value(format.encode_value(x)), max);
+            assert_eq!(format.decode_value(format.encode_value(-x)), -max);
+        }
+        assert!(format.decode_value(format.encode_value(f32::NAN)).is_nan());
+    }
+}
+
+#[test]
+fn test_fp8_quantizer_per_tensor() {
+    let mut rng = seeded_rng();
+    let data = generate_test_data(&mut rng, 200, 16);
+    let quantizer = Fp8Quantizer::fit(&data, Fp8Format::E4M3, Fp8Scaling::PerTensor);
+    let max_abs = data
+        .iter()
+        .flat_map(|v| v.data.iter())
+        .fold(0.0f32, |m, &x| m.max(x.abs()));
+    assert!((quantizer.scale() * 448.0 - max_abs).abs() < 1e-2);
+
+    for vector in data.iter() {
+        let code = quantizer.encode(vector);
+        assert_eq!(code.codes.len(), 16);
+        let decoded = quantizer.decode(&code);
+        for (&x, &y) in vector.data.iter().zip(decoded.data.iter()) {
+            // Three mantissa bits give a relative error of at most 2^-4 for normal values.
+            let subnormal_step = quantizer.scale() * 2.0f32.powi(-9);
+            assert!((x - y).abs() <= x.abs() / 16.0 + subnormal_step);
+        }
+    }
+}
+
+#[test]
+fn test_fp8_quantizer_per_vector_scale() {
+    let data = vec![Vector::new(vec![1000.0, -1.0]), Vector::new(vec![1.0, 2.0])];
+    let quantizer = Fp8Quantizer::fit(&data, Fp8Format::E5M2, Fp8Scaling::PerVector);
+    let small = Vector::new(vec![0.001, -0.002]);
+    let code = quantizer.encode(&small);
+    assert!((code.scale * 57344.0 - 0.002).abs() < 1e-9);
+    let decoded = quantizer.decode(&code);
+    assert!((decoded.data[1] + 0.002).abs() < 1e-9);
+    assert!((decoded.data[0] - 0.001).abs() <= 0.001 / 8.0);
+}
+
+#[test]
+#[should_panic(expected = "Dimension mismatch")]
+fn test_fp8_decode_wrong_length() {
+    let data = vec![Vector::new(vec![1.0, 2.0])];
+    let quantizer = Fp8Quantizer::fit(&data, Fp8Format::E4M3, Fp8Scaling::PerTensor);
+    quantizer.decode(&Fp8Code {
+        codes: vec![0x38],
+        scale: 1.0,
+    });
+}
@@ -0,0 +1,97 @@
+#[path = "utils.rs"]
+mod utils;
+
+use rand::Rng;
+use utils::seeded_rng;
+use vq::lattice::{Lattice, LatticeQuantizer};
+use vq::packing::PackedCodes;
+use vq::vector::Vector;
+
+fn squared_distance(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b.iter()).map(|(x, y)| (x - y) * (x - y)).sum()
+}
+
+#[test]
+fn test_lattice_shell_sizes() {
+    // Kissing numbers: Z⁴ has 8 neighbours, D⁴ has 24, and E₈ has 240.
+    assert_eq!(
+        LatticeQuantizer::new(Lattice::Z(4), 4, 1.0, 1).codebook_size(),
+        9
+    );
+    assert_eq!(
+        LatticeQuantizer::new(Lattice::D(4), 4, 1.0, 2).codebook_size(),
+        25
+    );
+    let e8 = LatticeQuantizer::new(Lattice::E8, 8, 1.0, 2);
+    assert_eq!(e8.codebook_size(), 241);
+    assert_eq!(e8.bits_per_block(), 8);
+    // The second shell of E₈ holds 2160 points.
+    assert_eq!(
+        LatticeQuantizer::new(Lattice::E8, 8, 1.0, 4).codebook_size(),
+        2401
+    );
+}
+
+#[test]
+fn test_lattice_nearest_point_matches_enumeration() {
+    let mut rng = seeded_rng();
+    for lattice in [Lattice::Z(3), Lattice::D(4), Lattice::E8] {
+        let dim = lattice.block_dim();
+        // With a large enough truncation, encoding finds the exact nearest enumerated point.
+        let quantizer = LatticeQuantizer::new(lattice, dim, 1.0, 6);
+        let all: Vec<Vector<f32>> = (0..quantizer.codebook_size())
+            .map(|c| {
+                quantizer.decode(&PackedCodes::from_codes(
+                    quantizer.bits_per_block(),
+                    [c as u16],
+                ))
+            })
+            .collect();
+        for _ in 0..50 {
+            let v = Vector::new((0..dim).map(|_| rng.random_range(-1.0..1.0)).collect());
+            let nearest = quantizer.nearest_point(&v);
+            let best = all
+                .iter()
+                .map(|p| squared_distance(&v.data, &p.data))
+                .fold(f32::INFINITY, f32::min);
+            assert!(
+                (squared_distance(&v.data, &nearest.data) - best).abs() < 1e-5,
+                "{:?} did not find the nearest point",
+                lattice
+            );
+            assert_eq!(quantizer.quantize(&v).data, nearest.data);
+        }
+    }
+}
+
+#[test]
+fn test_lattice_scale_and_truncation() {
+    let quantizer = LatticeQuantizer::new(Lattice::E8, 16, 0.25, 2);
+    let point = Vector::new(vec![
+        0.125, 0.125, 0.125, 0.125, 0.125, 0.125, 0.125, 0.125, 0.25, 0.25, 0.0, 0.0, 0.0, 0.0,
+        0.0, 0.0,
+    ]);
+    // Scaled lattice points are reproduced exactly.
+    assert_eq!(quantizer.quantize(&point).data, point.data);
+
+    // A vector far outside the truncation maps to an enumerated point.
+    let far = Vector::new(vec![10.0; 16]);
+    let codes = quantizer.encode(&far);
+    assert_eq!(codes.len(), 2);
+    assert!(codes
+        .iter()
+        .all(|c| (c as usize) < quantizer.codebook_size()));
+}
+
+#[test]
+#[should_panic(expected = "block dimension")]
+fn test_lattice_e8_requires_multiple_of_eight() {
+    LatticeQuantizer::new(Lattice::E8, 12, 1.0, 2);
+}
+
+#[test]
+#[should_panic(expected = "more than 65536 points")]
+fn test_lattice_rejects_large_truncation_early() {
+    // Far more than 2¹⁶ points: enumeration must stop once the limit is passed.
+    LatticeQuantizer::new(Lattice::Z(64), 64, 1.0, 100);
+}
@@ -0,0 +1,96 @@
+#[path = "utils.rs"]
+mod utils;
+
+use rand_distr::{Distribution, Normal};
+use utils::{generate_test_data, seeded_rng};
+use vq::lloyd_max::LloydMaxQuantizer;
+use vq::sq::ScalarQuantizer;
+use vq::vector::Vector;
+
+fn gaussian_data(n: usize, dim: usize) -> Vec<Vector<f32>> {
+    let mut rng = seeded_rng();
+    let normal = Normal::new(0.0f32, 1.0).unwrap();
+    (0..n)
+        .map(|_| Vector::new((0..dim).map(|_| normal.sample(&mut rng)).collect()))
+        .collect()
+}
+
+#[test]
+fn test_lloyd_max_boundaries_are_midpoints() {
+    let data = gaussian_data(500, 4);
+    let quantizer = LloydMaxQuantizer::fit(&data, 8, 100, true);
+    for d in 0..4 {
+        let points = quantizer.points(d);
+        let boundaries = quantizer.boundaries(d);
+        assert_eq!(points.len(), 8);
+        assert_eq!(boundaries.len(), 7);
+        for (j, &b) in boundaries.iter().enumerate() {
+            assert!(points[j] < b && b < points[j + 1]);
+            assert!((b - (points[j] + points[j + 1]) / 2.0).abs() < 1e-6);
+        }
+    }
+}
+
+#[test]
+fn test_lloyd_max_beats_uniform_on_gaussian() {
+    let data = gaussian_data(2000, 8);
+    let levels = 8;
+    let lloyd_max = LloydMaxQuantizer::fit(&data, levels, 100, false);
+    let (min, max) = data
+        .iter()
+        .flat_map(|v| v.data.iter())
+        .fold((f32::INFINITY, f32::NEG_INFINITY), |(lo, hi), &x| {
+            (lo.min(x), hi.max(x))
+        });
+    let uniform = ScalarQuantizer::fit(min, max, levels);
+
+    let (mut lloyd_max_error, mut uniform_error) = (0.0, 0.0);
+    for v in &data {
+        let decoded = lloyd_max.decode(&lloyd_max.encode(v));
+        let codes = uniform.quantize(v);
+        for ((&x, &y), &c) in v
+            .data
+            .iter()
+            .zip(decoded.data.iter())
+            .zip(codes.data.iter())
+        {
+            lloyd_max_error += (x - y) * (x - y);
+            let u = uniform.min + c as f32 * uniform.step;
+            uniform_error += (x - u) * (x - u);
+        }
+    }
+    assert!(
+        lloyd_max_error < uniform_error,
+        "Lloyd-Max error {} should be below uniform error {}",
+        lloyd_max_error,
+        uniform_error
+    );
+}
+
+#[test]
+fn test_lloyd_max_large_vector_codes_in_range() {
+    let mut rng = seeded_rng();
+    let data = generate_test_data(&mut rng, 20, 2048);
+    let quantizer = LloydMaxQuantizer::fit(&data, 256, 10, false);
+    let codes = quantizer.encode(&data[0]);
+    assert_eq!(codes.len(), 2048);
+    assert_eq!(codes.bits(), 8);
+    let decoded = quantizer.decode(&codes);
+    // The packed codes round-trip to the reconstruction points of the quantized levels.
+    for (d, (&level, &y)) in quantizer
+        .quantize(&data[0])
+        .data
+        .iter()
+        .zip(decoded.data.iter())
+        .enumerate()
+    {
+        assert_eq!(quantizer.points(d)[level as usize], y);
+    }
+}
+
+#[test]
+#[should_panic(expected = "levels must be no more than 256")]
+fn test_lloyd_max_too_many_levels() {
+    let data = gaussian_data(10, 2);
+    LloydMaxQuantizer::fit(&data, 300, 10, true);
+}
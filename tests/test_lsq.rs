@@ -0,0 +1,60 @@
+#[path = "utils.rs"]
+mod utils;
+
+use utils::{generate_test_data, mean_squared_error, seeded_rng};
+use vq::distances::Distance;
+use vq::lsq::LocalSearchQuantizer;
+use vq::rvq::ResidualQuantizer;
+use vq::vector::Vector;
+
+#[test]
+fn test_lsq_encode_decode() {
+    let mut rng = seeded_rng();
+    let training_data = generate_test_data(&mut rng, 300, 10);
+    let lsq = LocalSearchQuantizer::fit(&training_data, 3, 4, 20, 2, 4, 42);
+    for vector in training_data.iter().take(20) {
+        let codes = lsq.encode(vector);
+        assert_eq!(codes.len(), 3);
+        assert_eq!(
+            codes,
+            lsq.encode(vector),
+            "Encoding should be deterministic"
+        );
+        assert_eq!(lsq.decode(&codes).len(), vector.len());
+        assert_eq!(lsq.quantize(vector).len(), vector.len());
+    }
+}
+
+#[test]
+fn test_lsq_improves_on_rvq() {
+    let mut rng = seeded_rng();
+    let training_data = generate_test_data(&mut rng, 300, 8);
+    let (m, k, max_iters, seed) = (3, 8, 20, 42);
+    let rvq = ResidualQuantizer::fit(
+        &training_data,
+        m,
+        k,
+        max_iters,
+        0.0,
+        Distance::SquaredEuclidean,
+        seed,
+    );
+    let lsq = LocalSearchQuantizer::fit(&training_data, m, k, max_iters, 5, 8, seed);
+    let rvq_error = mean_squared_error(&training_data, |v| rvq.quantize(v));
+    let lsq_error = mean_squared_error(&training_data, |v| lsq.quantize(v));
+    assert!(
+        lsq_error < rvq_error,
+        "LSQ error {} should be below RVQ error {}",
+        lsq_error,
+        rvq_error
+    );
+}
+
+#[test]
+#[should_panic(expected = "Dimension mismatch")]
+fn test_lsq_dimension_mismatch() {
+    let mut rng = seeded_rng();
+    let training_data = generate_test_data(&mut rng, 50, 4);
+    let lsq = LocalSearchQuantizer::fit(&training_data, 2, 4, 10, 1, 1, 42);
+    lsq.encode(&Vector::new(vec![1.0, 2.0]));
+}
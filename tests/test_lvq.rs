@@ -0,0 +1,95 @@
+#[path = "utils.rs"]
+mod utils;
+
+use utils::{generate_test_data, seeded_rng};
+use vq::lvq::LocallyAdaptiveQuantizer;
+use vq::sq::ScalarQuantizer;
+use vq::vector::Vector;
+
+fn squared_error(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b.iter()).map(|(x, y)| (x - y) * (x - y)).sum()
+}
+
+#[test]
+fn test_lvq_code_sizes() {
+    let mut rng = seeded_rng();
+    let data = generate_test_data(&mut rng, 20, 9);
+    let lvq = LocallyAdaptiveQuantizer::fit(&data, 4, Some(8));
+    let code = lvq.encode(&data[0]);
+    assert_eq!(code.codes.len(), 9);
+    assert_eq!(
+        code.codes.to_bytes().len(),
+        5,
+        "Nine 4-bit codes need five bytes"
+    );
+    assert_eq!(code.residual.as_ref().map(|r| r.bits()), Some(8));
+    assert_eq!(lvq.decode(&code).len(), 9);
+}
+
+#[test]
+fn test_lvq_error_within_step() {
+    let mut rng = seeded_rng();
+    let data = generate_test_data(&mut rng, 100, 32);
+    for (bits, residual_bits) in [(4, None), (8, None), (4, Some(4)), (8, Some(8))] {
+        let lvq = LocallyAdaptiveQuantizer::fit(&data, bits, residual_bits);
+        for v in &data {
+            let code = lvq.encode(v);
+            let decoded = lvq.decode(&code);
+            let bound = match residual_bits {
+                Some(r) => code.scale / ((1u32 << r) - 1) as f32 / 2.0,
+                None => code.scale / 2.0,
+            };
+            for (x, y) in v.data.iter().zip(decoded.data.iter()) {
+                assert!(
+                    (x - y).abs() <= bound * 1.001 + 1e-3,
+                    "Error {} exceeds half a step {}",
+                    (x - y).abs(),
+                    bound
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn test_lvq_adapts_to_vector_norms() {
+    let mut rng = seeded_rng();
+    // Vectors whose norms span three orders of magnitude.
+    let data: Vec<Vector<f32>> = generate_test_data(&mut rng, 100, 16)
+        .into_iter()
+        .enumerate()
+        .map(|(i, v)| {
+            let factor = 10f32.powi((i % 4) as i32 - 3);
+            Vector::new(v.data.iter().map(|x| x * factor).collect())
+        })
+        .collect();
+    let sq = ScalarQuantizer::fit(-1000.0, 1000.0, 256);
+    let lvq = LocallyAdaptiveQuantizer::fit(&data, 8, None);
+
+    let (mut sq_error, mut lvq_error) = (0.0, 0.0);
+    for v in &data {
+        let sq_decoded: Vec<f32> = sq
+            .quantize(v)
+            .data
+            .iter()
+            .map(|&l| sq.min + l as f32 * sq.step)
+            .collect();
+        sq_error += squared_error(&v.data, &sq_decoded) / v.data.iter().map(|x| x * x).sum::<f32>();
+        let lvq_decoded = lvq.decode(&lvq.encode(v));
+        lvq_error +=
+            squared_error(&v.data, &lvq_decoded.data) / v.data.iter().map(|x| x * x).sum::<f32>();
+    }
+    assert!(
+        lvq_error < sq_error,
+        "LVQ relative error {} should be below SQ relative error {}",
+        lvq_error,
+        sq_error
+    );
+}
+
+#[test]
+#[should_panic(expected = "bits must be 4 or 8")]
+fn test_lvq_invalid_bits() {
+    let data = vec![Vector::new(vec![1.0, 2.0])];
+    LocallyAdaptiveQuantizer::fit(&data, 6, None);
+}
@@ -2,9 +2,27 @@
 mod utils;
 
 use half::f16;
-use utils::{generate_test_data, seeded_rng};
+use rand_distr::{Distribution, Normal};
+use utils::{generate_test_data, mean_squared_error, seeded_rng};
 use vq::distances::Distance;
 use vq::opq::OptimizedProductQuantizer;
+use vq::pq::ProductQuantizer;
+use vq::vector::Vector;
+
+/// Gaussian data whose first half of the dimensions has a much larger variance than the second.
+fn unbalanced_data(n: usize, dim: usize) -> Vec<Vector<f32>> {
+    let mut rng = seeded_rng();
+    let normal = Normal::new(0.0f32, 1.0).unwrap();
+    (0..n)
+        .map(|_| {
+            Vector::new(
+                (0..dim)
+                    .map(|d| normal.sample(&mut rng) * if d < dim / 2 { 10.0 } else { 0.1 })
+                    .collect(),
+            )
+        })
+        .collect()
+}
 
 #[test]
 fn test_opq_dimension() {
@@ -78,3 +96,269 @@ fn test_opq_reconstruction_error() {
         // );
     }
 }
+
+#[test]
+fn test_opq_fit_with_report() {
+    let mut rng = seeded_rng();
+    let training_data = generate_test_data(&mut rng, 500, 10);
+    let opq_iters = 3;
+    let (_, report) = OptimizedProductQuantizer::fit_with_report(
+        &training_data,
+        2,
+        4,
+        20,
+        opq_iters,
+        Distance::SquaredEuclidean,
+        42,
+    );
+    assert_eq!(report.rounds.len(), opq_iters);
+    for round in &report.rounds {
+        assert_eq!(round.clusterings.len(), 2);
+        assert!(round.distortion.is_finite());
+    }
+}
+
+#[test]
+fn test_opq_cancelled_after_first_round() {
+    use vq::exceptions::VqError;
+    use vq::progress::{ProgressEvent, TrainingControl};
+
+    let mut rng = seeded_rng();
+    let training_data = generate_test_data(&mut rng, 200, 10);
+    let observer = |event: &ProgressEvent| match event {
+        ProgressEvent::Round { .. } => TrainingControl::Cancel,
+        ProgressEvent::Iteration { round, .. } => {
+            assert_eq!(*round, 0, "No iteration should run after cancellation");
+            TrainingControl::Continue
+        }
+    };
+    let result = OptimizedProductQuantizer::fit_with_observer(
+        &training_data,
+        2,
+        4,
+        20,
+        5,
+        Distance::SquaredEuclidean,
+        42,
+        &observer,
+    );
+    assert!(matches!(result, Err(VqError::Cancelled)));
+}
+
+#[test]
+fn test_opq_warm_start() {
+    let mut rng = seeded_rng();
+    let training_data = generate_test_data(&mut rng, 300, 10);
+    let opq =
+        OptimizedProductQuantizer::fit(&training_data, 2, 4, 20, 3, Distance::SquaredEuclidean, 42);
+    // Without OPQ iterations, the given model is returned unchanged.
+    let unchanged = OptimizedProductQuantizer::warm_start(
+        &training_data,
+        opq.rotation(),
+        opq.codebooks(),
+        20,
+        0,
+        Distance::SquaredEuclidean,
+        42,
+    );
+    assert_eq!(unchanged.rotation(), opq.rotation());
+    assert_eq!(unchanged.codebooks(), opq.codebooks());
+
+    let new_data = generate_test_data(&mut rng, 300, 10);
+    let refined = opq.refine(&new_data, 5, 1, 42);
+    assert_eq!(refined.codebooks().len(), 2);
+    for vector in new_data.iter() {
+        assert_eq!(refined.quantize(vector).len(), vector.len());
+    }
+}
+
+#[test]
+fn test_opq_encode_decode() {
+    let mut rng = seeded_rng();
+    let training_data = generate_test_data(&mut rng, 500, 8);
+    let opq =
+        OptimizedProductQuantizer::fit(&training_data, 2, 8, 20, 3, Distance::SquaredEuclidean, 42);
+    for vector in training_data.iter().take(20) {
+        let codes = opq.encode(vector);
+        assert_eq!(codes.bits(), 3);
+        assert_eq!(codes.len(), 2);
+        // Rotating the decoded vector gives back the concatenated codewords of `quantize`.
+        let decoded = opq.decode(&codes);
+        let x = nalgebra::DMatrix::from_column_slice(8, 1, &decoded.data);
+        let rotated = opq.rotation() * x;
+        let quantized = opq.quantize(vector);
+        for (&r, &q) in rotated.iter().zip(quantized.data.iter()) {
+            assert!((r - q.to_f32()).abs() <= 1e-3 * r.abs().max(1.0));
+        }
+    }
+}
+
+#[test]
+fn test_opq_uneven_subspaces() {
+    let mut rng = seeded_rng();
+    let training_data = generate_test_data(&mut rng, 300, 10);
+    let opq =
+        OptimizedProductQuantizer::fit(&training_data, 3, 4, 20, 2, Distance::SquaredEuclidean, 42);
+    assert_eq!(opq.boundaries(), &[0, 4, 7, 10]);
+    for vector in training_data.iter().take(10) {
+        assert_eq!(opq.quantize(vector).len(), 10);
+        assert_eq!(opq.decode(&opq.encode(vector)).len(), 10);
+    }
+
+    let explicit = OptimizedProductQuantizer::fit_with_boundaries(
+        &training_data,
+        &[0, 2, 10],
+        4,
+        20,
+        2,
+        Distance::SquaredEuclidean,
+        42,
+    );
+    let sizes: Vec<usize> = explicit.codebooks().iter().map(|c| c[0].len()).collect();
+    assert_eq!(sizes, vec![2, 8]);
+    let refined = explicit.refine(&training_data, 5, 1, 7);
+    assert_eq!(refined.boundaries(), explicit.boundaries());
+}
+
+#[test]
+fn test_opq_parametric_balances_variance() {
+    let training_data = unbalanced_data(1000, 8);
+    let opq = OptimizedProductQuantizer::fit_parametric(
+        &training_data,
+        2,
+        16,
+        30,
+        Distance::SquaredEuclidean,
+        42,
+    );
+
+    // The rotation is orthogonal.
+    let identity = opq.rotation() * opq.rotation().transpose();
+    for i in 0..8 {
+        for j in 0..8 {
+            let expected = if i == j { 1.0 } else { 0.0 };
+            assert!((identity[(i, j)] - expected).abs() < 1e-4);
+        }
+    }
+
+    // Each subspace receives about half of the variance.
+    let mut variances = [0.0f32; 2];
+    for v in training_data.iter() {
+        let x = nalgebra::DMatrix::from_column_slice(8, 1, &v.data);
+        let y = opq.rotation() * x;
+        for (d, &value) in y.iter().enumerate() {
+            variances[d / 4] += value * value;
+        }
+    }
+    assert!(variances[0] / variances[1] < 1.5 && variances[1] / variances[0] < 1.5);
+
+    // Balancing the variance lowers the error compared with plain PQ on the same subspaces.
+    let pq = ProductQuantizer::fit(&training_data, 2, 16, 30, Distance::SquaredEuclidean, 42);
+    let pq_error = mean_squared_error(&training_data, |v| pq.decode(&pq.encode(v)));
+    let opq_error = mean_squared_error(&training_data, |v| opq.decode(&opq.encode(v)));
+    assert!(opq_error < pq_error, "{} >= {}", opq_error, pq_error);
+}
+
+#[test]
+fn test_opq_iterations_do_not_increase_distortion() {
+    let mut rng = seeded_rng();
+    let normal = Normal::new(0.0f32, 1.0).unwrap();
+    // Correlated data: every dimension mixes the same few latent factors.
+    let mixing: Vec<f32> = (0..8 * 3).map(|_| normal.sample(&mut rng)).collect();
+    let training_data: Vec<Vector<f32>> = (0..1000)
+        .map(|_| {
+            let z: Vec<f32> = (0..3).map(|_| normal.sample(&mut rng)).collect();
+            Vector::new(
+                (0..8)
+                    .map(|d| {
+                        (0..3).map(|f| mixing[d * 3 + f] * z[f]).sum::<f32>()
+                            + 0.1 * normal.sample(&mut rng)
+                    })
+                    .collect(),
+            )
+        })
+        .collect();
+    let (opq, report) = OptimizedProductQuantizer::fit_with_report(
+        &training_data,
+        4,
+        8,
+        30,
+        5,
+        Distance::SquaredEuclidean,
+        42,
+    );
+    let first = report.rounds[0].distortion;
+    let last = report.rounds[report.rounds.len() - 1].distortion;
+    assert!(last <= first * 1.05, "{} > {}", last, first);
+
+    // The final codebooks match the final rotation.
+    let error = mean_squared_error(&training_data, |v| opq.decode(&opq.encode(v)));
+    assert!((error - last).abs() <= 1e-2 * last.max(1.0));
+}
+
+#[test]
+#[should_panic(expected = "opq_iters must be at least 1")]
+fn test_opq_zero_iterations() {
+    let mut rng = seeded_rng();
+    let training_data = generate_test_data(&mut rng, 50, 4);
+    OptimizedProductQuantizer::fit(&training_data, 2, 2, 10, 0, Distance::SquaredEuclidean, 42);
+}
+
+#[test]
+fn test_opq_projection_reduces_dimension() {
+    let training_data = unbalanced_data(800, 12);
+    let opq = OptimizedProductQuantizer::fit_with_projection(
+        &training_data,
+        6,
+        3,
+        16,
+        20,
+        3,
+        Distance::SquaredEuclidean,
+        42,
+    );
+    assert_eq!(opq.rotation().nrows(), 6);
+    assert_eq!(opq.rotation().ncols(), 12);
+    assert_eq!(opq.boundaries(), &[0, 2, 4, 6]);
+
+    // The projection has orthonormal rows.
+    let identity = opq.rotation() * opq.rotation().transpose();
+    for i in 0..6 {
+        for j in 0..6 {
+            let expected = if i == j { 1.0 } else { 0.0 };
+            assert!((identity[(i, j)] - expected).abs() < 1e-4);
+        }
+    }
+
+    let query = opq.project(&training_data[0]);
+    assert_eq!(query.len(), 6);
+    // The high-variance half of the data is kept, so reconstructions remain accurate.
+    let total: f32 = mean_squared_error(&training_data, |_| Vector::new(vec![0.0; 12]));
+    let error = mean_squared_error(&training_data, |v| {
+        let codes = opq.encode(v);
+        assert_eq!(codes.len(), 3);
+        assert_eq!(opq.quantize(v).len(), 6);
+        opq.decode(&codes)
+    });
+    assert!(error < 0.2 * total, "{} >= {}", error, 0.2 * total);
+
+    let refined = opq.refine(&training_data, 5, 1, 7);
+    assert_eq!(refined.rotation().nrows(), 6);
+}
+
+#[test]
+#[should_panic(expected = "Projected dimension 20 must be at most the data dimension 10")]
+fn test_opq_projection_too_large() {
+    let mut rng = seeded_rng();
+    let training_data = generate_test_data(&mut rng, 50, 10);
+    OptimizedProductQuantizer::fit_with_projection(
+        &training_data,
+        20,
+        2,
+        2,
+        10,
+        1,
+        Distance::SquaredEuclidean,
+        42,
+    );
+}
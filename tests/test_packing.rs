@@ -0,0 +1,104 @@
+#[path = "utils.rs"]
+mod utils;
+
+use rand::Rng;
+use utils::seeded_rng;
+use vq::packing::{bits_for, MixedPackedCodes, PackedCodes};
+
+#[test]
+fn test_bits_for() {
+    assert_eq!(bits_for(1), 1);
+    assert_eq!(bits_for(2), 1);
+    assert_eq!(bits_for(3), 2);
+    assert_eq!(bits_for(256), 8);
+    assert_eq!(bits_for(257), 9);
+    assert_eq!(bits_for(65536), 16);
+}
+
+#[test]
+fn test_round_trip_for_every_width() {
+    let mut rng = seeded_rng();
+    for bits in 1..=16u8 {
+        let max = ((1u32 << bits) - 1) as u16;
+        let codes: Vec<u16> = (0..203).map(|_| rng.random_range(0..=max)).collect();
+        let packed = PackedCodes::from_codes(bits, codes.iter().copied());
+        assert_eq!(packed.len(), codes.len());
+        assert_eq!(
+            packed.words().len(),
+            (codes.len() * bits as usize).div_ceil(64)
+        );
+        assert_eq!(
+            packed.to_vec(),
+            codes,
+            "round trip failed for {} bits",
+            bits
+        );
+
+        let bytes = packed.to_bytes();
+        assert_eq!(bytes.len(), (codes.len() * bits as usize).div_ceil(8));
+        assert_eq!(PackedCodes::from_bytes(bits, codes.len(), &bytes), packed);
+    }
+}
+
+#[test]
+fn test_set_across_word_boundary() {
+    // With 12 bits, the sixth code occupies bits 60..72 and straddles two words.
+    let mut packed = PackedCodes::from_codes(12, vec![0u16; 8]);
+    packed.set(5, 0xABC);
+    assert_eq!(packed.get(5), 0xABC);
+    assert_eq!(packed.get(4), 0);
+    assert_eq!(packed.get(6), 0);
+    packed.set(5, 0x123);
+    assert_eq!(packed.to_vec(), vec![0, 0, 0, 0, 0, 0x123, 0, 0]);
+}
+
+#[test]
+#[should_panic(expected = "does not fit in 3 bits")]
+fn test_code_too_large() {
+    PackedCodes::from_codes(3, [8]);
+}
+
+#[test]
+#[should_panic(expected = "Bit width must be between 1 and 16")]
+fn test_invalid_bit_width() {
+    PackedCodes::new(17);
+}
+
+#[test]
+fn test_mixed_widths_round_trip() {
+    let mut rng = seeded_rng();
+    let widths: Vec<u8> = (0..40).map(|_| rng.random_range(1..=16)).collect();
+    let codes: Vec<u16> = widths
+        .iter()
+        .map(|&w| rng.random_range(0..=((1u32 << w) - 1) as u16))
+        .collect();
+    let packed = MixedPackedCodes::from_codes(&widths, codes.iter().copied());
+    let total: usize = widths.iter().map(|&w| w as usize).sum();
+    assert_eq!(packed.total_bits(), total);
+    assert_eq!(packed.words().len(), total.div_ceil(64));
+    assert_eq!(packed.to_vec(), codes);
+    for (i, &code) in codes.iter().enumerate() {
+        assert_eq!(packed.get(i), code);
+    }
+    let bytes = packed.to_bytes();
+    assert_eq!(bytes.len(), total.div_ceil(8));
+    assert_eq!(MixedPackedCodes::from_bytes(&widths, &bytes), packed);
+
+    let mut updated = packed.clone();
+    updated.set(7, 1);
+    assert_eq!(updated.get(7), 1);
+    assert_eq!(updated.get(6), codes[6]);
+    assert_eq!(updated.get(8), codes[8]);
+}
+
+#[test]
+#[should_panic(expected = "Dimension mismatch")]
+fn test_mixed_widths_code_count() {
+    MixedPackedCodes::from_codes(&[3, 5], [1u16, 2, 3]);
+}
+
+#[test]
+#[should_panic(expected = "does not fit in 2 bits")]
+fn test_mixed_widths_code_too_large() {
+    MixedPackedCodes::from_codes(&[8, 2], [200u16, 4]);
+}
@@ -4,7 +4,9 @@ mod utils;
 use half::f16;
 use utils::{generate_test_data, seeded_rng};
 use vq::distances::Distance;
-use vq::pq::ProductQuantizer;
+use vq::packing::PackedCodes;
+use vq::pq::{hamming_distance, ProductQuantizer};
+use vq::vector::Vector;
 
 #[test]
 fn test_pq_on_random_vectors() {
@@ -43,3 +45,353 @@ fn test_pq_on_random_vectors() {
         );
     }
 }
+
+#[test]
+fn test_pq_with_manhattan_distance() {
+    let mut rng = seeded_rng();
+    let training_data = generate_test_data(&mut rng, 200, 8);
+    let pq = ProductQuantizer::fit(&training_data, 2, 4, 20, Distance::Manhattan, 42);
+    for vector in training_data.iter().take(10) {
+        let quantized = pq.quantize(vector);
+        assert_eq!(quantized.len(), vector.len());
+    }
+}
+
+#[test]
+#[should_panic(expected = "Unsupported distance metric")]
+fn test_pq_with_unsupported_distance() {
+    let mut rng = seeded_rng();
+    let training_data = generate_test_data(&mut rng, 100, 8);
+    ProductQuantizer::fit(&training_data, 2, 4, 20, Distance::Hamming, 42);
+}
+
+#[test]
+fn test_pq_fit_with_report() {
+    let mut rng = seeded_rng();
+    let training_data = generate_test_data(&mut rng, 500, 10);
+    let (_, report) =
+        ProductQuantizer::fit_with_report(&training_data, 2, 4, 50, Distance::Euclidean, 42);
+    assert_eq!(report.rounds.len(), 1);
+    assert_eq!(report.rounds[0].clusterings.len(), 2);
+    for clustering in report.clusterings() {
+        assert!(clustering.iterations() >= 1);
+        assert!(clustering.final_distortion().unwrap().is_finite());
+    }
+    assert!(report.final_distortion().unwrap().is_finite());
+}
+
+#[test]
+fn test_pq_refine_from_converged_codebooks() {
+    let mut rng = seeded_rng();
+    let training_data = generate_test_data(&mut rng, 500, 10);
+    let (pq, report) = ProductQuantizer::fit_with_report(
+        &training_data,
+        2,
+        4,
+        100,
+        Distance::SquaredEuclidean,
+        42,
+    );
+    assert!(report.converged());
+    // Refining converged codebooks on the same data should leave them unchanged.
+    let refined = pq.refine(&training_data, 5, 7);
+    assert_eq!(refined.codebooks(), pq.codebooks());
+}
+
+#[test]
+#[should_panic(expected = "Dimension mismatch")]
+fn test_pq_warm_start_dimension_mismatch() {
+    let mut rng = seeded_rng();
+    let training_data = generate_test_data(&mut rng, 100, 10);
+    let pq = ProductQuantizer::fit(&training_data, 2, 4, 10, Distance::Euclidean, 42);
+    let other_data = generate_test_data(&mut rng, 100, 12);
+    ProductQuantizer::warm_start(&other_data, pq.codebooks(), 10, Distance::Euclidean, 42);
+}
+
+#[test]
+fn test_pq_anisotropic_reduces_parallel_error() {
+    let mut rng = seeded_rng();
+    // Normalized vectors, as used for maximum inner product search.
+    let training_data: Vec<Vector<f32>> = generate_test_data(&mut rng, 500, 32)
+        .into_iter()
+        .map(|v| {
+            let norm = v.data.iter().map(|x| x * x).sum::<f32>().sqrt();
+            Vector::new(v.data.iter().map(|x| x / norm).collect())
+        })
+        .collect();
+    let (m, k, max_iters, seed) = (4, 16, 20, 42);
+    let plain = ProductQuantizer::fit(
+        &training_data,
+        m,
+        k,
+        max_iters,
+        Distance::SquaredEuclidean,
+        seed,
+    );
+    let anisotropic = ProductQuantizer::fit_anisotropic(&training_data, m, k, max_iters, 0.5, seed);
+
+    // Mean squared error parallel to the datapoint, i.e. (r · x)² for unit-norm x.
+    let parallel_error = |pq: &ProductQuantizer| {
+        training_data
+            .iter()
+            .map(|v| {
+                let q = pq.quantize(v);
+                let p: f32 = v
+                    .data
+                    .iter()
+                    .zip(q.data.iter())
+                    .map(|(&x, &y)| (x - f16::to_f32(y)) * x)
+                    .sum();
+                p * p
+            })
+            .sum::<f32>()
+            / training_data.len() as f32
+    };
+    let plain_error = parallel_error(&plain);
+    let anisotropic_error = parallel_error(&anisotropic);
+    assert!(
+        anisotropic_error < plain_error,
+        "Anisotropic parallel error {} should be below plain PQ error {}",
+        anisotropic_error,
+        plain_error
+    );
+}
+
+#[test]
+#[should_panic(expected = "Anisotropic threshold must be between 0 and 1")]
+fn test_pq_anisotropic_invalid_threshold() {
+    let mut rng = seeded_rng();
+    let training_data = generate_test_data(&mut rng, 20, 4);
+    ProductQuantizer::fit_anisotropic(&training_data, 2, 2, 5, 1.0, 42);
+}
+
+#[test]
+fn test_pq_polysemous_reordering() {
+    let mut rng = seeded_rng();
+    let training_data = generate_test_data(&mut rng, 1000, 8);
+    let pq = ProductQuantizer::fit(&training_data, 2, 16, 20, Distance::SquaredEuclidean, 42);
+    let polysemous = pq.polysemous(20000, 42);
+    let codes = polysemous.encode(&training_data[0]);
+    assert_eq!(codes.bits(), 4);
+    assert_eq!(hamming_distance(&codes, &codes), 0);
+
+    // Reordering leaves the reconstructions unchanged.
+    for v in training_data.iter().take(50) {
+        assert_eq!(pq.quantize(v).data, polysemous.quantize(v).data);
+        assert_eq!(
+            polysemous.decode(&polysemous.encode(v)),
+            pq.decode(&pq.encode(v))
+        );
+    }
+
+    // Codes one bit apart should point to closer centroids after the reordering.
+    let neighbour_distance = |pq: &ProductQuantizer| {
+        let mut sum = 0.0;
+        let mut count = 0;
+        for codebook in pq.codebooks() {
+            for a in 0..codebook.len() {
+                for b in 0..codebook.len() {
+                    if (a ^ b).count_ones() == 1 {
+                        sum += Distance::Euclidean.compute(&codebook[a].data, &codebook[b].data);
+                        count += 1;
+                    }
+                }
+            }
+        }
+        sum / count as f32
+    };
+    assert!(neighbour_distance(&polysemous) < neighbour_distance(&pq));
+}
+
+#[test]
+fn test_pq_adc_matches_reconstruction_distance() {
+    let mut rng = seeded_rng();
+    let training_data = generate_test_data(&mut rng, 300, 12);
+    let pq = ProductQuantizer::fit(&training_data, 3, 8, 20, Distance::SquaredEuclidean, 42);
+    let query = &training_data[0];
+    let table = pq.distance_table(query);
+    for v in training_data.iter().skip(1).take(20) {
+        let codes = pq.encode(v);
+        let expected = Distance::SquaredEuclidean.compute(&query.data, &pq.decode(&codes).data);
+        let adc = pq.adc_distance(&table, &codes);
+        assert!((adc - expected).abs() <= 1e-3 * expected.max(1.0));
+    }
+}
+
+#[test]
+fn test_pq_uneven_subspaces() {
+    let mut rng = seeded_rng();
+    let training_data = generate_test_data(&mut rng, 300, 10);
+    let pq = ProductQuantizer::fit(&training_data, 4, 8, 20, Distance::SquaredEuclidean, 42);
+    assert_eq!(pq.boundaries(), &[0, 3, 6, 8, 10]);
+    let sizes: Vec<usize> = pq.codebooks().iter().map(|c| c[0].len()).collect();
+    assert_eq!(sizes, vec![3, 3, 2, 2]);
+
+    let query = &training_data[0];
+    let table = pq.distance_table(query);
+    for v in training_data.iter().skip(1).take(20) {
+        assert_eq!(pq.quantize(v).len(), 10);
+        let codes = pq.encode(v);
+        assert_eq!(codes.len(), 4);
+        let expected = Distance::SquaredEuclidean.compute(&query.data, &pq.decode(&codes).data);
+        let adc = pq.adc_distance(&table, &codes);
+        assert!((adc - expected).abs() <= 1e-3 * expected.max(1.0));
+    }
+
+    // Refining keeps the uneven boundaries.
+    let refined = pq.refine(&training_data, 5, 7);
+    assert_eq!(refined.boundaries(), pq.boundaries());
+}
+
+#[test]
+fn test_pq_explicit_boundaries() {
+    let mut rng = seeded_rng();
+    let training_data = generate_test_data(&mut rng, 200, 7);
+    let pq = ProductQuantizer::fit_with_boundaries(
+        &training_data,
+        &[0, 1, 7],
+        4,
+        20,
+        Distance::SquaredEuclidean,
+        42,
+    );
+    let sizes: Vec<usize> = pq.codebooks().iter().map(|c| c[0].len()).collect();
+    assert_eq!(sizes, vec![1, 6]);
+    assert_eq!(pq.quantize(&training_data[0]).len(), 7);
+}
+
+#[test]
+#[should_panic(expected = "Subspace boundaries must increase strictly")]
+fn test_pq_invalid_boundaries() {
+    let mut rng = seeded_rng();
+    let training_data = generate_test_data(&mut rng, 50, 6);
+    ProductQuantizer::fit_with_boundaries(
+        &training_data,
+        &[0, 4, 5],
+        2,
+        10,
+        Distance::SquaredEuclidean,
+        42,
+    );
+}
+
+#[test]
+#[should_panic(expected = "Dimension mismatch")]
+fn test_pq_hamming_distance_length_mismatch() {
+    hamming_distance(
+        &PackedCodes::from_codes(4, [1, 2, 3]),
+        &PackedCodes::from_codes(4, [1, 2]),
+    );
+}
+
+#[test]
+#[should_panic(expected = "Cannot compare codes of 4 and 8 bits")]
+fn test_pq_hamming_distance_width_mismatch() {
+    hamming_distance(
+        &PackedCodes::from_codes(4, [1, 2]),
+        &PackedCodes::from_codes(8, [1, 2]),
+    );
+}
+
+#[test]
+#[should_panic(expected = "Polysemous codes support at most 4096 centroids")]
+fn test_pq_polysemous_rejects_large_codebooks() {
+    let training_data: Vec<Vector<f32>> = (0..4097).map(|i| Vector::new(vec![i as f32])).collect();
+    let pq = ProductQuantizer::warm_start(
+        &training_data,
+        std::slice::from_ref(&training_data),
+        0,
+        Distance::SquaredEuclidean,
+        42,
+    );
+    pq.polysemous(10, 42);
+}
+
+#[test]
+fn test_pq_explicit_codebook_sizes() {
+    let mut rng = seeded_rng();
+    let training_data = generate_test_data(&mut rng, 300, 9);
+    let pq = ProductQuantizer::fit_with_codebook_sizes(
+        &training_data,
+        &[0, 3, 6, 9],
+        &[2, 16, 5],
+        20,
+        Distance::SquaredEuclidean,
+        42,
+    );
+    let sizes: Vec<usize> = pq.codebooks().iter().map(|c| c.len()).collect();
+    assert_eq!(sizes, vec![2, 16, 5]);
+    assert_eq!(pq.code_widths(), vec![1, 4, 3]);
+
+    let query = &training_data[0];
+    let table = pq.distance_table(query);
+    assert_eq!(table.iter().map(|row| row.len()).collect::<Vec<_>>(), sizes);
+    for v in training_data.iter().skip(1).take(20) {
+        let codes = pq.encode_mixed(v);
+        assert_eq!(codes.total_bits(), 8);
+        assert_eq!(codes.to_vec(), pq.encode(v).to_vec());
+        let decoded = pq.decode_mixed(&codes);
+        assert_eq!(decoded, pq.decode(&pq.encode(v)));
+        let expected = Distance::SquaredEuclidean.compute(&query.data, &decoded.data);
+        let adc = pq.adc_distance_mixed(&table, &codes);
+        assert!((adc - expected).abs() <= 1e-3 * expected.max(1.0));
+    }
+
+    // Refining keeps the codebook sizes.
+    let refined = pq.refine(&training_data, 5, 7);
+    assert_eq!(refined.code_widths(), pq.code_widths());
+}
+
+#[test]
+fn test_pq_bit_budget_favours_high_variance() {
+    let mut rng = seeded_rng();
+    // The first half of the dimensions varies 100 times more than the second half.
+    let training_data: Vec<Vector<f32>> = generate_test_data(&mut rng, 1000, 8)
+        .into_iter()
+        .map(|v| {
+            let data = v
+                .data
+                .iter()
+                .enumerate()
+                .map(|(d, &x)| if d < 4 { x } else { x / 100.0 })
+                .collect();
+            Vector::new(data)
+        })
+        .collect();
+    let pq = ProductQuantizer::fit_with_bit_budget(
+        &training_data,
+        2,
+        10,
+        20,
+        Distance::SquaredEuclidean,
+        42,
+    );
+    let widths = pq.code_widths();
+    assert_eq!(widths.iter().map(|&w| w as usize).sum::<usize>(), 10);
+    assert!(widths[0] > widths[1], "Widths {:?}", widths);
+    assert_eq!(pq.encode_mixed(&training_data[0]).total_bits(), 10);
+}
+
+#[test]
+#[should_panic(expected = "Bit budget must be between")]
+fn test_pq_bit_budget_too_large() {
+    let mut rng = seeded_rng();
+    let training_data = generate_test_data(&mut rng, 100, 4);
+    // 100 vectors allow at most 6 bits per subspace.
+    ProductQuantizer::fit_with_bit_budget(&training_data, 2, 13, 10, Distance::Euclidean, 42);
+}
+
+#[test]
+#[should_panic(expected = "Expected 2 codebook sizes")]
+fn test_pq_codebook_sizes_mismatch() {
+    let mut rng = seeded_rng();
+    let training_data = generate_test_data(&mut rng, 50, 4);
+    ProductQuantizer::fit_with_codebook_sizes(
+        &training_data,
+        &[0, 2, 4],
+        &[4],
+        10,
+        Distance::Euclidean,
+        42,
+    );
+}
@@ -0,0 +1,71 @@
+#[path = "utils.rs"]
+mod utils;
+
+use utils::{generate_test_data, seeded_rng};
+use vq::distances::Distance;
+use vq::rabitq::RaBitQuantizer;
+use vq::vector::Vector;
+
+#[test]
+fn test_rabitq_code_layout() {
+    let mut rng = seeded_rng();
+    let training_data = generate_test_data(&mut rng, 100, 100);
+    let quantizer = RaBitQuantizer::fit(&training_data, 42);
+    let code = quantizer.encode(&training_data[0]);
+    assert_eq!(code.bits.len(), 100);
+    assert_eq!(
+        code.bits.words().len(),
+        2,
+        "100 bits should fit in two words"
+    );
+    assert!(code.factor > 0.0 && code.factor <= 1.0 + 1e-5);
+    assert_eq!(quantizer.decode(&code).len(), 100);
+}
+
+#[test]
+fn test_rabitq_estimates_within_bounds() {
+    let mut rng = seeded_rng();
+    let dim = 128;
+    let data = generate_test_data(&mut rng, 200, dim);
+    let queries = generate_test_data(&mut rng, 10, dim);
+    let quantizer = RaBitQuantizer::fit(&data, 42);
+    let codes: Vec<_> = data.iter().map(|v| quantizer.encode(v)).collect();
+
+    let mut total = 0;
+    let mut within = 0;
+    let mut relative_error = 0.0;
+    for q in &queries {
+        let prepared = quantizer.prepare_query(q);
+        for (v, code) in data.iter().zip(codes.iter()) {
+            let exact = Distance::SquaredEuclidean.compute(&v.data, &q.data);
+            let estimate = quantizer.estimate_distance(code, &prepared);
+            assert!(estimate.lower <= estimate.distance && estimate.distance <= estimate.upper);
+            if estimate.lower <= exact && exact <= estimate.upper {
+                within += 1;
+            }
+            relative_error += (estimate.distance - exact).abs() / exact;
+            total += 1;
+        }
+    }
+    assert!(
+        within as f32 / total as f32 > 0.9,
+        "Only {} of {} exact distances fall within the bounds",
+        within,
+        total
+    );
+    let mean_relative_error = relative_error / total as f32;
+    assert!(
+        mean_relative_error < 0.1,
+        "Mean relative error {} is too large",
+        mean_relative_error
+    );
+}
+
+#[test]
+#[should_panic(expected = "Dimension mismatch")]
+fn test_rabitq_dimension_mismatch() {
+    let mut rng = seeded_rng();
+    let training_data = generate_test_data(&mut rng, 20, 8);
+    let quantizer = RaBitQuantizer::fit(&training_data, 42);
+    quantizer.encode(&Vector::new(vec![1.0, 2.0]));
+}
@@ -2,9 +2,11 @@
 mod utils;
 
 use half::f16;
-use utils::{generate_test_data, seeded_rng};
+use utils::{generate_test_data, mean_squared_error, seeded_rng};
 use vq::distances::Distance;
-use vq::rvq::ResidualQuantizer;
+use vq::packing::PackedCodes;
+use vq::rvq::{RateControl, ResidualQuantizer};
+use vq::vector::Vector;
 
 #[test]
 fn test_rvq_dimension() {
@@ -60,3 +62,318 @@ fn test_rvq_reconstruction_error() {
         assert!(total_error.is_finite());
     }
 }
+
+#[test]
+fn test_rvq_fit_with_report() {
+    let mut rng = seeded_rng();
+    let training_data = generate_test_data(&mut rng, 500, 10);
+    let (_, report) = ResidualQuantizer::fit_with_report(
+        &training_data,
+        3,
+        4,
+        50,
+        1e-6,
+        Distance::SquaredEuclidean,
+        42,
+    );
+    assert_eq!(report.rounds.len(), 3);
+    // Each stage should not increase the reconstruction error.
+    for pair in report.rounds.windows(2) {
+        assert!(pair[1].distortion <= pair[0].distortion * 1.0001);
+    }
+}
+
+#[test]
+fn test_rvq_fit_with_observer_reports_stages() {
+    use std::sync::Mutex;
+    use vq::progress::{ProgressEvent, TrainingControl};
+
+    let mut rng = seeded_rng();
+    let training_data = generate_test_data(&mut rng, 200, 10);
+    let rounds = Mutex::new(Vec::new());
+    let observer = |event: &ProgressEvent| {
+        if let ProgressEvent::Round { round, .. } = event {
+            rounds.lock().unwrap().push(*round);
+        }
+        TrainingControl::Continue
+    };
+    let result = ResidualQuantizer::fit_with_observer(
+        &training_data,
+        3,
+        4,
+        20,
+        1e-6,
+        Distance::SquaredEuclidean,
+        42,
+        &observer,
+    );
+    assert!(result.is_ok());
+    assert_eq!(*rounds.lock().unwrap(), vec![0, 1, 2]);
+}
+
+#[test]
+fn test_rvq_refine_on_new_data() {
+    let mut rng = seeded_rng();
+    let training_data = generate_test_data(&mut rng, 300, 10);
+    let rvq = ResidualQuantizer::fit(
+        &training_data,
+        3,
+        4,
+        50,
+        1e-6,
+        Distance::SquaredEuclidean,
+        42,
+    );
+    let new_data = generate_test_data(&mut rng, 300, 10);
+    let refined = rvq.refine(&new_data, 5, 42);
+    assert_eq!(refined.codebooks().len(), rvq.codebooks().len());
+    for vector in new_data.iter() {
+        assert_eq!(refined.quantize(vector).len(), vector.len());
+    }
+}
+
+#[test]
+fn test_rvq_encode_matches_quantize() {
+    let mut rng = seeded_rng();
+    let training_data = generate_test_data(&mut rng, 500, 10);
+    let rvq = ResidualQuantizer::fit(
+        &training_data,
+        3,
+        4,
+        20,
+        1e-6,
+        Distance::SquaredEuclidean,
+        42,
+    );
+    for vector in training_data.iter().take(20) {
+        let codes = rvq.encode(vector);
+        assert_eq!(codes.bits(), 2);
+        assert!(codes.len() <= 3);
+        let decoded = rvq.decode(&codes);
+        let quantized = rvq.quantize(vector);
+        for (&d, &q) in decoded.data.iter().zip(quantized.data.iter()) {
+            assert_eq!(f16::from_f32(d), q);
+        }
+    }
+}
+
+#[test]
+fn test_rvq_beam_search_reduces_error() {
+    let mut rng = seeded_rng();
+    let training_data = generate_test_data(&mut rng, 500, 8);
+    let greedy = ResidualQuantizer::fit(
+        &training_data,
+        6,
+        8,
+        20,
+        0.0,
+        Distance::SquaredEuclidean,
+        42,
+    );
+    let beam = greedy.with_beam_width(8);
+    assert_eq!(beam.beam_width(), 8);
+    let greedy_error = mean_squared_error(&training_data, |v| greedy.quantize(v));
+    let beam_error = mean_squared_error(&training_data, |v| beam.quantize(v));
+    assert!(
+        beam_error < greedy_error,
+        "Beam error {} should be below greedy error {}",
+        beam_error,
+        greedy_error
+    );
+    for v in training_data.iter().take(10) {
+        let decoded: Vec<f16> = beam
+            .decode(&beam.encode(v))
+            .data
+            .iter()
+            .map(|&x| f16::from_f32(x))
+            .collect();
+        assert_eq!(decoded, beam.quantize(v).data);
+    }
+}
+
+#[test]
+fn test_rvq_fit_with_beam_trains_on_beam_residuals() {
+    let mut rng = seeded_rng();
+    let training_data = generate_test_data(&mut rng, 500, 8);
+    let greedy = ResidualQuantizer::fit(
+        &training_data,
+        6,
+        8,
+        20,
+        0.0,
+        Distance::SquaredEuclidean,
+        42,
+    );
+    let beam = ResidualQuantizer::fit_with_beam(
+        &training_data,
+        6,
+        8,
+        20,
+        0.0,
+        8,
+        Distance::SquaredEuclidean,
+        42,
+    );
+    assert_eq!(beam.codebooks().len(), 6);
+    // Beam search over a single stage is greedy, so the first two codebooks are learned on the
+    // same residuals and only the later ones differ.
+    assert_eq!(beam.codebooks()[1], greedy.codebooks()[1]);
+    assert_ne!(beam.codebooks()[2], greedy.codebooks()[2]);
+    assert!(
+        mean_squared_error(&training_data, |v| beam.quantize(v))
+            < mean_squared_error(&training_data, |v| greedy.quantize(v))
+    );
+    assert_eq!(beam.refine(&training_data, 2, 7).beam_width(), 8);
+}
+
+#[test]
+#[should_panic(expected = "beam_width must be greater than 0")]
+fn test_rvq_zero_beam_width() {
+    let mut rng = seeded_rng();
+    let training_data = generate_test_data(&mut rng, 50, 4);
+    ResidualQuantizer::fit_with_beam(
+        &training_data,
+        2,
+        4,
+        10,
+        0.0,
+        0,
+        Distance::SquaredEuclidean,
+        42,
+    );
+}
+
+#[test]
+fn test_rvq_fine_tune_reduces_error() {
+    let mut rng = seeded_rng();
+    let training_data = generate_test_data(&mut rng, 500, 8);
+    let rq = ResidualQuantizer::fit_with_beam(
+        &training_data,
+        6,
+        8,
+        20,
+        0.0,
+        4,
+        Distance::SquaredEuclidean,
+        42,
+    );
+    let tuned = rq.fine_tune(&training_data, 3);
+    assert_eq!(tuned.codebooks().len(), rq.codebooks().len());
+    assert_eq!(
+        tuned.encode(&training_data[0]).len(),
+        rq.encode(&training_data[0]).len()
+    );
+    let before = mean_squared_error(&training_data, |v| rq.quantize(v));
+    let after = mean_squared_error(&training_data, |v| tuned.quantize(v));
+    assert!(
+        after < before,
+        "Fine-tuned error {} should be below {}",
+        after,
+        before
+    );
+}
+
+#[test]
+#[should_panic(expected = "Dimension mismatch")]
+fn test_rvq_fine_tune_dimension_mismatch() {
+    let mut rng = seeded_rng();
+    let training_data = generate_test_data(&mut rng, 100, 4);
+    let rq = ResidualQuantizer::fit(
+        &training_data,
+        2,
+        4,
+        10,
+        0.0,
+        Distance::SquaredEuclidean,
+        42,
+    );
+    rq.fine_tune(&generate_test_data(&mut rng, 100, 5), 1);
+}
+
+#[test]
+fn test_rvq_rate_control_fixed_stages() {
+    let mut rng = seeded_rng();
+    let training_data = generate_test_data(&mut rng, 300, 6);
+    let rq = ResidualQuantizer::fit(&training_data, 4, 8, 20, 0.0, Distance::Euclidean, 42);
+    let batch = &training_data[..20];
+    let full = rq.encode_batch(batch, RateControl::Stages(10));
+    let coarse = rq.encode_batch(batch, RateControl::Stages(2));
+    for (f, c) in full.iter().zip(coarse.iter()) {
+        assert_eq!(f.len(), 4);
+        assert_eq!(c.len(), 2);
+        // Lower quality levels are prefixes of the full codes.
+        assert_eq!(c.to_vec(), f.to_vec()[..2]);
+    }
+    assert_eq!(full[0], rq.encode(&batch[0]));
+}
+
+#[test]
+fn test_rvq_rate_control_relative_error() {
+    let mut rng = seeded_rng();
+    let training_data = generate_test_data(&mut rng, 300, 6);
+    let rq = ResidualQuantizer::fit(&training_data, 6, 16, 20, 0.0, Distance::Euclidean, 42);
+    let target = 0.5;
+    let codes = rq.encode_batch(&training_data, RateControl::RelativeError(target));
+    let distance = Distance::Euclidean;
+    for (v, c) in training_data.iter().zip(codes.iter()) {
+        let zero = vec![0.0; v.len()];
+        let error = distance.compute(&v.data, &rq.decode(c).data);
+        let reference = distance.compute(&v.data, &zero);
+        if c.len() < 6 {
+            assert!(error <= target * reference);
+        }
+        if !c.is_empty() {
+            // One stage fewer would not have been enough.
+            let shorter = PackedCodes::from_codes(c.bits(), c.iter().take(c.len() - 1));
+            assert!(distance.compute(&v.data, &rq.decode(&shorter).data) > target * reference);
+        }
+    }
+}
+
+#[test]
+fn test_rvq_rate_control_bit_budget() {
+    let mut rng = seeded_rng();
+    let training_data = generate_test_data(&mut rng, 300, 6);
+    let rq = ResidualQuantizer::fit(&training_data, 4, 16, 20, 0.0, Distance::Euclidean, 42);
+    let batch = &training_data[..50];
+    // 4 bits per stage and an average of 2 stages per vector.
+    let codes = rq.encode_batch(batch, RateControl::BitBudget(50 * 2 * 4));
+    let stages: usize = codes.iter().map(|c| c.len()).sum();
+    assert_eq!(stages, 100);
+    assert!(codes.iter().any(|c| c.len() != 2));
+
+    let error = |codes: &[PackedCodes]| {
+        batch
+            .iter()
+            .zip(codes.iter())
+            .map(|(v, c)| Distance::Euclidean.compute(&v.data, &rq.decode(c).data))
+            .sum::<f32>()
+    };
+    let uniform = rq.encode_batch(batch, RateControl::Stages(2));
+    assert!(error(&codes) <= error(&uniform));
+}
+
+#[test]
+#[should_panic(expected = "Target relative error must be finite and non-negative")]
+fn test_rvq_rate_control_invalid_target() {
+    let mut rng = seeded_rng();
+    let training_data = generate_test_data(&mut rng, 50, 4);
+    let rq = ResidualQuantizer::fit(&training_data, 2, 4, 10, 0.0, Distance::Euclidean, 42);
+    rq.encode_batch(&training_data, RateControl::RelativeError(-0.1));
+}
+
+#[test]
+fn test_rvq_cosine_normalizes_only_first_stage() {
+    let mut rng = seeded_rng();
+    let training_data = generate_test_data(&mut rng, 500, 8);
+    let rvq = ResidualQuantizer::fit(&training_data, 3, 4, 20, 0.0, Distance::CosineDistance, 42);
+    let norm = |c: &Vector<f32>| c.data.iter().map(|x| x * x).sum::<f32>().sqrt();
+    for centroid in &rvq.codebooks()[0] {
+        assert!((norm(centroid) - 1.0).abs() < 1e-4);
+    }
+    // Later stages quantize residuals and keep their magnitude.
+    assert!(rvq.codebooks()[1..]
+        .iter()
+        .flatten()
+        .any(|c| (norm(c) - 1.0).abs() > 1e-2));
+}
@@ -14,13 +14,7 @@ fn test_scalar_quantizer_on_scalars() {
         let indices = quantizer.quantize(&vec_x);
         assert_eq!(indices.len(), 1, "Expected one index per scalar vector");
         let reconstructed = quantizer.min + indices.data[0] as f32 * quantizer.step;
-        let clamped = if x < -1.0 {
-            -1.0
-        } else if x > 1.0 {
-            1.0
-        } else {
-            x
-        };
+        let clamped = x.clamp(-1.0, 1.0);
         let error = (reconstructed - clamped).abs();
         let max_error = quantizer.step / 2.0;
         assert!(
@@ -58,13 +52,7 @@ fn test_scalar_quantizer_on_large_vectors() {
             "Reconstructed vector length should match input dimension"
         );
         for (&orig, &recon) in vector.data.iter().zip(reconstructed.iter()) {
-            let clamped = if orig < quantizer.min {
-                quantizer.min
-            } else if orig > quantizer.max {
-                quantizer.max
-            } else {
-                orig
-            };
+            let clamped = orig.clamp(quantizer.min, quantizer.max);
             let error = (clamped - recon).abs();
             assert!(
                 error <= quantizer.step / 2.0 + 1e-6,
@@ -75,3 +63,37 @@ fn test_scalar_quantizer_on_large_vectors() {
         }
     }
 }
+
+#[test]
+fn test_scalar_quantizer_encode_more_than_256_levels() {
+    let mut rng = seeded_rng();
+    let data = generate_test_data(&mut rng, 10, 64);
+    let quantizer = ScalarQuantizer::fit_wide(-1000.0, 1000.0, 4096);
+
+    for vector in data.iter() {
+        let codes = quantizer.encode(vector);
+        assert_eq!(codes.bits(), 12);
+        assert_eq!(codes.len(), 64);
+        assert_eq!(codes.to_bytes().len(), 96);
+        let reconstructed = quantizer.decode(&codes);
+        for (&orig, &recon) in vector.data.iter().zip(reconstructed.data.iter()) {
+            let error = (orig.clamp(quantizer.min, quantizer.max) - recon).abs();
+            assert!(error <= quantizer.step / 2.0 + 1e-3);
+        }
+    }
+}
+
+#[test]
+#[should_panic(expected = "no more than 256")]
+fn test_scalar_quantizer_fit_rejects_wide_levels() {
+    ScalarQuantizer::fit(0.0, 1.0, 1000);
+}
+
+#[test]
+fn test_scalar_quantizer_quantize_wide_matches_encode() {
+    let quantizer = ScalarQuantizer::fit_wide(0.0, 1.0, 1000);
+    let input = Vector::new(vec![-0.5, 0.25, 0.5, 1.5]);
+    let codes = quantizer.quantize_wide(&input);
+    assert_eq!(codes, vec![0, 250, 500, 999]);
+    assert_eq!(quantizer.encode(&input).iter().collect::<Vec<_>>(), codes);
+}
@@ -0,0 +1,83 @@
+#[path = "utils.rs"]
+mod utils;
+
+use utils::{generate_test_data, seeded_rng};
+use vq::ternary::{TernaryPacking, TernaryQuantizer, ThresholdMode};
+use vq::vector::Vector;
+
+#[test]
+fn test_ternary_learned_threshold() {
+    // Small values around zero and large values around ±1: the dead zone separates them.
+    let data: Vec<Vector<f32>> = [-1.0, -0.9, -0.05, 0.0, 0.02, 0.04, 0.95, 1.1]
+        .iter()
+        .map(|&x| Vector::new(vec![x]))
+        .collect();
+    let quantizer = TernaryQuantizer::fit(&data, ThresholdMode::Global, TernaryPacking::TwoBit);
+    let threshold = quantizer.thresholds()[0];
+    assert!(
+        threshold > 0.05 && threshold < 0.9,
+        "threshold {}",
+        threshold
+    );
+    assert!((quantizer.scales()[0] - 0.9875).abs() < 1e-5);
+    let ternary = quantizer.quantize(&Vector::new(vec![0.5]));
+    assert_eq!(ternary.data, vec![1]);
+}
+
+#[test]
+fn test_ternary_packing_round_trip() {
+    let mut rng = seeded_rng();
+    let data = generate_test_data(&mut rng, 50, 13);
+    for packing in [TernaryPacking::TwoBit, TernaryPacking::Base3] {
+        let quantizer = TernaryQuantizer::fit(&data, ThresholdMode::PerDimension, packing);
+        for v in &data {
+            let codes = quantizer.encode(v);
+            assert_eq!(codes.bits(), 2);
+            assert_eq!(codes.len(), 13);
+            let bytes = quantizer.pack(&codes);
+            assert_eq!(bytes.len(), 13usize.div_ceil(packing.values_per_byte()));
+            assert_eq!(quantizer.unpack(&bytes), codes);
+            if packing == TernaryPacking::TwoBit {
+                assert_eq!(bytes, codes.to_bytes());
+            }
+            let ternary = quantizer.quantize(v);
+            let expected: Vec<f32> = ternary
+                .data
+                .iter()
+                .zip(quantizer.scales().iter())
+                .map(|(&t, &s)| t as f32 * s)
+                .collect();
+            assert_eq!(quantizer.decode(&codes).data, expected);
+        }
+    }
+    assert_eq!(TernaryPacking::Base3.bits_per_dimension(), 1.6);
+}
+
+#[test]
+fn test_ternary_dot_matches_decoded() {
+    let mut rng = seeded_rng();
+    let data = generate_test_data(&mut rng, 50, 64);
+    let query = generate_test_data(&mut rng, 1, 64).remove(0);
+    let quantizer = TernaryQuantizer::fit(&data, ThresholdMode::Global, TernaryPacking::Base3);
+    for v in &data {
+        let codes = quantizer.encode(v);
+        let decoded = quantizer.decode(&codes);
+        let expected: f32 = decoded
+            .data
+            .iter()
+            .zip(query.data.iter())
+            .map(|(a, b)| a * b)
+            .sum();
+        let dot = quantizer.dot(&quantizer.pack(&codes), &query);
+        assert!((dot - expected).abs() <= 1e-3 * expected.abs().max(1.0));
+    }
+}
+
+#[test]
+#[should_panic(expected = "Dimension mismatch")]
+fn test_ternary_code_length_mismatch() {
+    let mut rng = seeded_rng();
+    let data = generate_test_data(&mut rng, 10, 8);
+    let quantizer = TernaryQuantizer::fit(&data, ThresholdMode::Global, TernaryPacking::TwoBit);
+    quantizer.unpack(&[0u8; 3]);
+}
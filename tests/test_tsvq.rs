@@ -2,9 +2,9 @@
 mod utils;
 
 use half::f16;
-use utils::{generate_test_data, seeded_rng};
+use utils::{generate_test_data, mean_squared_error, seeded_rng};
 use vq::distances::Distance;
-use vq::tsvq::TSVQ;
+use vq::tsvq::{PruneTarget, SplitStrategy, Traversal, TSVQ};
 use vq::vector::Vector;
 
 #[test]
@@ -40,3 +40,253 @@ fn test_tsvq_on_random_vectors() {
         assert!(total_error.is_finite());
     }
 }
+
+#[test]
+fn test_tsvq_encode_path() {
+    let mut rng = seeded_rng();
+    let training_data = generate_test_data(&mut rng, 200, 6);
+    let tsvq = TSVQ::new(&training_data, 4, Distance::SquaredEuclidean);
+    for vector in training_data.iter().take(20) {
+        let path = tsvq.encode(vector);
+        assert_eq!(path.bits(), 1);
+        assert_eq!(path.len(), 4);
+        let decoded = tsvq.decode(&path);
+        let quantized = tsvq.quantize(vector);
+        for (&d, &q) in decoded.data.iter().zip(quantized.data.iter()) {
+            assert_eq!(f16::from_f32(d), q);
+        }
+    }
+}
+
+/// Vectors whose coordinates are strongly correlated, spread along the diagonal.
+fn correlated_data(n: usize, dim: usize) -> Vec<Vector<f32>> {
+    let mut rng = seeded_rng();
+    generate_test_data(&mut rng, n, dim)
+        .into_iter()
+        .map(|v| {
+            let t = v.data[0];
+            Vector::new(v.data.iter().map(|&x| t + 0.05 * x).collect())
+        })
+        .collect()
+}
+
+#[test]
+fn test_tsvq_split_strategies_encode_consistently() {
+    let mut rng = seeded_rng();
+    let training_data = generate_test_data(&mut rng, 300, 6);
+    let strategies = [
+        SplitStrategy::AxisMedian,
+        SplitStrategy::TwoMeans {
+            max_iters: 20,
+            seed: 42,
+        },
+        SplitStrategy::PrincipalDirection,
+    ];
+    for strategy in strategies {
+        let tsvq = TSVQ::with_split_strategy(&training_data, 4, Distance::Euclidean, strategy);
+        for vector in training_data.iter().take(20) {
+            let path = tsvq.encode(vector);
+            assert_eq!(path.len(), 4);
+            let decoded: Vec<f16> = tsvq
+                .decode(&path)
+                .data
+                .iter()
+                .map(|&x| f16::from_f32(x))
+                .collect();
+            assert_eq!(decoded, tsvq.quantize(vector).data);
+        }
+    }
+}
+
+#[test]
+fn test_tsvq_data_adaptive_splits_on_correlated_data() {
+    let training_data = correlated_data(500, 8);
+    let error = |strategy| {
+        let tsvq = TSVQ::with_split_strategy(&training_data, 4, Distance::Euclidean, strategy);
+        mean_squared_error(&training_data, |v| tsvq.quantize(v))
+    };
+    let axis = error(SplitStrategy::AxisMedian);
+    let principal = error(SplitStrategy::PrincipalDirection);
+    let two_means = error(SplitStrategy::TwoMeans {
+        max_iters: 50,
+        seed: 42,
+    });
+    assert!(
+        principal < axis,
+        "Principal direction error {} should be below axis median error {}",
+        principal,
+        axis
+    );
+    assert!(
+        two_means < axis,
+        "2-means error {} should be below axis median error {}",
+        two_means,
+        axis
+    );
+}
+
+#[test]
+#[should_panic(expected = "Unsupported distance metric")]
+fn test_tsvq_two_means_unsupported_distance() {
+    let mut rng = seeded_rng();
+    let training_data = generate_test_data(&mut rng, 50, 4);
+    TSVQ::with_split_strategy(
+        &training_data,
+        2,
+        Distance::Chebyshev,
+        SplitStrategy::TwoMeans {
+            max_iters: 10,
+            seed: 42,
+        },
+    );
+}
+
+#[test]
+fn test_tsvq_principal_direction_is_not_largest_variance_axis() {
+    // Coordinate 0 has the largest variance but is uncorrelated, so it is an exact eigenvector.
+    // Coordinates 1 and 2 are perfectly correlated and together vary more along (0, 1, 1).
+    let training_data: Vec<Vector<f32>> = (0..200)
+        .map(|i| {
+            let a = if i % 2 == 0 { 1.0 } else { -1.0 };
+            let b = if (i / 2) % 2 == 0 { 0.9 } else { -0.9 };
+            Vector::new(vec![a, b, b])
+        })
+        .collect();
+    let tsvq = TSVQ::with_split_strategy(
+        &training_data,
+        1,
+        Distance::SquaredEuclidean,
+        SplitStrategy::PrincipalDirection,
+    );
+    // The split follows the correlated coordinates, not coordinate 0.
+    let route = |x: [f32; 3]| tsvq.encode(&Vector::new(x.to_vec()));
+    assert_eq!(route([5.0, 1.0, 1.0]), route([-5.0, 1.0, 1.0]));
+    assert_ne!(route([5.0, 1.0, 1.0]), route([5.0, -1.0, -1.0]));
+}
+
+#[test]
+fn test_tsvq_traversals_find_nearer_leaves() {
+    let mut rng = seeded_rng();
+    let training_data = generate_test_data(&mut rng, 500, 8);
+    let tsvq = TSVQ::new(&training_data, 6, Distance::SquaredEuclidean);
+    let queries = generate_test_data(&mut rng, 50, 8);
+
+    let mean_distance = |traversal| {
+        queries
+            .iter()
+            .map(|q| tsvq.nearest_leaves(q, traversal, 1)[0].1)
+            .sum::<f32>()
+            / queries.len() as f32
+    };
+    let greedy = mean_distance(Traversal::Greedy);
+    let beam = mean_distance(Traversal::Beam { beam_width: 8 });
+    let best_first = mean_distance(Traversal::BestFirst { max_nodes: 64 });
+    assert!(beam < greedy, "Beam {} should beat greedy {}", beam, greedy);
+    assert!(
+        best_first < greedy,
+        "Best-first {} should beat greedy {}",
+        best_first,
+        greedy
+    );
+
+    for q in queries.iter().take(10) {
+        // A one-node budget of best-first search reaches only the greedy leaf.
+        assert_eq!(
+            tsvq.encode_with_traversal(q, Traversal::BestFirst { max_nodes: 1 }),
+            tsvq.encode(q)
+        );
+        assert_eq!(
+            tsvq.nearest_leaves(q, Traversal::BestFirst { max_nodes: 1 }, usize::MAX)
+                .len(),
+            1
+        );
+        // A beam as wide as the tree finds the nearest leaf of all.
+        let all = tsvq.nearest_leaves(q, Traversal::Beam { beam_width: 64 }, usize::MAX);
+        assert!(all.windows(2).all(|w| w[0].1 <= w[1].1));
+        let top = tsvq.nearest_leaves(q, Traversal::Beam { beam_width: 64 }, 3);
+        assert_eq!(top.len(), 3);
+        assert_eq!(top[..], all[..3]);
+        let quantized = tsvq.quantize_with_traversal(q, Traversal::Beam { beam_width: 64 });
+        let nearest: Vec<f16> = tsvq
+            .decode(&all[0].0)
+            .data
+            .iter()
+            .map(|&x| f16::from_f32(x))
+            .collect();
+        assert_eq!(quantized.data, nearest);
+    }
+}
+
+#[test]
+#[should_panic(expected = "beam_width must be greater than 0")]
+fn test_tsvq_zero_beam_width() {
+    let mut rng = seeded_rng();
+    let training_data = generate_test_data(&mut rng, 50, 4);
+    let tsvq = TSVQ::new(&training_data, 3, Distance::Euclidean);
+    tsvq.nearest_leaves(&training_data[0], Traversal::Beam { beam_width: 0 }, 1);
+}
+
+#[test]
+fn test_tsvq_prune_to_leaves() {
+    let training_data = correlated_data(500, 6);
+    let deep = TSVQ::new(&training_data, 8, Distance::SquaredEuclidean);
+    let pruned = deep.prune(PruneTarget::Leaves(16));
+    assert!(pruned.leaf_count() <= 16);
+    assert!(pruned.leaf_count() < deep.leaf_count());
+    assert!(pruned.distortion() >= deep.distortion());
+    assert_eq!(deep.prune(PruneTarget::Leaves(1)).leaf_count(), 1);
+    // BFOS pruning is nested: pruning in two steps gives the same tree as pruning at once.
+    let twice = deep
+        .prune(PruneTarget::Leaves(32))
+        .prune(PruneTarget::Leaves(16));
+    assert_eq!(twice.leaf_count(), pruned.leaf_count());
+    assert_eq!(twice.distortion(), pruned.distortion());
+    assert_eq!(twice.average_rate(), pruned.average_rate());
+
+    // Pruned trees still encode and decode consistently, with paths of varying length.
+    for vector in training_data.iter().take(20) {
+        let path = pruned.encode(vector);
+        let decoded: Vec<f16> = pruned
+            .decode(&path)
+            .data
+            .iter()
+            .map(|&x| f16::from_f32(x))
+            .collect();
+        assert_eq!(decoded, pruned.quantize(vector).data);
+    }
+}
+
+#[test]
+fn test_tsvq_pruning_beats_fixed_depth() {
+    let mut rng = seeded_rng();
+    // A wide cluster next to a tight one: extra depth pays off only in the wide cluster.
+    let training_data: Vec<Vector<f32>> = generate_test_data(&mut rng, 1000, 6)
+        .into_iter()
+        .enumerate()
+        .map(|(i, v)| {
+            if i % 4 == 0 {
+                v
+            } else {
+                Vector::new(v.data.iter().map(|&x| 5000.0 + 0.01 * x).collect())
+            }
+        })
+        .collect();
+    let fixed = TSVQ::new(&training_data, 4, Distance::SquaredEuclidean);
+    let pruned = TSVQ::new(&training_data, 10, Distance::SquaredEuclidean)
+        .prune(PruneTarget::AverageRate(fixed.average_rate()));
+    assert!(pruned.average_rate() <= fixed.average_rate());
+    assert!(
+        pruned.distortion() < fixed.distortion(),
+        "Pruned distortion {} should be below fixed-depth distortion {}",
+        pruned.distortion(),
+        fixed.distortion()
+    );
+}
+
+#[test]
+#[should_panic(expected = "Target number of leaves must be at least 1")]
+fn test_tsvq_prune_to_zero_leaves() {
+    let mut rng = seeded_rng();
+    let training_data = generate_test_data(&mut rng, 50, 4);
+    TSVQ::new(&training_data, 3, Distance::Euclidean).prune(PruneTarget::Leaves(0));
+}
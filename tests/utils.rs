@@ -3,7 +3,7 @@
 use rand::rngs::StdRng;
 use rand::Rng;
 use rand::SeedableRng;
-use vq::vector::Vector;
+use vq::vector::{Real, Vector};
 
 pub const SEED: u64 = 42;
 pub const MIN_VAL: f32 = -1000.0;
@@ -23,3 +23,20 @@ fn generate_random_vector<R: Rng>(rng: &mut R, dim: usize) -> Vector<f32> {
 pub fn generate_test_data<R: Rng>(rng: &mut R, n: usize, dim: usize) -> Vec<Vector<f32>> {
     (0..n).map(|_| generate_random_vector(rng, dim)).collect()
 }
+
+/// Returns the mean squared Euclidean distance between the vectors and their reconstructions.
+pub fn mean_squared_error<T: Real + Into<f32>>(
+    data: &[Vector<f32>],
+    reconstruct: impl Fn(&Vector<f32>) -> Vector<T>,
+) -> f32 {
+    data.iter()
+        .map(|v| {
+            v.data
+                .iter()
+                .zip(reconstruct(v).data)
+                .map(|(&x, y)| (x - y.into()).powi(2))
+                .sum::<f32>()
+        })
+        .sum::<f32>()
+        / data.len() as f32
+}